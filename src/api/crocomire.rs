@@ -1,6 +1,7 @@
 use reqwest;
 use serde::Deserialize;
 use serde_json;
+use crate::api::SendMetered;
 
 #[derive(Deserialize, Debug)]
 #[allow(dead_code)]
@@ -25,7 +26,7 @@ impl Strategy {
         let reqclient = reqwest::Client::new();
         let response = reqclient
             .get(format!("https://crocomi.re/api/strats/{}", strat).as_str())
-            .send()
+            .send_metered()
             .await?;
         let body = response.text().await?;
         let data: serde_json::Value = serde_json::from_str(&body)?;
@@ -1,6 +1,7 @@
 use reqwest;
 use serde::Deserialize;
 use serde_json;
+use crate::api::SendMetered;
 
 #[derive(Deserialize, Debug)]
 #[allow(dead_code)]
@@ -32,7 +33,7 @@ impl DeerTierRecord {
         let reqclient = reqwest::Client::new();
         let response = reqclient
             .get("https://deertier.com/api/records")
-            .send()
+            .send_metered()
             .await?;
         let body = response.text().await?;
         let records: Vec<DeerTierRecord> = serde_json::from_str(&body)?;
@@ -1,14 +1,31 @@
 #![allow(dead_code)]
 
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::future::{BoxFuture, FutureExt};
+use futures::stream::{BoxStream, StreamExt};
 use reqwest::header::USER_AGENT;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
+use crate::api::SendMetered;
 use crate::Error;
 
 const AGENT: &str = "shaktool-rs/2.0";
 const DEFAULT_OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
 const DEFAULT_ANTHROPIC_BASE_URL: &str = "https://api.anthropic.com/v1";
 const DEFAULT_ANTHROPIC_VERSION: &str = "2023-06-01";
+/// Texts per embeddings request unless `LLM_EMBEDDING_BATCH_SIZE` says
+/// otherwise; OpenAI takes up to 2048, local servers often far fewer.
+const DEFAULT_EMBEDDING_BATCH_SIZE: usize = 64;
+
+/// Longest wait to connect to the backend.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest wait for more of a response, including between the chunks of a
+/// streamed one; a whole answer can take longer.
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum LlmVendor {
@@ -35,50 +52,113 @@ impl LlmVendor {
             Self::OpenAiCompatible => "openai-compatible",
         }
     }
+
+    /// Whether the vendor's API has an embeddings endpoint.
+    pub fn has_embeddings(self) -> bool {
+        match self {
+            Self::OpenAi | Self::OpenAiCompatible => true,
+            Self::Anthropic => false,
+        }
+    }
 }
 
-#[derive(Clone, Copy, Debug, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum LlmRole {
     System,
     User,
     Assistant,
+    /// The result of a tool call, answering [`LlmMessage::tool_call_id`].
+    Tool,
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug)]
 pub struct LlmMessage {
     pub role: LlmRole,
     pub content: String,
+    /// Tools the assistant asked to call in this turn.
+    pub tool_calls: Vec<LlmToolCall>,
+    /// For [`LlmRole::Tool`] messages, the call this is the result of.
+    pub tool_call_id: Option<String>,
 }
 
 impl LlmMessage {
-    pub fn system(content: impl Into<String>) -> Self {
+    fn new(role: LlmRole, content: impl Into<String>) -> Self {
         Self {
-            role: LlmRole::System,
+            role,
             content: content.into(),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
         }
     }
 
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new(LlmRole::System, content)
+    }
+
     pub fn user(content: impl Into<String>) -> Self {
+        Self::new(LlmRole::User, content)
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::new(LlmRole::Assistant, content)
+    }
+
+    /// The result of running the tool call `call_id`.
+    pub fn tool_result(call_id: impl Into<String>, content: impl Into<String>) -> Self {
         Self {
-            role: LlmRole::User,
-            content: content.into(),
+            tool_call_id: Some(call_id.into()),
+            ..Self::new(LlmRole::Tool, content)
         }
     }
+}
 
-    pub fn assistant(content: impl Into<String>) -> Self {
+/// A function the model may call, with a JSON schema of its arguments.
+#[derive(Clone, Debug)]
+pub struct LlmTool {
+    pub name: String,
+    pub description: String,
+    /// JSON schema of the arguments object.
+    pub parameters: Value,
+}
+
+impl LlmTool {
+    pub fn new(name: impl Into<String>, description: impl Into<String>, parameters: Value) -> Self {
         Self {
-            role: LlmRole::Assistant,
-            content: content.into(),
+            name: name.into(),
+            description: description.into(),
+            parameters,
         }
     }
 }
 
+/// A call of one of the request's tools, asked for by the model.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LlmToolCall {
+    pub id: String,
+    pub name: String,
+    /// The arguments object, supposed to match the tool's schema.
+    pub arguments: Value,
+}
+
+/// Runs the tool calls of [`LlmClient::chat_with_tools`].
+#[async_trait]
+pub trait ToolHandler: Send + Sync {
+    /// Runs `call` and returns what to tell the model. An error is passed on
+    /// to the model as the result, so it can retry or answer without it.
+    async fn call(&self, call: &LlmToolCall) -> Result<String, Error>;
+}
+
 #[derive(Clone, Debug)]
 pub struct LlmRequest {
     pub messages: Vec<LlmMessage>,
     pub temperature: Option<f32>,
     pub max_tokens: Option<u32>,
+    pub tools: Vec<LlmTool>,
+    /// Sends the tools but forbids calling them (`tool_choice` none), for a
+    /// last answer in a conversation that already used them; backends
+    /// reject tool calls in the history of a request without tools.
+    pub forbid_tool_calls: bool,
 }
 
 impl LlmRequest {
@@ -87,9 +167,16 @@ impl LlmRequest {
             messages,
             temperature: None,
             max_tokens: None,
+            tools: Vec::new(),
+            forbid_tool_calls: false,
         }
     }
 
+    pub fn with_tools(mut self, tools: Vec<LlmTool>) -> Self {
+        self.tools = tools;
+        self
+    }
+
     pub fn with_temperature(mut self, temperature: f32) -> Self {
         self.temperature = Some(temperature);
         self
@@ -101,37 +188,114 @@ impl LlmRequest {
     }
 }
 
+/// Tokens a call used, as billed by the vendor.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LlmUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+impl LlmUsage {
+    pub fn total(self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+
+    /// Combines counts reported cumulatively: each is the latest (largest)
+    /// one, and a count missing from a report (0) keeps the earlier one.
+    pub fn latest(self, other: Self) -> Self {
+        Self {
+            input_tokens: self.input_tokens.max(other.input_tokens),
+            output_tokens: self.output_tokens.max(other.output_tokens),
+        }
+    }
+}
+
+impl std::ops::AddAssign for LlmUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+    }
+}
+
+/// A piece of a streamed answer (see [`LlmClient::chat_stream`]).
+#[derive(Clone, Debug, PartialEq)]
+pub enum LlmDelta {
+    Text(String),
+    /// Tokens used so far; each report replaces the one before.
+    Usage(LlmUsage),
+}
+
 #[derive(Clone, Debug)]
 pub struct LlmResponse {
     pub text: String,
+    /// Tools the model wants called before it answers; run them and send
+    /// [`LlmResponse::message`] and their [`LlmMessage::tool_result`]s back.
+    pub tool_calls: Vec<LlmToolCall>,
+    pub usage: LlmUsage,
     pub vendor: LlmVendor,
     pub model: String,
 }
 
+impl LlmResponse {
+    /// The response as an assistant message, to continue the conversation.
+    pub fn message(&self) -> LlmMessage {
+        LlmMessage {
+            tool_calls: self.tool_calls.clone(),
+            ..LlmMessage::assistant(self.text.clone())
+        }
+    }
+}
+
+/// Vectors returned by [`LlmClient::embed`], one per input text in order.
+#[derive(Clone, Debug)]
+pub struct Embeddings {
+    pub vectors: Vec<Vec<f32>>,
+    pub model: String,
+    /// Length of every vector; 0 when nothing was embedded.
+    pub dimensions: usize,
+    pub usage: LlmUsage,
+}
+
 #[derive(Clone, Debug)]
 pub struct LlmConfig {
     pub vendor: LlmVendor,
     pub model: String,
+    /// Empty for OpenAI-compatible servers that don't need one (e.g. local
+    /// ones).
     pub api_key: String,
     pub base_url: String,
     pub anthropic_version: String,
+    /// Model for [`LlmClient::embed`], from `LLM_EMBEDDING_MODEL`.
+    pub embedding_model: Option<String>,
+    /// Texts sent per embeddings request, from `LLM_EMBEDDING_BATCH_SIZE`.
+    pub embedding_batch_size: usize,
+    /// Vector length to ask for, from `LLM_EMBEDDING_DIMENSIONS`; models that
+    /// can't shorten their vectors reject it.
+    pub embedding_dimensions: Option<u32>,
 }
 
 impl LlmConfig {
-    pub fn from_env() -> Result<Self, Error> {
+    /// The backend configured through the environment, or `None` if
+    /// `LLM_MODEL` isn't set. Errors are settings that are set but wrong.
+    pub fn from_env() -> Result<Option<Self>, Error> {
+        let Ok(model) = std::env::var("LLM_MODEL") else {
+            return Ok(None);
+        };
         let vendor = std::env::var("LLM_VENDOR")
             .unwrap_or_else(|_| "openai".to_string())
             .to_ascii_lowercase();
         let vendor = LlmVendor::parse(&vendor)
             .ok_or_else(|| format!("unsupported LLM_VENDOR '{}'", vendor))?;
 
-        let model = std::env::var("LLM_MODEL")
-            .map_err(|_| "LLM_MODEL must be set to choose the backend model")?;
         let api_key = std::env::var("LLM_API_KEY")
             .or_else(|_| match vendor {
                 LlmVendor::OpenAi | LlmVendor::OpenAiCompatible => std::env::var("OPENAI_API_KEY"),
                 LlmVendor::Anthropic => std::env::var("ANTHROPIC_API_KEY"),
             })
+            .or_else(|e| match vendor {
+                LlmVendor::OpenAiCompatible => Ok(String::new()),
+                _ => Err(e),
+            })
             .map_err(|_| format!("no API key configured for {} backend", vendor.label()))?;
 
         let base_url = std::env::var("LLM_BASE_URL").unwrap_or_else(|_| match vendor {
@@ -141,14 +305,36 @@ impl LlmConfig {
         });
         let anthropic_version = std::env::var("ANTHROPIC_VERSION")
             .unwrap_or_else(|_| DEFAULT_ANTHROPIC_VERSION.to_string());
+        let embedding_model = std::env::var("LLM_EMBEDDING_MODEL").ok().filter(|m| !m.is_empty());
+        let embedding_batch_size = match std::env::var("LLM_EMBEDDING_BATCH_SIZE") {
+            Ok(size) => size
+                .parse()
+                .ok()
+                .filter(|&size| size > 0)
+                .ok_or_else(|| format!("LLM_EMBEDDING_BATCH_SIZE must be a positive number, not '{}'", size))?,
+            Err(_) => DEFAULT_EMBEDDING_BATCH_SIZE,
+        };
+        let embedding_dimensions = match std::env::var("LLM_EMBEDDING_DIMENSIONS") {
+            Ok(dimensions) => Some(
+                dimensions
+                    .parse()
+                    .ok()
+                    .filter(|&dimensions| dimensions > 0)
+                    .ok_or_else(|| format!("LLM_EMBEDDING_DIMENSIONS must be a positive number, not '{}'", dimensions))?,
+            ),
+            Err(_) => None,
+        };
 
-        Ok(Self {
+        Ok(Some(Self {
             vendor,
             model,
             api_key,
             base_url,
             anthropic_version,
-        })
+            embedding_model,
+            embedding_batch_size,
+            embedding_dimensions,
+        }))
     }
 }
 
@@ -158,14 +344,78 @@ pub struct LlmClient {
 }
 
 impl LlmClient {
-    pub fn from_env() -> Result<Self, Error> {
-        Ok(Self::new(LlmConfig::from_env()?))
+    /// See [`LlmConfig::from_env`].
+    pub fn from_env() -> Result<Option<Self>, Error> {
+        Ok(LlmConfig::from_env()?.map(Self::new))
     }
 
     pub fn new(config: LlmConfig) -> Self {
-        Self {
-            http: reqwest::Client::new(),
-            config,
+        let http = reqwest::Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .read_timeout(READ_TIMEOUT)
+            .build()
+            .expect("LLM HTTP client can be built");
+        Self { http, config }
+    }
+
+    pub fn config(&self) -> &LlmConfig {
+        &self.config
+    }
+
+    /// Embeds `texts` with the configured embedding model, sending them in
+    /// batches of `embedding_batch_size`. Uses the OpenAI `/embeddings`
+    /// request shape; vendors without one are an error.
+    pub async fn embed(&self, texts: &[String]) -> Result<Embeddings, Error> {
+        let model = self
+            .config
+            .embedding_model
+            .as_deref()
+            .ok_or("LLM_EMBEDDING_MODEL must be set to use embeddings")?;
+        if !self.config.vendor.has_embeddings() {
+            return Err(format!(
+                "the {} backend has no embeddings endpoint; embeddings need an openai or openai-compatible LLM_VENDOR",
+                self.config.vendor.label()
+            )
+            .into());
+        }
+
+        let mut embeddings = Embeddings {
+            vectors: Vec::with_capacity(texts.len()),
+            model: model.to_string(),
+            dimensions: 0,
+            usage: LlmUsage::default(),
+        };
+        for batch in texts.chunks(self.config.embedding_batch_size.max(1)) {
+            let body = OpenAiEmbeddingRequest {
+                model,
+                input: batch,
+                dimensions: self.config.embedding_dimensions,
+            };
+            let response = self
+                .openai_auth(self.http.post(format!(
+                    "{}/embeddings",
+                    self.config.base_url.trim_end_matches('/')
+                )))
+                .header(USER_AGENT, AGENT)
+                .json(&body)
+                .send_metered()
+                .await?
+                .error_for_status()?;
+            let response: OpenAiEmbeddingResponse = response.json().await?;
+            embeddings.model = response.model.unwrap_or(embeddings.model);
+            embeddings.usage += response.usage.map(LlmUsage::from).unwrap_or_default();
+            embeddings.vectors.extend(batch_vectors(response.data, batch.len())?);
+        }
+        embeddings.dimensions = dimensions(&embeddings.vectors)?;
+        Ok(embeddings)
+    }
+
+    /// Adds the bearer token, unless there is none (local servers).
+    fn openai_auth(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        if self.config.api_key.is_empty() {
+            request
+        } else {
+            request.bearer_auth(&self.config.api_key)
         }
     }
 
@@ -178,48 +428,157 @@ impl LlmClient {
         }
     }
 
-    async fn chat_openai_compatible(&self, request: LlmRequest) -> Result<LlmResponse, Error> {
-        let body = OpenAiChatRequest {
+    /// Like [`LlmClient::chat`], but streams the answer: the stream yields
+    /// pieces of text as the model writes them, and the tokens used. Tools
+    /// aren't supported.
+    pub async fn chat_stream(&self, request: LlmRequest) -> Result<BoxStream<'static, Result<LlmDelta, Error>>, Error> {
+        if !request.tools.is_empty() {
+            return Err("streamed chats can't use tools".into());
+        }
+        let vendor = self.config.vendor;
+        let response = match vendor {
+            LlmVendor::OpenAi | LlmVendor::OpenAiCompatible => {
+                let body = self.openai_body(&request, true);
+                self.openai_auth(self.http.post(format!(
+                    "{}/chat/completions",
+                    self.config.base_url.trim_end_matches('/')
+                )))
+                .header(USER_AGENT, AGENT)
+                .json(&body)
+                .send_metered()
+                .await?
+            }
+            LlmVendor::Anthropic => {
+                let body = self.anthropic_body(&request, true);
+                self.http
+                    .post(format!("{}/messages", self.config.base_url.trim_end_matches('/')))
+                    .header(USER_AGENT, AGENT)
+                    .header("x-api-key", &self.config.api_key)
+                    .header("anthropic-version", &self.config.anthropic_version)
+                    .json(&body)
+                    .send_metered()
+                    .await?
+            }
+        }
+        .error_for_status()?;
+
+        let state = SseState {
+            bytes: response.bytes_stream(),
+            parser: SseParser::default(),
+            deltas: VecDeque::new(),
+            usage: LlmUsage::default(),
+            done: false,
+        };
+        let deltas = futures::stream::try_unfold(state, move |mut state| async move {
+            loop {
+                if let Some(delta) = state.deltas.pop_front() {
+                    return Ok(Some((delta, state)));
+                }
+                if state.done {
+                    return Ok(None);
+                }
+                let chunk = tokio::time::timeout(READ_TIMEOUT, state.bytes.next())
+                    .await
+                    .map_err(|_| format!("the model sent nothing for {}s", READ_TIMEOUT.as_secs()))?;
+                let Some(chunk) = chunk else {
+                    return Ok(None);
+                };
+                for event in state.parser.push(&chunk?) {
+                    match stream_event(vendor, &event)? {
+                        StreamEvent::Delta(LlmDelta::Text(text)) if text.is_empty() => {}
+                        // Anthropic reports the input at the start and the
+                        // output (so far) at the end, so reports are merged.
+                        StreamEvent::Delta(LlmDelta::Usage(usage)) => {
+                            state.usage = state.usage.latest(usage);
+                            state.deltas.push_back(LlmDelta::Usage(state.usage));
+                        }
+                        StreamEvent::Delta(delta) => state.deltas.push_back(delta),
+                        StreamEvent::Nothing => {}
+                        StreamEvent::Done => state.done = true,
+                    }
+                }
+            }
+        });
+        Ok(deltas.boxed())
+    }
+
+    /// Chats with `request.tools` available, running the calls the model asks
+    /// for through `handler` until it answers, for at most `max_rounds` rounds
+    /// of calls. The last round forbids calling tools, so the model has to
+    /// answer; they are still sent, as the history refers to them.
+    /// The response's usage covers every round.
+    pub async fn chat_with_tools(
+        &self,
+        request: LlmRequest,
+        handler: &dyn ToolHandler,
+        max_rounds: usize,
+    ) -> Result<LlmResponse, Error> {
+        let mut usage = LlmUsage::default();
+        tool_rounds(request, handler, max_rounds, &mut usage, |request| self.chat(request).boxed()).await
+    }
+
+    fn openai_body<'a>(&'a self, request: &'a LlmRequest, stream: bool) -> OpenAiChatRequest<'a> {
+        OpenAiChatRequest {
             model: &self.config.model,
-            messages: &request.messages,
+            messages: request.messages.iter().map(OpenAiRequestMessage::from).collect(),
             temperature: request.temperature,
             max_tokens: request.max_tokens,
-        };
+            tools: request.tools.iter().map(OpenAiTool::from).collect(),
+            tool_choice: (request.forbid_tool_calls && !request.tools.is_empty()).then_some("none"),
+            stream,
+            stream_options: stream.then_some(OpenAiStreamOptions { include_usage: true }),
+        }
+    }
+
+    fn anthropic_body<'a>(&'a self, request: &'a LlmRequest, stream: bool) -> AnthropicChatRequest<'a> {
+        let (system, messages) = split_anthropic_messages(request.messages.clone());
+        AnthropicChatRequest {
+            model: &self.config.model,
+            max_tokens: request.max_tokens.unwrap_or(1024),
+            temperature: request.temperature,
+            system,
+            messages,
+            tools: request.tools.iter().map(AnthropicTool::from).collect(),
+            tool_choice: (request.forbid_tool_calls && !request.tools.is_empty())
+                .then_some(AnthropicToolChoice { r#type: "none" }),
+            stream,
+        }
+    }
+
+    async fn chat_openai_compatible(&self, request: LlmRequest) -> Result<LlmResponse, Error> {
+        let body = self.openai_body(&request, false);
         let response = self
-            .http
-            .post(format!(
+            .openai_auth(self.http.post(format!(
                 "{}/chat/completions",
                 self.config.base_url.trim_end_matches('/')
-            ))
+            )))
             .header(USER_AGENT, AGENT)
-            .bearer_auth(&self.config.api_key)
             .json(&body)
-            .send()
+            .send_metered()
             .await?
             .error_for_status()?;
         let response: OpenAiChatResponse = response.json().await?;
-        let text = response
-            .choices
-            .first()
-            .and_then(|choice| choice.message.content.clone())
-            .unwrap_or_default();
+        let usage = response.usage.map(LlmUsage::from).unwrap_or_default();
+        let message = response.choices.into_iter().next().map(|choice| choice.message);
+        let (text, tool_calls) = match message {
+            Some(message) => (
+                message.content.unwrap_or_default(),
+                message.tool_calls.into_iter().map(LlmToolCall::from).collect(),
+            ),
+            None => (String::new(), Vec::new()),
+        };
 
         Ok(LlmResponse {
             text,
+            tool_calls,
+            usage,
             vendor: self.config.vendor,
             model: response.model,
         })
     }
 
     async fn chat_anthropic(&self, request: LlmRequest) -> Result<LlmResponse, Error> {
-        let (system, messages) = split_anthropic_messages(request.messages);
-        let body = AnthropicChatRequest {
-            model: &self.config.model,
-            max_tokens: request.max_tokens.unwrap_or(1024),
-            temperature: request.temperature,
-            system,
-            messages,
-        };
+        let body = self.anthropic_body(&request, false);
         let response = self
             .http
             .post(format!(
@@ -230,47 +589,213 @@ impl LlmClient {
             .header("x-api-key", &self.config.api_key)
             .header("anthropic-version", &self.config.anthropic_version)
             .json(&body)
-            .send()
+            .send_metered()
             .await?
             .error_for_status()?;
         let response: AnthropicChatResponse = response.json().await?;
-        let text = response
-            .content
-            .into_iter()
-            .filter_map(|part| match part {
-                AnthropicContent::Text { text, .. } => Some(text),
-                AnthropicContent::Other => None,
-            })
-            .collect::<Vec<_>>()
-            .join("");
+        let usage = response.usage.map(LlmUsage::from).unwrap_or_default();
+        let mut text = String::new();
+        let mut tool_calls = Vec::new();
+        for part in response.content {
+            match part {
+                AnthropicContent::Text { text: part } => text.push_str(&part),
+                AnthropicContent::ToolUse { id, name, input } => tool_calls.push(LlmToolCall {
+                    id,
+                    name,
+                    arguments: input,
+                }),
+                AnthropicContent::ToolResult { .. } | AnthropicContent::Other => {}
+            }
+        }
 
         Ok(LlmResponse {
             text,
+            tool_calls,
+            usage,
             vendor: self.config.vendor,
             model: response.model,
         })
     }
 }
 
+/// The rounds of [`LlmClient::chat_with_tools`], each sent through `chat`.
+/// `usage` adds up every round's, so it is known even if a later round fails.
+pub async fn tool_rounds<'a>(
+    mut request: LlmRequest,
+    handler: &dyn ToolHandler,
+    max_rounds: usize,
+    usage: &mut LlmUsage,
+    chat: impl Fn(LlmRequest) -> BoxFuture<'a, Result<LlmResponse, Error>>,
+) -> Result<LlmResponse, Error> {
+    for round in 0..=max_rounds {
+        if round == max_rounds {
+            request.forbid_tool_calls = true;
+        }
+        let mut response = chat(request.clone()).await?;
+        *usage += response.usage;
+        if response.tool_calls.is_empty() {
+            response.usage = *usage;
+            return Ok(response);
+        }
+        request.messages.push(response.message());
+        for call in &response.tool_calls {
+            let result = match handler.call(call).await {
+                Ok(result) => result,
+                Err(e) => format!("error: {}", e),
+            };
+            request.messages.push(LlmMessage::tool_result(&call.id, result));
+        }
+    }
+    Err("the model kept calling tools".into())
+}
+
 /// Sends a chat request through the backend selected by environment variables.
 pub async fn chat(request: LlmRequest) -> Result<LlmResponse, Error> {
-    LlmClient::from_env()?.chat(request).await
+    LlmClient::from_env()?.ok_or("LLM_MODEL must be set to choose the backend model")?.chat(request).await
 }
 
 #[derive(Serialize)]
 struct OpenAiChatRequest<'a> {
     model: &'a str,
-    messages: &'a [LlmMessage],
+    messages: Vec<OpenAiRequestMessage<'a>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<OpenAiTool<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<&'static str>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<OpenAiStreamOptions>,
+}
+
+/// Asks for a last chunk with the tokens used.
+#[derive(Serialize)]
+struct OpenAiStreamOptions {
+    include_usage: bool,
+}
+
+#[derive(Serialize)]
+struct OpenAiRequestMessage<'a> {
+    role: LlmRole,
+    /// `null` for assistant turns that only call tools.
+    content: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<OpenAiToolCall>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<&'a str>,
+}
+
+impl<'a> From<&'a LlmMessage> for OpenAiRequestMessage<'a> {
+    fn from(message: &'a LlmMessage) -> Self {
+        let only_calls = message.content.is_empty() && !message.tool_calls.is_empty();
+        Self {
+            role: message.role,
+            content: (!only_calls).then_some(message.content.as_str()),
+            tool_calls: message.tool_calls.iter().map(OpenAiToolCall::from).collect(),
+            tool_call_id: message.tool_call_id.as_deref(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct OpenAiTool<'a> {
+    r#type: &'static str,
+    function: OpenAiFunction<'a>,
+}
+
+#[derive(Serialize)]
+struct OpenAiFunction<'a> {
+    name: &'a str,
+    description: &'a str,
+    parameters: &'a Value,
+}
+
+impl<'a> From<&'a LlmTool> for OpenAiTool<'a> {
+    fn from(tool: &'a LlmTool) -> Self {
+        Self {
+            r#type: "function",
+            function: OpenAiFunction {
+                name: &tool.name,
+                description: &tool.description,
+                parameters: &tool.parameters,
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct OpenAiToolCall {
+    id: String,
+    #[serde(default = "function_type")]
+    r#type: String,
+    function: OpenAiFunctionCall,
+}
+
+fn function_type() -> String {
+    "function".to_string()
+}
+
+/// OpenAI passes the arguments as a JSON-encoded string.
+#[derive(Serialize, Deserialize)]
+struct OpenAiFunctionCall {
+    name: String,
+    arguments: String,
+}
+
+impl From<&LlmToolCall> for OpenAiToolCall {
+    fn from(call: &LlmToolCall) -> Self {
+        Self {
+            id: call.id.clone(),
+            r#type: function_type(),
+            function: OpenAiFunctionCall {
+                name: call.name.clone(),
+                arguments: call.arguments.to_string(),
+            },
+        }
+    }
+}
+
+impl From<OpenAiToolCall> for LlmToolCall {
+    /// Arguments that aren't valid JSON are kept as a string, for the
+    /// handler to reject.
+    fn from(call: OpenAiToolCall) -> Self {
+        let arguments = serde_json::from_str(&call.function.arguments)
+            .unwrap_or(Value::String(call.function.arguments));
+        Self {
+            id: call.id,
+            name: call.function.name,
+            arguments,
+        }
+    }
 }
 
 #[derive(Deserialize)]
 struct OpenAiChatResponse {
     model: String,
     choices: Vec<OpenAiChoice>,
+    #[serde(default)]
+    usage: Option<OpenAiUsage>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiUsage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+}
+
+impl From<OpenAiUsage> for LlmUsage {
+    fn from(usage: OpenAiUsage) -> Self {
+        Self {
+            input_tokens: usage.prompt_tokens,
+            output_tokens: usage.completion_tokens,
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -281,6 +806,31 @@ struct OpenAiChoice {
 #[derive(Deserialize)]
 struct OpenAiMessage {
     content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<OpenAiToolCall>,
+}
+
+#[derive(Serialize)]
+struct OpenAiEmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbeddingResponse {
+    #[serde(default)]
+    model: Option<String>,
+    data: Vec<OpenAiEmbedding>,
+    #[serde(default)]
+    usage: Option<OpenAiUsage>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiEmbedding {
+    index: usize,
+    embedding: Vec<f32>,
 }
 
 #[derive(Serialize)]
@@ -292,50 +842,259 @@ struct AnthropicChatRequest<'a> {
     #[serde(skip_serializing_if = "Option::is_none")]
     system: Option<String>,
     messages: Vec<AnthropicMessage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<AnthropicTool<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<AnthropicToolChoice>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+#[derive(Serialize)]
+struct AnthropicToolChoice {
+    r#type: &'static str,
 }
 
 #[derive(Serialize)]
 struct AnthropicMessage {
     role: AnthropicRole,
-    content: String,
+    content: Vec<AnthropicContent>,
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "lowercase")]
 enum AnthropicRole {
     User,
     Assistant,
 }
 
+#[derive(Serialize)]
+struct AnthropicTool<'a> {
+    name: &'a str,
+    description: &'a str,
+    input_schema: &'a Value,
+}
+
+impl<'a> From<&'a LlmTool> for AnthropicTool<'a> {
+    fn from(tool: &'a LlmTool) -> Self {
+        Self {
+            name: &tool.name,
+            description: &tool.description,
+            input_schema: &tool.parameters,
+        }
+    }
+}
+
 #[derive(Deserialize)]
 struct AnthropicChatResponse {
     model: String,
     content: Vec<AnthropicContent>,
+    #[serde(default)]
+    usage: Option<AnthropicUsage>,
 }
 
+/// Streams report input tokens when the message starts and output tokens
+/// when it ends, so either may be missing.
 #[derive(Deserialize)]
+struct AnthropicUsage {
+    #[serde(default)]
+    input_tokens: u64,
+    #[serde(default)]
+    output_tokens: u64,
+}
+
+impl From<AnthropicUsage> for LlmUsage {
+    fn from(usage: AnthropicUsage) -> Self {
+        Self {
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 #[serde(tag = "type")]
 enum AnthropicContent {
     #[serde(rename = "text")]
     Text { text: String },
+    #[serde(rename = "tool_use")]
+    ToolUse { id: String, name: String, input: Value },
+    #[serde(rename = "tool_result")]
+    ToolResult { tool_use_id: String, content: String },
     #[serde(other)]
     Other,
 }
 
+struct SseState<S> {
+    bytes: S,
+    parser: SseParser,
+    deltas: VecDeque<LlmDelta>,
+    usage: LlmUsage,
+    done: bool,
+}
+
+/// A server-sent event: its `event:` name (if any) and `data:` lines.
+#[derive(Debug, PartialEq)]
+struct SseEvent {
+    event: Option<String>,
+    data: String,
+}
+
+/// Splits a byte stream into server-sent events; events can arrive split
+/// across chunks, and several in one.
+#[derive(Default)]
+struct SseParser {
+    buffer: Vec<u8>,
+    /// The event being read, sent on the next blank line.
+    pending: Option<SseEvent>,
+}
+
+impl SseParser {
+    fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            self.line(line, &mut events);
+        }
+        events
+    }
+
+    fn line(&mut self, line: &str, events: &mut Vec<SseEvent>) {
+        if line.is_empty() {
+            if let Some(event) = self.pending.take() {
+                events.push(event);
+            }
+            return;
+        }
+        if line.starts_with(':') {
+            // A comment, e.g. a keep-alive.
+            return;
+        }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        let event = self.pending.get_or_insert_with(|| SseEvent { event: None, data: String::new() });
+        match field {
+            "event" => event.event = Some(value.to_string()),
+            "data" => {
+                if !event.data.is_empty() {
+                    event.data.push('\n');
+                }
+                event.data.push_str(value);
+            }
+            _ => {}
+        }
+    }
+}
+
+enum StreamEvent {
+    Delta(LlmDelta),
+    Done,
+    Nothing,
+}
+
+/// What a streamed event of `vendor`'s API means.
+fn stream_event(vendor: LlmVendor, event: &SseEvent) -> Result<StreamEvent, Error> {
+    match vendor {
+        LlmVendor::OpenAi | LlmVendor::OpenAiCompatible => {
+            if event.data == "[DONE]" {
+                return Ok(StreamEvent::Done);
+            }
+            let chunk: Value = serde_json::from_str(&event.data)?;
+            if let Some(error) = chunk.get("error") {
+                return Err(format!("stream failed: {}", error).into());
+            }
+            // The usage comes in a last chunk of its own, without choices.
+            if let Some(usage) = chunk.get("usage").filter(|usage| !usage.is_null()) {
+                let usage: OpenAiUsage = serde_json::from_value(usage.clone())?;
+                return Ok(StreamEvent::Delta(LlmDelta::Usage(usage.into())));
+            }
+            Ok(text_delta(&chunk["choices"][0]["delta"]["content"]))
+        }
+        LlmVendor::Anthropic => match event.event.as_deref() {
+            Some("content_block_delta") => {
+                let chunk: Value = serde_json::from_str(&event.data)?;
+                Ok(text_delta(&chunk["delta"]["text"]))
+            }
+            Some("message_start") | Some("message_delta") => {
+                let chunk: Value = serde_json::from_str(&event.data)?;
+                let usage = chunk.get("usage").or_else(|| chunk["message"].get("usage"));
+                match usage {
+                    Some(usage) => {
+                        let usage: AnthropicUsage = serde_json::from_value(usage.clone())?;
+                        Ok(StreamEvent::Delta(LlmDelta::Usage(usage.into())))
+                    }
+                    None => Ok(StreamEvent::Nothing),
+                }
+            }
+            Some("message_stop") => Ok(StreamEvent::Done),
+            Some("error") => Err(format!("stream failed: {}", event.data).into()),
+            _ => Ok(StreamEvent::Nothing),
+        },
+    }
+}
+
+fn text_delta(text: &Value) -> StreamEvent {
+    text.as_str()
+        .map_or(StreamEvent::Nothing, |text| StreamEvent::Delta(LlmDelta::Text(text.to_string())))
+}
+
+/// A batch's vectors in input order, checking one came back per input.
+fn batch_vectors(mut data: Vec<OpenAiEmbedding>, expected: usize) -> Result<Vec<Vec<f32>>, Error> {
+    if data.len() != expected {
+        return Err(format!("asked for {} embeddings, got {}", expected, data.len()).into());
+    }
+    data.sort_by_key(|item| item.index);
+    Ok(data.into_iter().map(|item| item.embedding).collect())
+}
+
+/// The length shared by all `vectors`.
+fn dimensions(vectors: &[Vec<f32>]) -> Result<usize, Error> {
+    let dimensions = vectors.first().map_or(0, Vec::len);
+    if vectors.iter().any(|vector| vector.len() != dimensions) {
+        return Err("embeddings of different lengths returned".into());
+    }
+    Ok(dimensions)
+}
+
+/// Splits off the system prompt and turns the rest into content blocks.
+/// Tool results are user turns to Anthropic, and consecutive turns of the same
+/// role are merged, as its API requires roles to alternate.
 fn split_anthropic_messages(messages: Vec<LlmMessage>) -> (Option<String>, Vec<AnthropicMessage>) {
     let mut system = Vec::new();
-    let mut chat = Vec::new();
+    let mut chat: Vec<AnthropicMessage> = Vec::new();
     for message in messages {
-        match message.role {
-            LlmRole::System => system.push(message.content),
-            LlmRole::User => chat.push(AnthropicMessage {
-                role: AnthropicRole::User,
-                content: message.content,
-            }),
-            LlmRole::Assistant => chat.push(AnthropicMessage {
-                role: AnthropicRole::Assistant,
-                content: message.content,
-            }),
+        let (role, mut content) = match message.role {
+            LlmRole::System => {
+                system.push(message.content);
+                continue;
+            }
+            LlmRole::User => (AnthropicRole::User, vec![AnthropicContent::Text { text: message.content }]),
+            LlmRole::Assistant => {
+                let mut content = Vec::new();
+                if !message.content.is_empty() {
+                    content.push(AnthropicContent::Text { text: message.content });
+                }
+                content.extend(message.tool_calls.into_iter().map(|call| AnthropicContent::ToolUse {
+                    id: call.id,
+                    name: call.name,
+                    input: call.arguments,
+                }));
+                (AnthropicRole::Assistant, content)
+            }
+            LlmRole::Tool => (
+                AnthropicRole::User,
+                vec![AnthropicContent::ToolResult {
+                    tool_use_id: message.tool_call_id.unwrap_or_default(),
+                    content: message.content,
+                }],
+            ),
+        };
+        match chat.last_mut() {
+            Some(last) if last.role == role => last.content.append(&mut content),
+            _ => chat.push(AnthropicMessage { role, content }),
         }
     }
 
@@ -346,3 +1105,202 @@ fn split_anthropic_messages(messages: Vec<LlmMessage>) -> (Option<String>, Vec<A
     };
     (system, chat)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embedding_batches_come_back_in_input_order() {
+        let data = vec![
+            OpenAiEmbedding { index: 1, embedding: vec![0.0, 1.0] },
+            OpenAiEmbedding { index: 0, embedding: vec![1.0, 0.0] },
+        ];
+        let vectors = batch_vectors(data, 2).unwrap();
+        assert_eq!(vectors, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+        assert_eq!(dimensions(&vectors).unwrap(), 2);
+        assert_eq!(dimensions(&[]).unwrap(), 0);
+
+        let short = vec![OpenAiEmbedding { index: 0, embedding: vec![1.0] }];
+        assert!(batch_vectors(short, 2).is_err());
+        assert!(dimensions(&[vec![1.0], vec![1.0, 2.0]]).is_err());
+    }
+
+    fn call() -> LlmToolCall {
+        LlmToolCall {
+            id: "call_1".to_string(),
+            name: "leaderboard".to_string(),
+            arguments: serde_json::json!({ "category": "any%" }),
+        }
+    }
+
+    fn conversation() -> Vec<LlmMessage> {
+        let mut asked = LlmMessage::assistant("");
+        asked.tool_calls.push(call());
+        vec![
+            LlmMessage::system("Be brief."),
+            LlmMessage::user("Who holds any%?"),
+            asked,
+            LlmMessage::tool_result("call_1", "1. Zoast 41:12"),
+        ]
+    }
+
+    #[test]
+    fn tool_calls_round_trip_through_openai_messages() {
+        let messages = conversation();
+        let json: Vec<Value> = messages
+            .iter()
+            .map(|m| serde_json::to_value(OpenAiRequestMessage::from(m)).unwrap())
+            .collect();
+        assert_eq!(json[2]["content"], Value::Null);
+        assert_eq!(json[2]["tool_calls"][0]["function"]["arguments"], r#"{"category":"any%"}"#);
+        assert_eq!(json[3]["role"], "tool");
+        assert_eq!(json[3]["tool_call_id"], "call_1");
+
+        let returned: OpenAiToolCall = serde_json::from_value(json[2]["tool_calls"][0].clone()).unwrap();
+        assert_eq!(LlmToolCall::from(returned), call());
+    }
+
+    #[test]
+    fn tool_results_are_user_turns_to_anthropic() {
+        let mut messages = conversation();
+        messages.push(LlmMessage::tool_result("call_2", "no runs"));
+        let (system, chat) = split_anthropic_messages(messages);
+        assert_eq!(system.as_deref(), Some("Be brief."));
+        let json = serde_json::to_value(&chat).unwrap();
+        assert_eq!(chat.len(), 3);
+        assert_eq!(json[1]["content"][0]["type"], "tool_use");
+        assert_eq!(json[1]["content"][0]["input"]["category"], "any%");
+        assert_eq!(json[2]["role"], "user");
+        assert_eq!(json[2]["content"][1]["tool_use_id"], "call_2");
+    }
+
+    fn client(vendor: LlmVendor) -> LlmClient {
+        LlmClient::new(LlmConfig {
+            vendor,
+            model: "model".to_string(),
+            api_key: String::new(),
+            base_url: String::new(),
+            anthropic_version: String::new(),
+            embedding_model: None,
+            embedding_batch_size: 1,
+            embedding_dimensions: None,
+        })
+    }
+
+    #[test]
+    fn final_round_keeps_tools_but_forbids_calls() {
+        let tool = LlmTool {
+            name: "leaderboard".to_string(),
+            description: "Top runs of a category".to_string(),
+            parameters: serde_json::json!({ "type": "object" }),
+        };
+        let mut request = LlmRequest::new(conversation()).with_tools(vec![tool]);
+        let openai = client(LlmVendor::OpenAi);
+        let anthropic = client(LlmVendor::Anthropic);
+        assert!(serde_json::to_value(openai.openai_body(&request, false)).unwrap().get("tool_choice").is_none());
+
+        request.forbid_tool_calls = true;
+        let body = serde_json::to_value(openai.openai_body(&request, false)).unwrap();
+        assert_eq!(body["tool_choice"], "none");
+        assert_eq!(body["tools"][0]["function"]["name"], "leaderboard");
+        let body = serde_json::to_value(anthropic.anthropic_body(&request, false)).unwrap();
+        assert_eq!(body["tool_choice"], serde_json::json!({ "type": "none" }));
+        assert_eq!(body["tools"][0]["name"], "leaderboard");
+    }
+
+    struct Leaderboard;
+
+    #[async_trait]
+    impl ToolHandler for Leaderboard {
+        async fn call(&self, _call: &LlmToolCall) -> Result<String, Error> {
+            Ok("1. Zoast 41:12".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn usage_of_every_round_is_kept_when_a_later_one_fails() {
+        let asking = LlmResponse {
+            text: String::new(),
+            tool_calls: vec![call()],
+            usage: LlmUsage { input_tokens: 100, output_tokens: 10 },
+            vendor: LlmVendor::OpenAi,
+            model: "model".to_string(),
+        };
+        let rounds = std::sync::atomic::AtomicUsize::new(0);
+        let mut usage = LlmUsage::default();
+        let result = tool_rounds(LlmRequest::new(conversation()), &Leaderboard, 4, &mut usage, |request| {
+            let round = rounds.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let asking = asking.clone();
+            async move {
+                match round {
+                    0 | 1 => Ok(asking),
+                    _ => {
+                        assert_eq!(request.messages.len(), 8);
+                        Err("connection reset".into())
+                    }
+                }
+            }
+            .boxed()
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(usage, LlmUsage { input_tokens: 200, output_tokens: 20 });
+    }
+
+    #[test]
+    fn server_sent_events_are_read_across_chunks() {
+        let mut parser = SseParser::default();
+        assert!(parser.push(b"event: content_block_delta\r\ndata: {\"delta\":").is_empty());
+        let events = parser.push(b"{\"text\":\"Hi\"}}\r\n\r\n: ping\n\ndata: [DONE]\n\n");
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event.as_deref(), Some("content_block_delta"));
+
+        let text = |vendor, event| match stream_event(vendor, event).unwrap() {
+            StreamEvent::Delta(LlmDelta::Text(text)) => Some(text),
+            _ => None,
+        };
+        assert_eq!(text(LlmVendor::Anthropic, &events[0]).as_deref(), Some("Hi"));
+        assert!(matches!(stream_event(LlmVendor::OpenAi, &events[1]).unwrap(), StreamEvent::Done));
+        let chunk = SseEvent {
+            event: None,
+            data: r#"{"choices":[{"delta":{"content":" there"}}]}"#.to_string(),
+        };
+        assert_eq!(text(LlmVendor::OpenAiCompatible, &chunk).as_deref(), Some(" there"));
+    }
+
+    #[test]
+    fn streams_report_usage() {
+        let usage = |vendor, event: Option<&str>, data: &str| {
+            let event = SseEvent {
+                event: event.map(str::to_string),
+                data: data.to_string(),
+            };
+            match stream_event(vendor, &event).unwrap() {
+                StreamEvent::Delta(LlmDelta::Usage(usage)) => Some(usage),
+                _ => None,
+            }
+        };
+        let openai = r#"{"choices":[],"usage":{"prompt_tokens":120,"completion_tokens":30,"total_tokens":150}}"#;
+        assert_eq!(
+            usage(LlmVendor::OpenAi, None, openai),
+            Some(LlmUsage { input_tokens: 120, output_tokens: 30 })
+        );
+        assert_eq!(usage(LlmVendor::OpenAi, None, r#"{"choices":[{"delta":{}}],"usage":null}"#), None);
+
+        let start = r#"{"type":"message_start","message":{"usage":{"input_tokens":80,"output_tokens":1}}}"#;
+        // The delta's output count includes the start's.
+        let end = r#"{"type":"message_delta","delta":{},"usage":{"output_tokens":42}}"#;
+        let total = usage(LlmVendor::Anthropic, Some("message_start"), start)
+            .unwrap()
+            .latest(usage(LlmVendor::Anthropic, Some("message_delta"), end).unwrap());
+        assert_eq!(total.total(), 122);
+    }
+
+    #[test]
+    fn only_openai_style_vendors_have_embeddings() {
+        assert!(LlmVendor::OpenAi.has_embeddings());
+        assert!(LlmVendor::OpenAiCompatible.has_embeddings());
+        assert!(!LlmVendor::Anthropic.has_embeddings());
+    }
+}
@@ -1,3 +1,8 @@
+use async_trait::async_trait;
+use reqwest::{RequestBuilder, Response};
+
+use crate::metrics;
+
 pub mod crocomire;
 pub mod deertier;
 pub mod llm;
@@ -7,4 +12,27 @@ pub mod quad;
 pub mod smz3;
 pub mod speedrun;
 pub mod wiki;
-// pub mod openai;
+
+/// Sends a request like [`RequestBuilder::send`], counting it in the
+/// per-host API metrics. API modules use this for every outgoing request.
+#[async_trait]
+pub trait SendMetered {
+    async fn send_metered(self) -> reqwest::Result<Response>;
+}
+
+#[async_trait]
+impl SendMetered for RequestBuilder {
+    async fn send_metered(self) -> reqwest::Result<Response> {
+        let (client, request) = self.build_split();
+        let request = request?;
+        let host = request.url().host_str().unwrap_or("unknown").to_string();
+        let result = client.execute(request).await;
+        let outcome = match &result {
+            Ok(response) if response.status().is_success() => "ok",
+            Ok(_) => "http_error",
+            Err(_) => "failed",
+        };
+        metrics::api_request(&host, outcome);
+        result
+    }
+}
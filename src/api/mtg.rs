@@ -1,5 +1,6 @@
 use reqwest;
 use serde::Deserialize;
+use crate::api::SendMetered;
 
 type CardError = Box<dyn std::error::Error + Send + Sync>;
 
@@ -27,11 +28,10 @@ pub struct CardResult {
 }
 
 pub async fn get_card(name: &str) -> Result<CardResult, CardError> {
-    let response = reqwest::get(format!(
-        "https://api.magicthegathering.io/v1/cards?name=\"{}\"",
-        name
-    ))
-    .await?;
+    let response = reqwest::Client::new()
+        .get(format!("https://api.magicthegathering.io/v1/cards?name=\"{}\"", name))
+        .send_metered()
+        .await?;
     let result = response.json::<CardResult>().await?;
     Ok(result)
 }
@@ -1,6 +1,7 @@
 use reqwest::header::USER_AGENT;
 use serde::Deserialize;
 
+use crate::api::SendMetered;
 use crate::Error;
 
 const AGENT: &str = "shaktool-rs/2.0";
@@ -22,7 +23,7 @@ pub async fn youtube_info(video_url: &str) -> Result<Option<VideoInfo>, Error> {
     let response = reqwest::Client::new()
         .get(&url)
         .header(USER_AGENT, AGENT)
-        .send()
+        .send_metered()
         .await?;
     if response.status().is_client_error() {
         return Ok(None);
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Map, Value};
 use std::error::Error as StdError;
+use crate::api::SendMetered;
 
 type ApiResult<T> = Result<T, Box<dyn StdError + Send + Sync>>;
 
@@ -131,7 +132,7 @@ impl RandomizerRequest {
         if let Some(api_key) = self.api_key.as_deref() {
             request = request.bearer_auth(api_key);
         }
-        let response = request.send().await?;
+        let response = request.send_metered().await?;
 
         let status = response.status();
         let body = response.text().await?;
@@ -159,7 +160,7 @@ pub async fn presets(base_url: &str, api_key: Option<&str>) -> ApiResult<Presets
     if let Some(api_key) = api_key {
         request = request.bearer_auth(api_key);
     }
-    let response = request.send().await?;
+    let response = request.send_metered().await?;
 
     let status = response.status();
     let body = response.text().await?;
@@ -177,7 +178,7 @@ pub async fn metadata(base_url: &str) -> ApiResult<Value> {
             "{}/api/metadata/Combo",
             base_url.trim_end_matches('/')
         ))
-        .send()
+        .send_metered()
         .await?;
 
     let status = response.status();
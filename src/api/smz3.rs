@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 use serde_json;
 use std::collections::HashMap;
 use strum_macros::EnumString;
+use crate::api::SendMetered;
 type ApiError = Box<dyn std::error::Error + Send + Sync>;
 
 #[derive(Deserialize, Debug)]
@@ -308,7 +309,7 @@ impl RandomizerRequest {
             }
         }
 
-        let res = client.post(url).json(&json_object).send().await?;
+        let res = client.post(url).json(&json_object).send_metered().await?;
         let body = res.text().await?;
         let mut response: RandomizerResponse = serde_json::from_str(&body)?;
         response.beta = self.beta;
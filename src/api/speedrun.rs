@@ -3,6 +3,7 @@ use std::io;
 use reqwest::header::USER_AGENT;
 use serde::Deserialize;
 
+use crate::api::SendMetered;
 use crate::Error;
 
 const API_BASE: &str = "https://www.speedrun.com/api/v1";
@@ -136,7 +137,7 @@ pub async fn get_game(abbreviation: &str) -> Result<Option<Game>, Error> {
     let response = reqwest::Client::new()
         .get(&url)
         .header(USER_AGENT, AGENT)
-        .send()
+        .send_metered()
         .await?
         .error_for_status()?;
     let games: Embedded<Vec<Game>> = response.json().await?;
@@ -164,7 +165,7 @@ pub async fn get_runs_limited(
     let response = reqwest::Client::new()
         .get(&url)
         .header(USER_AGENT, AGENT)
-        .send()
+        .send_metered()
         .await?
         .error_for_status()?;
     let runs: Embedded<Vec<Run>> = response.json().await?;
@@ -178,7 +179,7 @@ pub async fn get_run(run_id: &str) -> Result<Option<Run>, Error> {
     let response = reqwest::Client::new()
         .get(&url)
         .header(USER_AGENT, AGENT)
-        .send()
+        .send_metered()
         .await?;
     if response.status() == reqwest::StatusCode::NOT_FOUND {
         return Ok(None);
@@ -196,7 +197,7 @@ pub async fn count_verified_runs(user_id: &str, game_id: &str) -> Result<usize,
     let response = reqwest::Client::new()
         .get(&url)
         .header(USER_AGENT, AGENT)
-        .send()
+        .send_metered()
         .await?
         .error_for_status()?;
     let runs: Embedded<Vec<serde_json::Value>> = response.json().await?;
@@ -216,7 +217,7 @@ pub async fn count_verified_category_runs(
     let response = reqwest::Client::new()
         .get(&url)
         .header(USER_AGENT, AGENT)
-        .send()
+        .send_metered()
         .await?
         .error_for_status()?;
     let runs: Embedded<Vec<serde_json::Value>> = response.json().await?;
@@ -248,7 +249,7 @@ pub async fn get_top_times(game_id: &str, category_id: &str, top: u32) -> Result
     let response = reqwest::Client::new()
         .get(&url)
         .header(USER_AGENT, AGENT)
-        .send()
+        .send_metered()
         .await?
         .error_for_status()?;
     let leaderboard: Embedded<Leaderboard> = response.json().await?;
@@ -298,7 +299,7 @@ pub async fn set_run_status(
         .header(USER_AGENT, AGENT)
         .header("X-API-Key", api_key)
         .json(&body)
-        .send()
+        .send_metered()
         .await?
         .error_for_status()?;
     validate_status_update_response(response.text().await?, run_id, change)?;
@@ -1,4 +1,5 @@
 use cached::proc_macro::cached;
+use futures::StreamExt;
 use kuchiki::traits::*;
 use mediawiki::api::Api;
 use mediawiki::title::Title;
@@ -6,6 +7,7 @@ use std::collections::VecDeque;
 use std::error::Error;
 
 const URL: &str = "https://wiki.supermetroid.run/api.php";
+const HOST: &str = "wiki.supermetroid.run";
 
 pub type WikiResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
 
@@ -24,15 +26,27 @@ pub struct WikiRecord {
 /* Use the cached crate here to cache this for 300 seconds (5 minutes) to prevent hammering of the Wiki */
 #[cached(size = 1, time = 300, result = true)]
 pub async fn get_leaderboard_result() -> WikiResult<serde_json::Value> {
-    let api = Api::new(URL).await?;
-    let params = api.params_into(&[
-        ("action", "parse"),
-        ("page", "Combined_Leaderboards"),
-        ("prop", "text"),
-    ]);
-
-    let result = api.get_query_api_json(&params).await?;
-    Ok(result)
+    metered(async {
+        let api = Api::new(URL).await?;
+        let params = api.params_into(&[
+            ("action", "parse"),
+            ("page", "Combined_Leaderboards"),
+            ("prop", "text"),
+        ]);
+
+        let result = api.get_query_api_json(&params).await?;
+        Ok(result)
+    })
+    .await
+}
+
+/// Runs a wiki API call, counting it in the API metrics. The `mediawiki`
+/// client does its own HTTP, so calls are counted here instead of by
+/// [`SendMetered`](crate::api::SendMetered).
+async fn metered<T>(call: impl std::future::Future<Output = WikiResult<T>>) -> WikiResult<T> {
+    let result = call.await;
+    crate::metrics::api_request(HOST, if result.is_ok() { "ok" } else { "failed" });
+    result
 }
 
 pub async fn get_wiki_leaderboard() -> WikiResult<Vec<WikiRecord>> {
@@ -118,21 +132,94 @@ pub async fn get_wiki_leaderboard() -> WikiResult<Vec<WikiRecord>> {
 }
 
 pub async fn search_wiki_titles(title: &str) -> WikiResult<Vec<Title>> {
-    let api = Api::new(URL).await?;
-
-    let params = api.params_into(&[
-        ("action", "query"),
-        ("list", "search"),
-        ("redirects", "1"),
-        ("utf8", "1"),
-        ("formatversion", "2"),
-        ("srsearch", title),
-        ("srwhat", "title"),
-        ("srprop", "redirecttitle"),
-        ("srlimit", "10"),
-    ]);
-
-    let result = api.get_query_api_json(&params).await?;
-    let titles = Api::result_array_to_titles(&result);
-    Ok(titles)
+    metered(async {
+        let api = Api::new(URL).await?;
+
+        let params = api.params_into(&[
+            ("action", "query"),
+            ("list", "search"),
+            ("redirects", "1"),
+            ("utf8", "1"),
+            ("formatversion", "2"),
+            ("srsearch", title),
+            ("srwhat", "title"),
+            ("srprop", "redirecttitle"),
+            ("srlimit", "10"),
+        ]);
+
+        let result = api.get_query_api_json(&params).await?;
+        let titles = Api::result_array_to_titles(&result);
+        Ok(titles)
+    })
+    .await
+}
+
+/// A content page of the wiki and its latest revision.
+#[derive(Debug, Clone)]
+pub struct WikiPage {
+    pub page_id: i64,
+    pub title: String,
+    pub revision: i64,
+}
+
+/// Every content page (main namespace, no redirects) with its latest
+/// revision, for indexing.
+pub async fn list_pages() -> WikiResult<Vec<WikiPage>> {
+    metered(async {
+        let api = Api::new(URL).await?;
+        let params = api.params_into(&[
+            ("action", "query"),
+            ("generator", "allpages"),
+            ("gapnamespace", "0"),
+            ("gapfilterredir", "nonredirects"),
+            ("gaplimit", "max"),
+            ("prop", "info"),
+            ("formatversion", "2"),
+        ]);
+        let mut pages = Vec::new();
+        let mut results = Box::pin(api.get_query_api_json_limit_iter(&params, None).await);
+        while let Some(result) = results.next().await {
+            let result = result?;
+            let batch = result["query"]["pages"].as_array().cloned().unwrap_or_default();
+            for page in batch {
+                let (Some(page_id), Some(title), Some(revision)) =
+                    (page["pageid"].as_i64(), page["title"].as_str(), page["lastrevid"].as_i64())
+                else {
+                    continue;
+                };
+                pages.push(WikiPage { page_id, title: title.to_string(), revision });
+            }
+        }
+        Ok(pages)
+    })
+    .await
+}
+
+/// The rendered HTML of a page, without edit links.
+pub async fn page_html(title: &str) -> WikiResult<String> {
+    metered(async {
+        let api = Api::new(URL).await?;
+        let params = api.params_into(&[
+            ("action", "parse"),
+            ("page", title),
+            ("prop", "text"),
+            ("disableeditsection", "1"),
+            ("disablelimitreport", "1"),
+            ("formatversion", "2"),
+        ]);
+        let result = api.get_query_api_json(&params).await?;
+        let html = result["parse"]["text"].as_str().ok_or("Could not parse wiki result")?;
+        Ok(html.to_string())
+    })
+    .await
+}
+
+/// A link to a page, or to a section of it by its anchor.
+pub fn page_url(title: &str, anchor: Option<&str>) -> String {
+    let mut url = format!("https://{}/{}", HOST, urlencoding::encode(&title.replace(' ', "_")));
+    if let Some(anchor) = anchor.filter(|a| !a.is_empty()) {
+        url.push('#');
+        url.push_str(&urlencoding::encode(anchor));
+    }
+    url
 }
@@ -0,0 +1,49 @@
+use crate::api::llm::LlmUsage;
+use crate::util::llm_usage::{MeteredLlm, OverBudget};
+use crate::util::streamed_reply::StreamedReply;
+use crate::util::wiki_search;
+use crate::{Context, Error};
+
+/// Answers a question from the Super Metroid Wiki, with links to the sections used
+// Only in servers: answers are paid for out of a server's LLM budget, and DMs
+// have none.
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn ask(
+    ctx: Context<'_>,
+    #[description = "Question"]
+    #[rest]
+    question: String,
+) -> Result<(), Error> {
+    let Some(client) = &ctx.data().llm else {
+        ctx.say("Asking the wiki isn't set up on this bot.").await?;
+        return Ok(());
+    };
+    let db = &ctx.data().db;
+    let llm = MeteredLlm::new(client, db, "ask").for_user(ctx.guild_id().map(|g| g.get()), ctx.author().id.get());
+    if let Err(e) = llm.check_budget().await {
+        if let Some(over) = e.downcast_ref::<OverBudget>() {
+            ctx.say(format!("Sorry, {}; try again later.", over)).await?;
+            return Ok(());
+        }
+        return Err(e);
+    }
+    ctx.defer().await?;
+    let Some(question) = wiki_search::question(db, &llm, &question).await? else {
+        ctx.say("The wiki is still being indexed; try again in a few minutes.").await?;
+        return Ok(());
+    };
+
+    let mut reply = StreamedReply::new(ctx);
+    let mut usage = LlmUsage::default();
+    let streamed = reply.push_stream(llm.chat_stream(question.request.clone()).await?, &mut usage).await;
+    llm.record(&client.config().model, usage).await?;
+    streamed?;
+    let sources: String = question
+        .sources(reply.text())
+        .iter()
+        .map(|(label, url)| format!("\n{} <{}>", label, url))
+        .collect();
+    reply.push(&sources).await?;
+    reply.finish().await?;
+    Ok(())
+}
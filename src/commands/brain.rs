@@ -0,0 +1,314 @@
+use std::time::{Duration, Instant};
+
+use chrono::{NaiveDate, TimeZone, Utc};
+
+use poise::serenity_prelude as serenity;
+use poise::serenity_prelude::{CreateActionRow, CreateButton, CreateInteractionResponse, CreateInteractionResponseMessage};
+
+use crate::db::brain::SHARED_BRAIN;
+use crate::permissions;
+use crate::util::cobe;
+use crate::{Context, Error};
+
+/// How long `brain reset` waits for the confirmation press.
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Messages `brain train` reads when no limit is given, and at most.
+const DEFAULT_TRAIN_LIMIT: u32 = 1000;
+const MAX_TRAIN_LIMIT: u32 = 50_000;
+
+/// Messages per history request (Discord's maximum), learned as one batch.
+const PAGE_SIZE: u8 = 100;
+
+/// Minimum time between progress edits of the `brain train` reply.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Discord's epoch (2015-01-01) in Unix milliseconds, for message ids at a
+/// given time.
+const DISCORD_EPOCH_MS: i64 = 1_420_070_400_000;
+
+const RESET_ID: &str = "brain_reset:confirm";
+const CANCEL_ID: &str = "brain_reset:cancel";
+
+/// Shows the size of this server's chatter brain; subcommands: `stats`, `train`, `reset`, `purge`
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "ADMINISTRATOR",
+    subcommands("stats", "train", "reset", "purge")
+)]
+pub async fn brain(ctx: Context<'_>) -> Result<(), Error> {
+    stats_inner(ctx).await
+}
+
+/// Shows which chatter brain this server uses and how much it has learned
+#[poise::command(prefix_command, slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+pub async fn stats(ctx: Context<'_>) -> Result<(), Error> {
+    stats_inner(ctx).await
+}
+
+/// Teaches this server's chatter brain from a channel's message history
+#[poise::command(prefix_command, slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+pub async fn train(
+    ctx: Context<'_>,
+    #[description = "Channel to learn from"] channel: serenity::GuildChannel,
+    #[description = "Messages to read, newest first (default 1000, at most 50000)"] limit: Option<u32>,
+    #[description = "Only messages from this day on (YYYY-MM-DD)"] since: Option<String>,
+    #[description = "Only messages before this day (YYYY-MM-DD)"] until: Option<String>,
+) -> Result<(), Error> {
+    if Some(channel.guild_id) != ctx.guild_id() {
+        ctx.say("That channel is not in this server.").await?;
+        return Ok(());
+    }
+    let limit = limit.unwrap_or(DEFAULT_TRAIN_LIMIT).min(MAX_TRAIN_LIMIT);
+    let (since, until) = match (parse_day(since.as_deref()), parse_day(until.as_deref())) {
+        (Ok(since), Ok(until)) => (since, until),
+        (Err(e), _) | (_, Err(e)) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+    };
+
+    let data = ctx.data();
+    let mut trainer = cobe::Trainer::new(data, channel.guild_id, channel.id).await?;
+    if trainer.cobe().id() == SHARED_BRAIN && !permissions::is_owner(ctx) {
+        ctx.say("This server uses the shared brain, which only bot owners can train.").await?;
+        return Ok(());
+    }
+
+    let reply = ctx.say(format!("📚 Reading <#{}>…", channel.id)).await?;
+    let mut before = until.map(message_id_at);
+    let (mut read, mut learned) = (0u32, 0usize);
+    let mut last_edit = Instant::now();
+    let mut oldest = None;
+    let mut stopped = false;
+    while read < limit {
+        // Each page is learned whole, with shutdown waiting for it.
+        let Some(_guard) = data.shutdown.guard().await else {
+            stopped = true;
+            break;
+        };
+        let page_size = PAGE_SIZE.min((limit - read).min(u8::MAX as u32) as u8);
+        let mut request = serenity::GetMessages::new().limit(page_size);
+        if let Some(before) = before {
+            request = request.before(before);
+        }
+        let mut page = channel.id.messages(ctx, request).await?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        before = Some(last.id);
+        let exhausted = page.len() < page_size as usize;
+        let passed_since = since.is_some_and(|since| *last.timestamp < since);
+        if let Some(since) = since {
+            page.retain(|m| *m.timestamp >= since);
+        }
+        read += page.len() as u32;
+        oldest = page.last().map(|m| m.timestamp).or(oldest);
+        learned += trainer.learn(ctx.serenity_context(), data, &page).await?;
+        if exhausted || passed_since {
+            break;
+        }
+        if last_edit.elapsed() >= PROGRESS_INTERVAL {
+            last_edit = Instant::now();
+            let progress = format!(
+                "📚 Reading <#{}>… {} message(s) read, {} learned{}",
+                channel.id,
+                read,
+                learned,
+                back_to(oldest)
+            );
+            reply.edit(ctx, poise::CreateReply::default().content(progress)).await?;
+        }
+    }
+    reply
+        .edit(
+            ctx,
+            poise::CreateReply::default().content(format!(
+                "{} from <#{}>: {} message(s) read, {} learned{}.",
+                if stopped { "⏹️ Stopped training for a restart" } else { "✅ Trained" },
+                channel.id,
+                read,
+                learned,
+                back_to(oldest)
+            )),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Parses an optional `YYYY-MM-DD` option as the start of that day (UTC).
+fn parse_day(day: Option<&str>) -> Result<Option<chrono::DateTime<Utc>>, String> {
+    let Some(day) = day else {
+        return Ok(None);
+    };
+    let date = NaiveDate::parse_from_str(day.trim(), "%Y-%m-%d")
+        .map_err(|_| format!("`{}` is not a date (expected YYYY-MM-DD)", day))?;
+    Ok(Some(Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).expect("midnight exists"))))
+}
+
+/// The first message id at or after `time`, as a history cursor.
+fn message_id_at(time: chrono::DateTime<Utc>) -> serenity::MessageId {
+    let ms = (time.timestamp_millis() - DISCORD_EPOCH_MS).max(1) as u64;
+    serenity::MessageId::new(ms << 22)
+}
+
+fn back_to(oldest: Option<serenity::Timestamp>) -> String {
+    oldest.map(|t| format!(" (back to <t:{}:d>)", t.unix_timestamp())).unwrap_or_default()
+}
+
+/// Forgets everything this server's own chatter brain has learned
+#[poise::command(prefix_command, slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+pub async fn reset(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
+    let db = &ctx.data().db;
+    let own = db.brain_stats(guild_id).await?;
+    if own.nodes == 0 {
+        ctx.say("This server's brain is already empty.").await?;
+        return Ok(());
+    }
+
+    let buttons = CreateActionRow::Buttons(vec![
+        CreateButton::new(RESET_ID).label("Reset").style(serenity::ButtonStyle::Danger),
+        CreateButton::new(CANCEL_ID).label("Cancel").style(serenity::ButtonStyle::Secondary),
+    ]);
+    let reply = ctx
+        .send(
+            poise::CreateReply::default()
+                .content(format!(
+                    "Reset this server's brain? It forgets {} words and {} contexts; this can't be undone.",
+                    own.tokens, own.nodes
+                ))
+                .components(vec![buttons]),
+        )
+        .await?;
+    let message = reply.message().await?;
+    let press = serenity::ComponentInteractionCollector::new(ctx.serenity_context())
+        .author_id(ctx.author().id)
+        .message_id(message.id)
+        .timeout(CONFIRM_TIMEOUT)
+        .await;
+
+    let content = match &press {
+        Some(press) if press.data.custom_id == RESET_ID => {
+            let removed = db.reset_brain(guild_id).await?;
+            format!("🧹 Reset this server's brain ({} contexts removed).", removed)
+        }
+        _ => "Nothing was reset.".to_string(),
+    };
+    let update = CreateInteractionResponseMessage::new().content(&content).components(Vec::new());
+    match press {
+        Some(press) => press.create_response(ctx, CreateInteractionResponse::UpdateMessage(update)).await?,
+        None => reply.edit(ctx, poise::CreateReply::default().content(content).components(Vec::new())).await?,
+    }
+    Ok(())
+}
+
+/// Unlearns everything a user taught the bot in this server
+#[poise::command(prefix_command, slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+pub async fn purge(ctx: Context<'_>, #[description = "User to forget"] user: serenity::User) -> Result<(), Error> {
+    ctx.defer().await?;
+    let guild_id = ctx.guild_id().map(|g| g.get());
+    let forgotten = ctx.data().cobe.forget_user(user.id.get(), guild_id).await?;
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!("Forgot {} message(s) <@{}> taught the bot in this server.", forgotten, user.id))
+            .allowed_mentions(serenity::CreateAllowedMentions::new()),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Shows whether the bot learns from your messages; subcommands: `optout`, `optin`
+#[poise::command(prefix_command, slash_command, subcommands("optout", "optin"))]
+pub async fn chatter(ctx: Context<'_>) -> Result<(), Error> {
+    let content = if ctx.data().db.brain_opted_out(ctx.author().id.get()).await? {
+        "The bot doesn't learn from your messages (`chatter optin` to change that)."
+    } else {
+        "The bot learns from your messages to chat (`chatter optout` to stop, `forget me` to remove what it learned)."
+    };
+    ctx.send(poise::CreateReply::default().content(content).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Stops the bot from learning from your messages, in every server
+#[poise::command(prefix_command, slash_command)]
+pub async fn optout(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.data().db.set_brain_opt_out(ctx.author().id.get(), true).await?;
+    ctx.send(
+        poise::CreateReply::default()
+            .content("The bot won't learn from your messages anymore. Use `forget me` to also remove what it already learned.")
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Lets the bot learn from your messages again
+#[poise::command(prefix_command, slash_command)]
+pub async fn optin(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.data().db.set_brain_opt_out(ctx.author().id.get(), false).await?;
+    ctx.send(poise::CreateReply::default().content("The bot learns from your messages again.").ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Makes the bot forget what it learned from you; subcommand: `me`
+#[poise::command(prefix_command, slash_command, subcommands("me"))]
+pub async fn forget(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say("Use `forget me` to remove everything the bot learned from your messages.").await?;
+    Ok(())
+}
+
+/// Removes everything the bot learned from your messages, in every server
+#[poise::command(prefix_command, slash_command)]
+pub async fn me(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    let forgotten = ctx.data().cobe.forget_user(ctx.author().id.get(), None).await?;
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!(
+                "Forgot {} of your message(s). The bot still learns from new ones unless you `chatter optout`.",
+                forgotten
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn stats_inner(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
+    let data = ctx.data();
+    let cobe = data.cobe.for_guild(Some(guild_id)).await?;
+    let own = data.db.brain_stats(guild_id).await?;
+    let mut output = format!(
+        "**This server's brain**\n{} words | {} contexts | {} transitions\n",
+        own.tokens, own.nodes, own.edges
+    );
+    if cobe.id() == SHARED_BRAIN {
+        let shared = data.db.brain_stats(SHARED_BRAIN).await?;
+        output.push_str(&format!(
+            "\nThis server uses the **shared brain** instead (`cobe.shared_brain`):\n{} words | {} contexts | {} transitions\n",
+            shared.tokens, shared.nodes, shared.edges
+        ));
+    }
+    ctx.say(output).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn days_become_history_cursors() {
+        let day = parse_day(Some("2024-03-01")).unwrap().unwrap();
+        assert_eq!(day.timestamp(), 1_709_251_200);
+        assert!(parse_day(Some("March 1st")).is_err());
+        assert_eq!(parse_day(None).unwrap(), None);
+        // Snowflakes encode milliseconds since the Discord epoch above bit 22.
+        let id = message_id_at(day);
+        assert_eq!(*id.created_at(), day);
+    }
+}
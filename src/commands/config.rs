@@ -1,8 +1,21 @@
+use poise::serenity_prelude as serenity;
+
+use crate::tasks::schedule::Cron;
 use crate::tasks::speedrun::Mode;
-use crate::{Context, Error};
+use crate::{permissions, Context, Error};
+
+mod channel;
+mod doctor;
+mod edit;
+mod history;
+mod transfer;
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 enum Level {
+    /// Stored per Discord channel, falling back to a server-wide value and
+    /// then the global one. `config set` sets the server-wide value;
+    /// `config channel set` overrides it in one channel.
+    Channel,
     /// Stored per Discord server; each server has its own value.
     Server,
     /// Stored once for the whole bot; changing it affects all servers.
@@ -12,45 +25,71 @@ enum Level {
 impl Level {
     fn label(self) -> &'static str {
         match self {
+            Level::Channel => "channel",
             Level::Server => "server",
             Level::Global => "global",
         }
     }
+
+    /// The section of an exported settings file the setting is stored in.
+    fn exported_as(self) -> Level {
+        match self {
+            Level::Channel | Level::Server => Level::Server,
+            Level::Global => Level::Global,
+        }
+    }
 }
 
 /// The shape a setting's value must take. Stored values are always strings;
 /// the kind is what `config set` validates input against and what the help
 /// text describes, so the background task never has to defend against garbage.
+/// Channel and role kinds are also checked against the server itself (see
+/// `doctor::live_problem`).
 #[derive(Clone, Copy)]
 enum ValueKind {
-    /// A Discord snowflake id (channel, role, …).
-    Id,
+    /// The id of a text channel in the server, where the bot needs the given
+    /// permissions.
+    Channel(serenity::Permissions),
     /// An integer within `[min, max]` inclusive.
     IntRange(i64, i64),
     /// A boolean (`true`/`false`, also accepts `1/0`, `yes/no`, `on/off`).
     Bool,
+    /// A bare name: letters, numbers, hyphen or underscore (e.g. a site name).
+    Name,
     /// A comma-separated list of bare tokens (e.g. game abbreviations).
     CsvList,
-    /// A comma-separated list of Discord snowflake ids (e.g. role ids).
-    IdList,
+    /// A comma-separated list of ids of roles in the server.
+    RoleList,
+    /// A comma-separated list of user ids.
+    UserList,
     /// A comma-separated list of named URLs (`name=https://example.com`).
     NamedUrlList,
     /// A comma-separated `game[/category]:value` override list, where each
     /// value is validated by the named inner kind.
     OverrideList(&'static str),
+    /// Free text of at most the given number of characters (e.g. a prompt).
+    Text(usize),
+    /// A cron expression with optional timezone (`0 18 * * SUN Europe/Stockholm`),
+    /// for per-server task schedules.
+    #[allow(dead_code)] // No per-server scheduled task registered yet.
+    Schedule,
 }
 
 impl ValueKind {
     /// One-word type name shown in help.
     fn type_name(self) -> &'static str {
         match self {
-            ValueKind::Id => "id",
+            ValueKind::Channel(_) => "channel",
             ValueKind::IntRange(..) => "integer",
             ValueKind::Bool => "boolean",
+            ValueKind::Name => "name",
             ValueKind::CsvList => "list",
-            ValueKind::IdList => "id list",
+            ValueKind::RoleList => "role list",
+            ValueKind::UserList => "user list",
             ValueKind::NamedUrlList => "named URL list",
             ValueKind::OverrideList(_) => "overrides",
+            ValueKind::Text(_) => "text",
+            ValueKind::Schedule => "schedule",
         }
     }
 
@@ -61,16 +100,17 @@ impl ValueKind {
             return Err("value is empty".to_string());
         }
         match self {
-            ValueKind::Id => validate_id(value),
+            ValueKind::Channel(_) => validate_id(value),
             ValueKind::IntRange(min, max) => validate_int_range(value, min, max),
             ValueKind::Bool => validate_bool(value),
+            ValueKind::Name => validate_site_name(value),
             ValueKind::CsvList => {
                 if non_empty_tokens(value).next().is_none() {
                     return Err("list has no entries".to_string());
                 }
                 Ok(())
             }
-            ValueKind::IdList => {
+            ValueKind::RoleList | ValueKind::UserList => {
                 let mut seen = false;
                 for token in non_empty_tokens(value) {
                     validate_id(token)?;
@@ -83,10 +123,31 @@ impl ValueKind {
             }
             ValueKind::NamedUrlList => validate_named_url_list(value),
             ValueKind::OverrideList(inner) => validate_override_list(value, inner),
+            ValueKind::Text(max) => {
+                let length = value.chars().count();
+                if length > max {
+                    return Err(format!("text is {} characters long (at most {})", length, max));
+                }
+                Ok(())
+            }
+            ValueKind::Schedule => Cron::parse(value).map(|_| ()),
         }
     }
 }
 
+/// What the bot does in the moderation log: post embeds with buttons and
+/// open review threads off them.
+const MOD_LOG_PERMISSIONS: serenity::Permissions = serenity::Permissions::VIEW_CHANNEL
+    .union(serenity::Permissions::SEND_MESSAGES)
+    .union(serenity::Permissions::EMBED_LINKS)
+    .union(serenity::Permissions::CREATE_PUBLIC_THREADS)
+    .union(serenity::Permissions::SEND_MESSAGES_IN_THREADS);
+
+/// What the bot does in an announcement channel: post embeds.
+const ANNOUNCE_PERMISSIONS: serenity::Permissions = serenity::Permissions::VIEW_CHANNEL
+    .union(serenity::Permissions::SEND_MESSAGES)
+    .union(serenity::Permissions::EMBED_LINKS);
+
 fn validate_id(value: &str) -> Result<(), String> {
     value
         .parse::<u64>()
@@ -102,10 +163,19 @@ fn validate_int_range(value: &str, min: i64, max: i64) -> Result<(), String> {
     }
 }
 
+/// Reads a boolean setting value, in any case; `None` if it isn't one.
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Some(true),
+        "false" | "0" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
 fn validate_bool(value: &str) -> Result<(), String> {
-    match value.to_ascii_lowercase().as_str() {
-        "true" | "1" | "yes" | "on" | "false" | "0" | "no" | "off" => Ok(()),
-        _ => Err(format!("`{}` is not a boolean (use true or false)", value)),
+    match parse_bool(value) {
+        Some(_) => Ok(()),
+        None => Err(format!("`{}` is not a boolean (use true or false)", value)),
     }
 }
 
@@ -202,7 +272,7 @@ const KNOWN_SETTINGS: &[SettingDef] = &[
         scope: "speedrun",
         key: "mod_channel",
         level: Level::Server,
-        kind: ValueKind::Id,
+        kind: ValueKind::Channel(MOD_LOG_PERMISSIONS),
         example: "123456789012345678",
         description: "Moderation log channel: queue submissions are posted here with review buttons",
     },
@@ -210,7 +280,7 @@ const KNOWN_SETTINGS: &[SettingDef] = &[
         scope: "speedrun",
         key: "announce_channel",
         level: Level::Server,
-        kind: ValueKind::Id,
+        kind: ValueKind::Channel(ANNOUNCE_PERMISSIONS),
         example: "123456789012345678",
         description: "Public channel where approved runs are announced",
     },
@@ -226,7 +296,7 @@ const KNOWN_SETTINGS: &[SettingDef] = &[
         scope: "speedrun",
         key: "mod_role",
         level: Level::Server,
-        kind: ValueKind::IdList,
+        kind: ValueKind::RoleList,
         example: "123456789012345678,987654321098765432",
         description: "Role(s) allowed to use the run review buttons in this server (comma-separated)",
     },
@@ -270,6 +340,126 @@ const KNOWN_SETTINGS: &[SettingDef] = &[
         example: "beta=https://beta-quad.example.com",
         description: "Extra Quad randomizer sites selectable by the quad command (live is always built in)",
     },
+    SettingDef {
+        scope: "quad",
+        key: "default_site",
+        level: Level::Channel,
+        kind: ValueKind::Name,
+        example: "beta",
+        description: "Quad site the quad commands use when none is given (live or one from `quad sites`)",
+    },
+    SettingDef {
+        scope: "cobe",
+        key: "learn",
+        level: Level::Channel,
+        kind: ValueKind::Bool,
+        example: "false",
+        description: "Whether the chatter brain learns from messages here (default true)",
+    },
+    SettingDef {
+        scope: "cobe",
+        key: "reply",
+        level: Level::Channel,
+        kind: ValueKind::Bool,
+        example: "false",
+        description: "Whether the chatter brain replies here, to mentions or at random (default true)",
+    },
+    SettingDef {
+        scope: "cobe",
+        key: "reply_chance",
+        level: Level::Channel,
+        kind: ValueKind::IntRange(0, 100),
+        example: "2",
+        description: "Percent chance of replying to a message that doesn't mention the bot (default 0)",
+    },
+    SettingDef {
+        scope: "cobe",
+        key: "min_length",
+        level: Level::Channel,
+        kind: ValueKind::IntRange(0, 2000),
+        example: "20",
+        description: "Shorter messages (in characters) are neither learned nor replied to at random (default 0)",
+    },
+    SettingDef {
+        scope: "cobe",
+        key: "ignore_users",
+        level: Level::Server,
+        kind: ValueKind::UserList,
+        example: "123456789012345678,987654321098765432",
+        description: "Users whose messages the chatter brain neither learns nor answers (comma-separated)",
+    },
+    SettingDef {
+        scope: "cobe",
+        key: "ignore_roles",
+        level: Level::Server,
+        kind: ValueKind::RoleList,
+        example: "123456789012345678",
+        description: "Roles whose members' messages the chatter brain neither learns nor answers (comma-separated)",
+    },
+    SettingDef {
+        scope: "cobe",
+        key: "ignore_bots",
+        level: Level::Server,
+        kind: ValueKind::Bool,
+        example: "true",
+        description: "Whether the chatter brain ignores other bots' messages (default false)",
+    },
+    SettingDef {
+        scope: "cobe",
+        key: "shared_brain",
+        level: Level::Server,
+        kind: ValueKind::Bool,
+        example: "true",
+        description: "Learn into and reply from the brain shared with other servers instead of this server's own",
+    },
+    SettingDef {
+        scope: "llm",
+        key: "daily_tokens",
+        level: Level::Server,
+        kind: ValueKind::IntRange(0, 1_000_000_000),
+        example: "200000",
+        description: "Language model tokens this server may use per day (UTC); 0 turns LLM features off (default unlimited)",
+    },
+    SettingDef {
+        scope: "llm",
+        key: "monthly_tokens",
+        level: Level::Server,
+        kind: ValueKind::IntRange(0, 1_000_000_000),
+        example: "3000000",
+        description: "Language model tokens this server may use per calendar month (UTC); 0 turns LLM features off (default unlimited)",
+    },
+    SettingDef {
+        scope: "llm",
+        key: "chat",
+        level: Level::Server,
+        kind: ValueKind::Bool,
+        example: "true",
+        description: "Answer mentions with the language model instead of the chatter brain (default false)",
+    },
+    SettingDef {
+        scope: "llm",
+        key: "persona",
+        level: Level::Server,
+        kind: ValueKind::Text(2000),
+        example: "You are Shaktool, a cheerful robot from Super Metroid who loves speedrunning.",
+        description: "System prompt giving the bot its personality in chat mode (default: a short Shaktool persona)",
+    },
+    SettingDef {
+        scope: "llm",
+        key: "history",
+        level: Level::Server,
+        kind: ValueKind::IntRange(0, 50),
+        example: "20",
+        description: "Earlier channel messages the model sees when answering a mention (default 15)",
+    },
+    SettingDef {
+        scope: "llm",
+        key: "user_replies_per_hour",
+        level: Level::Server,
+        kind: ValueKind::IntRange(0, 1000),
+        example: "10",
+        description: "Language model answers per user per hour; beyond it the chatter brain answers (default 10)",
+    },
 ];
 
 fn find_setting(scope: &str, key: &str) -> Option<&'static SettingDef> {
@@ -300,12 +490,24 @@ fn known_settings_text() -> String {
     prefix_command,
     slash_command,
     guild_only,
-    required_permissions = "ADMINISTRATOR",
-    subcommands("get", "set", "unset", "list")
+    check = "permissions::config_any",
+    subcommands(
+        "get",
+        "set",
+        "unset",
+        "list",
+        "transfer::export",
+        "transfer::import",
+        "history::history",
+        "history::revert",
+        "channel::channel",
+        "doctor::doctor",
+        "edit::edit"
+    )
 )]
 pub async fn config(ctx: Context<'_>) -> Result<(), Error> {
     ctx.say(format!(
-        "Available subcommands: `get`, `set`, `unset`, `list`\n\nKnown settings:\n{}",
+        "Available subcommands: `get`, `set`, `unset`, `list`, `export`, `import`, `history`, `revert`, `channel`, `doctor`, `edit`\n\nKnown settings:\n{}",
         known_settings_text()
     ))
     .await?;
@@ -313,7 +515,7 @@ pub async fn config(ctx: Context<'_>) -> Result<(), Error> {
 }
 
 /// Gets a configuration value
-#[poise::command(prefix_command, slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+#[poise::command(prefix_command, slash_command, guild_only)]
 pub async fn get(
     ctx: Context<'_>,
     #[description = "Setting scope (e.g. speedrun)"] scope: String,
@@ -323,15 +525,37 @@ pub async fn get(
         ctx.say(unknown_setting_text(&scope, &key)).await?;
         return Ok(());
     };
+    if !permissions::require(ctx, &permissions::config_group(&scope)).await? {
+        return Ok(());
+    }
+    let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
     let value = match def.level {
-        Level::Server => {
-            let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
-            ctx.data().db.get_guild_setting(guild_id, &scope, &key).await?
+        Level::Channel => {
+            let db = &ctx.data().db;
+            let server = db.get_guild_setting(guild_id, &scope, &key).await?;
+            let here = db.resolve_setting(guild_id, ctx.channel_id().get(), &scope, &key).await?;
+            let server_text = match &server {
+                Some(value) => format!("`{}`", value),
+                None => "not set".to_string(),
+            };
+            let here_text = match &here {
+                Some(value) => format!("`{}`", value),
+                None => "not set".to_string(),
+            };
+            ctx.say(format!(
+                "`{}.{}` (channel setting): server default is {}; in this channel it is {}",
+                scope, key, server_text, here_text
+            ))
+            .await?;
+            return Ok(());
         }
+        Level::Server => ctx.data().db.get_guild_setting(guild_id, &scope, &key).await?,
         Level::Global => ctx.data().db.get_global_setting(&scope, &key).await?,
     };
     match value {
         Some(value) => {
+            // Leaves room for the text around the value.
+            let value = shown_value(&value, MESSAGE_LIMIT - 100);
             ctx.say(format!("`{}.{}` ({} setting) = `{}`", scope, key, def.level.label(), value)).await?
         }
         None => ctx.say(format!("`{}.{}` ({} setting) is not set", scope, key, def.level.label())).await?,
@@ -340,7 +564,7 @@ pub async fn get(
 }
 
 /// Sets a configuration value
-#[poise::command(prefix_command, slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+#[poise::command(prefix_command, slash_command, guild_only)]
 pub async fn set(
     ctx: Context<'_>,
     #[description = "Setting scope (e.g. speedrun)"] scope: String,
@@ -353,6 +577,9 @@ pub async fn set(
         ctx.say(unknown_setting_text(&scope, &key)).await?;
         return Ok(());
     };
+    if !permissions::require_config_write(ctx, &scope, def.level == Level::Global).await? {
+        return Ok(());
+    }
     let value = value.trim();
     if let Err(reason) = def.kind.validate(value) {
         ctx.say(format!(
@@ -368,14 +595,31 @@ pub async fn set(
         .await?;
         return Ok(());
     }
+    if def.level != Level::Global {
+        if let Some(problem) = doctor::live_problem(ctx, def.kind, value).await? {
+            ctx.say(format!("Can't set `{}.{}`: {}.", scope, key, problem)).await?;
+            return Ok(());
+        }
+    }
     match def.level {
+        Level::Channel => {
+            let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
+            ctx.data().db.set_guild_setting(guild_id, &scope, &key, value, ctx.author().id.get()).await?;
+            ctx.say(format!(
+                "Set the server default of **channel** setting `{}.{}` = `{}` (override it per channel with `config channel set`)",
+                scope, key, value
+            ))
+            .await?;
+        }
         Level::Server => {
             let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
-            ctx.data().db.set_guild_setting(guild_id, &scope, &key, value).await?;
+            ctx.data().db.set_guild_setting(guild_id, &scope, &key, value, ctx.author().id.get()).await?;
+            let value = shown_value(value, SHOWN_VALUE_CHARS);
             ctx.say(format!("Set **server** setting `{}.{}` = `{}` (only affects this server)", scope, key, value)).await?;
         }
         Level::Global => {
-            ctx.data().db.set_global_setting(&scope, &key, value).await?;
+            ctx.data().db.set_global_setting(&scope, &key, value, ctx.author().id.get()).await?;
+            let value = shown_value(value, SHOWN_VALUE_CHARS);
             ctx.say(format!("Set **global** setting `{}.{}` = `{}` (affects all servers)", scope, key, value)).await?;
         }
     }
@@ -383,7 +627,7 @@ pub async fn set(
 }
 
 /// Removes a configuration value
-#[poise::command(prefix_command, slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+#[poise::command(prefix_command, slash_command, guild_only)]
 pub async fn unset(
     ctx: Context<'_>,
     #[description = "Setting scope (e.g. speedrun)"] scope: String,
@@ -393,14 +637,18 @@ pub async fn unset(
         ctx.say(unknown_setting_text(&scope, &key)).await?;
         return Ok(());
     };
+    if !permissions::require_config_write(ctx, &scope, def.level == Level::Global).await? {
+        return Ok(());
+    }
     let removed = match def.level {
-        Level::Server => {
+        Level::Channel | Level::Server => {
             let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
-            ctx.data().db.delete_guild_setting(guild_id, &scope, &key).await?
+            ctx.data().db.delete_guild_setting(guild_id, &scope, &key, ctx.author().id.get()).await?
         }
-        Level::Global => ctx.data().db.delete_global_setting(&scope, &key).await?,
+        Level::Global => ctx.data().db.delete_global_setting(&scope, &key, ctx.author().id.get()).await?,
     };
     if removed {
+        // Channel overrides stay; only the server default goes.
         ctx.say(format!("Removed {} setting `{}.{}`", def.level.label(), scope, key)).await?;
     } else {
         ctx.say(format!("`{}.{}` ({} setting) is not set", scope, key, def.level.label())).await?;
@@ -409,7 +657,7 @@ pub async fn unset(
 }
 
 /// Lists configured values, or all known settings when no scope is given
-#[poise::command(prefix_command, slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+#[poise::command(prefix_command, slash_command, guild_only)]
 pub async fn list(
     ctx: Context<'_>,
     #[description = "Setting scope (e.g. speedrun)"] scope: Option<String>,
@@ -418,12 +666,16 @@ pub async fn list(
         ctx.say(format!("Known settings:\n{}", known_settings_text())).await?;
         return Ok(());
     };
+    if !permissions::require(ctx, &permissions::config_group(&scope)).await? {
+        return Ok(());
+    }
 
     let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
+    let channel_settings = ctx.data().db.list_channel_settings(guild_id, ctx.channel_id().get(), &scope).await?;
     let server_settings = ctx.data().db.list_guild_settings(guild_id, &scope).await?;
     let global_settings = ctx.data().db.list_global_settings(&scope).await?;
 
-    if server_settings.is_empty() && global_settings.is_empty() {
+    if channel_settings.is_empty() && server_settings.is_empty() && global_settings.is_empty() {
         ctx.say(format!("No settings configured in scope `{}`", scope)).await?;
         return Ok(());
     }
@@ -431,12 +683,15 @@ pub async fn list(
     let format_settings = |settings: &[(String, String)]| {
         settings
             .iter()
-            .map(|(key, value)| format!("`{}` = `{}`", key, value))
+            .map(|(key, value)| format!("`{}` = `{}`", key, shown_value(value, SHOWN_VALUE_CHARS)))
             .collect::<Vec<_>>()
             .join("\n")
     };
 
     let mut output = format!("Settings in scope `{}`:", scope);
+    if !channel_settings.is_empty() {
+        output.push_str(&format!("\n**This channel:**\n{}", format_settings(&channel_settings)));
+    }
     if !server_settings.is_empty() {
         output.push_str(&format!("\n**This server:**\n{}", format_settings(&server_settings)));
     }
@@ -451,6 +706,46 @@ fn unknown_setting_text(scope: &str, key: &str) -> String {
     format!("Unknown setting `{} {}`. Known settings:\n{}", scope, key, known_settings_text())
 }
 
+/// Discord's limit on a message's text.
+const MESSAGE_LIMIT: usize = 2000;
+
+/// How much of a value lists of changes show; a 2000-character persona
+/// would otherwise not fit in a message.
+const SHOWN_VALUE_CHARS: usize = 100;
+
+/// The first `max_chars` characters of `text`.
+fn clip(text: &str, max_chars: usize) -> String {
+    text.chars().take(max_chars).collect()
+}
+
+/// `value` for display, cut short with "…" past `max_chars`.
+fn shown_value(value: &str, max_chars: usize) -> String {
+    if value.chars().count() <= max_chars {
+        return value.to_string();
+    }
+    format!("{}…", clip(value, max_chars.saturating_sub(1)))
+}
+
+/// Joins as many of `lines` as fit in `max_chars`, noting how many were left out.
+fn join_within(lines: &[String], max_chars: usize) -> String {
+    let more = |count: usize| format!("…and {} more", count);
+    let mut joined = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        let separator = if i == 0 { "" } else { "\n" };
+        let left = lines.len() - i - 1;
+        // Room for the note about the lines after this one, should they not fit.
+        let reserved = if left == 0 { 0 } else { 1 + more(left).chars().count() };
+        if joined.chars().count() + separator.len() + line.chars().count() + reserved > max_chars {
+            joined.push_str(separator);
+            joined.push_str(&more(left + 1));
+            return joined;
+        }
+        joined.push_str(separator);
+        joined.push_str(line);
+    }
+    joined
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -469,15 +764,39 @@ mod tests {
     }
 
     #[test]
-    fn id_rejects_non_numeric() {
-        assert!(ValueKind::Id.validate("123456789012345678").is_ok());
-        assert!(ValueKind::Id.validate("banana").is_err());
-        assert!(ValueKind::Id.validate("").is_err());
+    fn every_settings_scope_has_a_permission_group() {
+        for setting in KNOWN_SETTINGS {
+            let group = permissions::config_group(setting.scope);
+            assert!(permissions::find_group(&group).is_some(), "no permission group `{}`", group);
+        }
+    }
+
+    #[test]
+    fn text_is_limited_in_length() {
+        assert!(ValueKind::Text(10).validate("Be nice.").is_ok());
+        assert!(ValueKind::Text(10).validate("Be nice to everyone.").is_err());
+        assert!(ValueKind::Text(10).validate("  ").is_err());
     }
 
     #[test]
-    fn id_list_accepts_one_or_many_ids() {
-        let kind = ValueKind::IdList;
+    fn name_accepts_bare_names_only() {
+        assert!(ValueKind::Name.validate("beta").is_ok());
+        assert!(ValueKind::Name.validate("local_dev-2").is_ok());
+        assert!(ValueKind::Name.validate("beta site").is_err());
+        assert!(ValueKind::Name.validate("beta=https://example.com").is_err());
+    }
+
+    #[test]
+    fn channel_rejects_non_numeric() {
+        let kind = ValueKind::Channel(ANNOUNCE_PERMISSIONS);
+        assert!(kind.validate("123456789012345678").is_ok());
+        assert!(kind.validate("banana").is_err());
+        assert!(kind.validate("").is_err());
+    }
+
+    #[test]
+    fn role_list_accepts_one_or_many_ids() {
+        let kind = ValueKind::RoleList;
         assert!(kind.validate("123456789012345678").is_ok());
         assert!(kind.validate("123456789012345678,987654321098765432").is_ok());
         // Whitespace around entries is tolerated.
@@ -528,4 +847,25 @@ mod tests {
         assert!(thresholds.validate("supermetroid:60").is_ok());
         assert!(thresholds.validate("supermetroid:200").is_err());
     }
+
+    #[test]
+    fn schedule_accepts_cron_with_timezone() {
+        assert!(ValueKind::Schedule.validate("0 18 * * SUN Europe/Stockholm").is_ok());
+        assert!(ValueKind::Schedule.validate("0 18 * * SUN").is_ok());
+        assert!(ValueKind::Schedule.validate("sunday at six").is_err());
+    }
+
+    #[test]
+    fn long_lists_fit_in_a_message() {
+        assert_eq!(shown_value("short", 10), "short");
+        assert_eq!(shown_value("abcdefghijk", 5), "abcd…");
+
+        let lines: Vec<String> = (0..50).map(|i| format!("line {:>40}", i)).collect();
+        let joined = join_within(&lines, 500);
+        assert!(joined.chars().count() <= 500, "{}", joined);
+        assert!(joined.starts_with("line"));
+        let kept = joined.lines().filter(|l| l.starts_with("line")).count();
+        assert!(joined.ends_with(&format!("…and {} more", 50 - kept)), "{}", joined);
+        assert_eq!(join_within(&lines[..2], 500), lines[..2].join("\n"));
+    }
 }
@@ -0,0 +1,139 @@
+use poise::serenity_prelude as serenity;
+
+use super::{doctor, find_setting, unknown_setting_text, Level, SettingDef, KNOWN_SETTINGS};
+use crate::{permissions, Context, Error};
+
+/// Overrides channel-level settings in a single channel
+#[poise::command(prefix_command, slash_command, guild_only, subcommands("set", "unset", "list"))]
+pub async fn channel(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.say(
+        "Available subcommands: `set`, `unset`, `list`. A channel's own value wins over the server's \
+         (`config set`), which wins over the global one.",
+    )
+    .await?;
+    Ok(())
+}
+
+/// Sets a channel-level setting for one channel
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn set(
+    ctx: Context<'_>,
+    #[description = "Channel to override the setting in"] channel: serenity::GuildChannel,
+    #[description = "Setting scope (e.g. quad)"] scope: String,
+    #[description = "Setting key"] key: String,
+    #[description = "Setting value"]
+    #[rest]
+    value: String,
+) -> Result<(), Error> {
+    let Some((guild_id, def)) = channel_setting(ctx, &channel, &scope, &key).await? else {
+        return Ok(());
+    };
+    let value = value.trim();
+    if let Err(reason) = def.kind.validate(value) {
+        ctx.say(format!(
+            "Can't set `{}.{}`: {}.\nExpected a {} — e.g. `channel set #channel {} {} {}`",
+            scope,
+            key,
+            reason,
+            def.kind.type_name(),
+            scope,
+            key,
+            def.example
+        ))
+        .await?;
+        return Ok(());
+    }
+    if let Some(problem) = doctor::live_problem(ctx, def.kind, value).await? {
+        ctx.say(format!("Can't set `{}.{}`: {}.", scope, key, problem)).await?;
+        return Ok(());
+    }
+    let by = ctx.author().id.get();
+    ctx.data().db.set_channel_setting(guild_id, channel.id.get(), &scope, &key, value, by).await?;
+    ctx.say(format!("Set `{}.{}` = `{}` in <#{}> (overrides the server value)", scope, key, value, channel.id))
+        .await?;
+    Ok(())
+}
+
+/// Removes a channel's override, so it uses the server value again
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn unset(
+    ctx: Context<'_>,
+    #[description = "Channel to remove the override from"] channel: serenity::GuildChannel,
+    #[description = "Setting scope (e.g. quad)"] scope: String,
+    #[description = "Setting key"] key: String,
+) -> Result<(), Error> {
+    let Some((guild_id, _)) = channel_setting(ctx, &channel, &scope, &key).await? else {
+        return Ok(());
+    };
+    let by = ctx.author().id.get();
+    if ctx.data().db.delete_channel_setting(guild_id, channel.id.get(), &scope, &key, by).await? {
+        ctx.say(format!("Removed the `{}.{}` override in <#{}>", scope, key, channel.id)).await?;
+    } else {
+        ctx.say(format!("<#{}> has no `{}.{}` override", channel.id, scope, key)).await?;
+    }
+    Ok(())
+}
+
+/// Lists a channel's overrides and the value each channel-level setting has there
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn list(
+    ctx: Context<'_>,
+    #[description = "Channel (default: this one)"] channel: Option<serenity::GuildChannel>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
+    let channel_id = channel.map_or(ctx.channel_id(), |c| c.id).get();
+    let db = &ctx.data().db;
+    let grants = permissions::grants(ctx).await?;
+    let mut lines = Vec::new();
+    for def in KNOWN_SETTINGS.iter().filter(|d| d.level == Level::Channel) {
+        if !grants.allows(&permissions::config_group(def.scope)) {
+            continue;
+        }
+        let own = db.get_channel_setting(guild_id, channel_id, def.scope, def.key).await?;
+        let line = match (own, db.resolve_setting(guild_id, channel_id, def.scope, def.key).await?) {
+            (Some(value), _) => format!("`{}.{}` = `{}` (this channel)", def.scope, def.key, value),
+            (None, Some(value)) => format!("`{}.{}` = `{}` (inherited)", def.scope, def.key, value),
+            (None, None) => format!("`{}.{}` is not set", def.scope, def.key),
+        };
+        lines.push(line);
+    }
+    if lines.is_empty() {
+        ctx.say("There are no channel-level settings you can manage.").await?;
+    } else {
+        ctx.say(format!("Channel settings in <#{}>:\n{}", channel_id, lines.join("\n"))).await?;
+    }
+    Ok(())
+}
+
+/// Looks up a channel-level setting and checks the member may change it in
+/// `channel`, replying and returning `None` when not.
+async fn channel_setting(
+    ctx: Context<'_>,
+    channel: &serenity::GuildChannel,
+    scope: &str,
+    key: &str,
+) -> Result<Option<(u64, &'static SettingDef)>, Error> {
+    let Some(def) = find_setting(scope, key) else {
+        ctx.say(unknown_setting_text(scope, key)).await?;
+        return Ok(None);
+    };
+    if def.level != Level::Channel {
+        ctx.say(format!(
+            "`{}.{}` is a {} setting and can't differ per channel; use `config set` instead.",
+            scope,
+            key,
+            def.level.label()
+        ))
+        .await?;
+        return Ok(None);
+    }
+    let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
+    if channel.guild_id.get() != guild_id {
+        ctx.say("That channel is not in this server.").await?;
+        return Ok(None);
+    }
+    if !permissions::require(ctx, &permissions::config_group(scope)).await? {
+        return Ok(None);
+    }
+    Ok(Some((guild_id, def)))
+}
@@ -0,0 +1,196 @@
+use std::collections::BTreeSet;
+
+use poise::serenity_prelude as serenity;
+
+use super::{find_setting, non_empty_tokens, Level, ValueKind, KNOWN_SETTINGS};
+use crate::{permissions, Context, Error};
+
+/// Longest report `config doctor` sends; the rest is summarised.
+const REPORT_LIMIT: usize = 1900;
+
+/// Checks every stored setting in this server (and the global ones) and reports broken values
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn doctor(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.defer().await?;
+    let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
+    let db = &ctx.data().db;
+    let scopes: BTreeSet<&str> = KNOWN_SETTINGS.iter().map(|d| d.scope).collect();
+
+    let grants = permissions::grants(ctx).await?;
+    let mut checked = 0;
+    let mut problems = Vec::new();
+    for scope in scopes {
+        if !grants.allows(&permissions::config_group(scope)) {
+            continue;
+        }
+        for (key, value) in db.list_guild_settings(guild_id, scope).await? {
+            checked += 1;
+            if let Some(problem) = audit(ctx, Level::Server, scope, &key, &value).await? {
+                problems.push(format!("server `{}.{}` = `{}`: {}", scope, key, value, problem));
+            }
+        }
+        for (key, value) in db.list_global_settings(scope).await? {
+            checked += 1;
+            if let Some(problem) = audit(ctx, Level::Global, scope, &key, &value).await? {
+                problems.push(format!("global `{}.{}` = `{}`: {}", scope, key, value, problem));
+            }
+        }
+    }
+    for (channel_id, scope, key, value) in db.list_channel_overrides(guild_id).await? {
+        if !grants.allows(&permissions::config_group(&scope)) {
+            continue;
+        }
+        checked += 1;
+        let problem = match guild_channel(ctx, channel_id).await? {
+            Err(problem) => Some(format!("the channel is gone ({})", problem)),
+            Ok(_) => audit(ctx, Level::Channel, &scope, &key, &value).await?,
+        };
+        if let Some(problem) = problem {
+            problems.push(format!("<#{}> `{}.{}` = `{}`: {}", channel_id, scope, key, value, problem));
+        }
+    }
+
+    let content = if problems.is_empty() {
+        format!("✅ Checked {} stored setting(s); everything looks fine.", checked)
+    } else {
+        let mut report = format!("⚠️ {} of {} stored setting(s) have problems:", problems.len(), checked);
+        for (i, problem) in problems.iter().enumerate() {
+            if report.len() + problem.len() + 3 > REPORT_LIMIT {
+                report.push_str(&format!("\n…and {} more", problems.len() - i));
+                break;
+            }
+            report.push_str(&format!("\n• {}", problem));
+        }
+        report
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .allowed_mentions(serenity::CreateAllowedMentions::new()),
+    )
+    .await?;
+    Ok(())
+}
+
+/// What's wrong with a value stored at `level`, if anything.
+async fn audit(ctx: Context<'_>, level: Level, scope: &str, key: &str, value: &str) -> Result<Option<String>, Error> {
+    let Some(def) = find_setting(scope, key) else {
+        return Ok(Some("unknown setting; the bot ignores it".to_string()));
+    };
+    if !is_read(def.level, level) {
+        return Ok(Some(format!("is a {} setting, so this value is ignored", def.level.label())));
+    }
+    if let Err(reason) = def.kind.validate(value) {
+        return Ok(Some(reason));
+    }
+    // Ids only mean something within a server.
+    if level == Level::Global {
+        return Ok(None);
+    }
+    live_problem(ctx, def.kind, value).await
+}
+
+/// Whether the bot ever reads a value of a `setting`-level setting stored at
+/// `stored` level. Global values are the last fallback of channel-level
+/// settings too.
+fn is_read(setting: Level, stored: Level) -> bool {
+    match stored {
+        Level::Channel => setting == Level::Channel,
+        Level::Server => setting != Level::Global,
+        Level::Global => setting != Level::Server,
+    }
+}
+
+/// Checks a syntactically valid value against the server: channels must be
+/// text channels in it where the bot has the permissions the setting needs,
+/// roles must exist. Returns the problem, if any; kinds that don't refer to
+/// Discord objects always pass.
+pub(super) async fn live_problem(ctx: Context<'_>, kind: ValueKind, value: &str) -> Result<Option<String>, Error> {
+    match kind {
+        ValueKind::Channel(needed) => {
+            let id = value.trim().parse::<u64>()?;
+            let channel = match guild_channel(ctx, id).await? {
+                Ok(channel) => channel,
+                Err(problem) => return Ok(Some(problem)),
+            };
+            if !matches!(channel.kind, serenity::ChannelType::Text | serenity::ChannelType::News) {
+                return Ok(Some(format!("<#{}> is not a text channel", id)));
+            }
+            let missing = needed - bot_permissions_in(ctx, &channel).await?;
+            if !missing.is_empty() {
+                return Ok(Some(format!(
+                    "the bot is missing {} in <#{}>",
+                    missing.get_permission_names().join(", "),
+                    id
+                )));
+            }
+            Ok(None)
+        }
+        ValueKind::RoleList => {
+            let Some(guild_id) = ctx.guild_id() else {
+                return Ok(None);
+            };
+            let cached: Option<Vec<serenity::RoleId>> = ctx.guild().map(|g| g.roles.keys().copied().collect());
+            let roles = match cached {
+                Some(roles) => roles,
+                None => guild_id.roles(ctx).await?.into_keys().collect(),
+            };
+            let unknown: Vec<&str> = non_empty_tokens(value)
+                .filter(|id| id.parse::<u64>().is_ok_and(|id| !roles.contains(&serenity::RoleId::new(id))))
+                .collect();
+            if unknown.is_empty() {
+                Ok(None)
+            } else {
+                Ok(Some(format!("no role with id {} in this server", unknown.join(", "))))
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+/// The channel `id` if it's in this server, else why not.
+async fn guild_channel(ctx: Context<'_>, id: u64) -> Result<Result<serenity::GuildChannel, String>, Error> {
+    if id == 0 {
+        return Ok(Err("`0` is not a channel".to_string()));
+    }
+    let channel = match serenity::ChannelId::new(id).to_channel(ctx).await {
+        Ok(serenity::Channel::Guild(channel)) => channel,
+        Ok(_) => return Ok(Err(format!("`{}` is not a server channel", id))),
+        // Channels of servers the bot isn't in are forbidden rather than missing.
+        Err(serenity::Error::Http(serenity::HttpError::UnsuccessfulRequest(response)))
+            if response.status_code == serenity::StatusCode::NOT_FOUND
+                || response.status_code == serenity::StatusCode::FORBIDDEN =>
+        {
+            return Ok(Err(format!("no channel with id `{}` that the bot can see", id)));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if Some(channel.guild_id) != ctx.guild_id() {
+        return Ok(Err(format!("<#{}> is in another server", id)));
+    }
+    Ok(Ok(channel))
+}
+
+async fn bot_permissions_in(ctx: Context<'_>, channel: &serenity::GuildChannel) -> Result<serenity::Permissions, Error> {
+    let member = channel.guild_id.member(ctx, ctx.framework().bot_id).await?;
+    let cached = ctx.guild().map(|g| g.user_permissions_in(channel, &member));
+    Ok(match cached {
+        Some(permissions) => permissions,
+        None => channel.guild_id.to_partial_guild(ctx).await?.user_permissions_in(channel, &member),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_stored_at_the_wrong_level_are_unread() {
+        assert!(is_read(Level::Channel, Level::Channel));
+        assert!(is_read(Level::Channel, Level::Server));
+        assert!(is_read(Level::Channel, Level::Global));
+        assert!(!is_read(Level::Server, Level::Channel));
+        assert!(!is_read(Level::Server, Level::Global));
+        assert!(!is_read(Level::Global, Level::Server));
+    }
+}
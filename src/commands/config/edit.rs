@@ -0,0 +1,528 @@
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use poise::serenity_prelude as serenity;
+use poise::serenity_prelude::{
+    ComponentInteraction, ComponentInteractionDataKind, CreateActionRow, CreateButton, CreateInputText,
+    CreateInteractionResponse, CreateModal, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption,
+    MessageId,
+};
+
+use super::{clip, doctor, find_setting, shown_value, Level, SettingDef, ValueKind, KNOWN_SETTINGS, SHOWN_VALUE_CHARS};
+use crate::db::Db;
+use crate::{permissions, Context, Error};
+
+/// How long each step of the editor waits for the member.
+const STEP_TIMEOUT: Duration = Duration::from_secs(300);
+
+const SCOPE_ID: &str = "config_edit:scope";
+const KEY_ID: &str = "config_edit:key";
+const CHANNEL_ID: &str = "config_edit:channel";
+const SERVER_DEFAULT_ID: &str = "config_edit:server";
+const VALUE_ID: &str = "config_edit:value";
+const ENTER_ID: &str = "config_edit:enter";
+const UNSET_ID: &str = "config_edit:unset";
+const SAVE_ID: &str = "config_edit:save";
+const BACK_ID: &str = "config_edit:back";
+const CANCEL_ID: &str = "config_edit:cancel";
+
+const ENDED: &str = "Config editor closed; nothing was changed.";
+
+/// Where an edited value is stored.
+#[derive(Clone, Copy)]
+enum Target {
+    Global,
+    Server(u64),
+    /// `(guild_id, channel_id)`.
+    Channel(u64, u64),
+}
+
+impl Target {
+    fn describe(self) -> String {
+        match self {
+            Target::Global => "global (all servers)".to_string(),
+            Target::Server(_) => "this server".to_string(),
+            Target::Channel(_, channel) => format!("<#{}>", channel),
+        }
+    }
+
+    async fn read(self, db: &Db, def: &SettingDef) -> Result<Option<String>, Error> {
+        match self {
+            Target::Global => db.get_global_setting(def.scope, def.key).await,
+            Target::Server(guild) => db.get_guild_setting(guild, def.scope, def.key).await,
+            Target::Channel(guild, channel) => db.get_channel_setting(guild, channel, def.scope, def.key).await,
+        }
+    }
+
+    async fn write(self, db: &Db, def: &SettingDef, value: Option<&str>, by: u64) -> Result<(), Error> {
+        let (scope, key) = (def.scope, def.key);
+        match (self, value) {
+            (Target::Global, Some(value)) => db.set_global_setting(scope, key, value, by).await?,
+            (Target::Global, None) => {
+                db.delete_global_setting(scope, key, by).await?;
+            }
+            (Target::Server(guild), Some(value)) => db.set_guild_setting(guild, scope, key, value, by).await?,
+            (Target::Server(guild), None) => {
+                db.delete_guild_setting(guild, scope, key, by).await?;
+            }
+            (Target::Channel(guild, channel), Some(value)) => {
+                db.set_channel_setting(guild, channel, scope, key, value, by).await?
+            }
+            (Target::Channel(guild, channel), None) => {
+                db.delete_channel_setting(guild, channel, scope, key, by).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Edits a setting step by step with menus, showing the change before saving it
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn edit(ctx: Context<'_>) -> Result<(), Error> {
+    let grants = permissions::grants(ctx).await?;
+    let owner = permissions::is_owner(ctx);
+    let scopes: Vec<&str> = KNOWN_SETTINGS
+        .iter()
+        .filter(|d| d.level != Level::Global || owner)
+        .map(|d| d.scope)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|scope| grants.allows(&permissions::config_group(scope)))
+        .collect();
+    if scopes.is_empty() {
+        ctx.say("There are no settings you can manage. See `permissions`.").await?;
+        return Ok(());
+    }
+
+    let scope_menu = CreateSelectMenu::new(
+        SCOPE_ID,
+        CreateSelectMenuKind::String {
+            options: scopes.iter().map(|s| CreateSelectMenuOption::new(*s, *s)).collect(),
+        },
+    )
+    .placeholder("Settings scope");
+    let reply = ctx
+        .send(
+            poise::CreateReply::default()
+                .content("**Config editor** — pick a settings scope.")
+                .components(vec![CreateActionRow::SelectMenu(scope_menu), cancel_row()])
+                .allowed_mentions(serenity::CreateAllowedMentions::new()),
+        )
+        .await?;
+    let message_id = reply.message().await?.id;
+    let mut editor = Editor { ctx, reply, message_id, pending: None };
+    let outcome = run(&mut editor).await?;
+    editor.show(outcome, Vec::new()).await
+}
+
+/// The editor message and the press waiting for an answer, if any. Each step
+/// answers the previous press by updating the message in place.
+struct Editor<'a> {
+    ctx: Context<'a>,
+    reply: poise::ReplyHandle<'a>,
+    message_id: MessageId,
+    pending: Option<ComponentInteraction>,
+}
+
+impl Editor<'_> {
+    async fn show(&mut self, content: String, components: Vec<CreateActionRow>) -> Result<(), Error> {
+        let no_pings = serenity::CreateAllowedMentions::new();
+        match self.pending.take() {
+            Some(press) => {
+                let message = serenity::CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .components(components)
+                    .allowed_mentions(no_pings);
+                press.create_response(self.ctx, CreateInteractionResponse::UpdateMessage(message)).await?;
+            }
+            None => {
+                let reply = poise::CreateReply::default().content(content).components(components).allowed_mentions(no_pings);
+                self.reply.edit(self.ctx, reply).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The member's next press on the editor message, unanswered; `None` on
+    /// cancel or timeout.
+    async fn next(&mut self) -> Result<Option<ComponentInteraction>, Error> {
+        wait(self.ctx, self.message_id).await
+    }
+}
+
+/// Walks the member through scope → key → (channel) → value → confirmation
+/// and returns what the message should finally say.
+async fn run(editor: &mut Editor<'_>) -> Result<String, Error> {
+    let ctx = editor.ctx;
+    let Some(press) = editor.next().await? else {
+        return Ok(ENDED.to_string());
+    };
+    let scope = selected(&press).into_iter().next().unwrap_or_default();
+    editor.pending = Some(press);
+
+    // Global settings are for bot owners to change.
+    let owner = permissions::is_owner(ctx);
+    let key_menu = CreateSelectMenu::new(
+        KEY_ID,
+        CreateSelectMenuKind::String {
+            options: KNOWN_SETTINGS
+                .iter()
+                .filter(|d| d.scope == scope && (d.level != Level::Global || owner))
+                .map(|d| CreateSelectMenuOption::new(d.key, d.key).description(clip(d.description, 100)))
+                .collect(),
+        },
+    )
+    .placeholder("Setting");
+    let content = format!("**Config editor** — `{}`: pick a setting.", scope);
+    editor.show(content, vec![CreateActionRow::SelectMenu(key_menu), cancel_row()]).await?;
+    let Some(press) = editor.next().await? else {
+        return Ok(ENDED.to_string());
+    };
+    let key = selected(&press).into_iter().next().unwrap_or_default();
+    editor.pending = Some(press);
+    let Some(def) = find_setting(&scope, &key) else {
+        return Ok(ENDED.to_string());
+    };
+    if def.level == Level::Global && !owner {
+        return Ok(permissions::GLOBAL_DENIED.to_string());
+    }
+
+    let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
+    let target = match def.level {
+        Level::Global => Target::Global,
+        Level::Server => Target::Server(guild_id),
+        Level::Channel => {
+            let channel_menu = CreateSelectMenu::new(
+                CHANNEL_ID,
+                CreateSelectMenuKind::Channel {
+                    channel_types: Some(vec![serenity::ChannelType::Text, serenity::ChannelType::News]),
+                    default_channels: None,
+                },
+            )
+            .placeholder("Override in one channel…");
+            let buttons = CreateActionRow::Buttons(vec![
+                CreateButton::new(SERVER_DEFAULT_ID).label("Server default").style(serenity::ButtonStyle::Primary),
+                CreateButton::new(CANCEL_ID).label("Cancel").style(serenity::ButtonStyle::Secondary),
+            ]);
+            let content = format!(
+                "**Config editor** — `{}.{}` can differ per channel. Change the server default, or pick a channel to override it in.",
+                def.scope, def.key
+            );
+            editor.show(content, vec![CreateActionRow::SelectMenu(channel_menu), buttons]).await?;
+            let Some(press) = editor.next().await? else {
+                return Ok(ENDED.to_string());
+            };
+            let target = match &press.data.kind {
+                ComponentInteractionDataKind::ChannelSelect { values } if !values.is_empty() => {
+                    Target::Channel(guild_id, values[0].get())
+                }
+                _ => Target::Server(guild_id),
+            };
+            editor.pending = Some(press);
+            target
+        }
+    };
+
+    let db = &ctx.data().db;
+    let mut notice = String::new();
+    let mut waiting: Option<ComponentInteraction> = None;
+    loop {
+        let current = target.read(db, def).await?;
+        let press = match waiting.take() {
+            // A press made while a modal was open answers this step directly.
+            Some(press) => press,
+            None => {
+                let content = format!("{}{}", notice, describe(def, target, current.as_deref()));
+                editor.show(content, value_components(def, current.is_some())).await?;
+                let Some(press) = editor.next().await? else {
+                    return Ok(ENDED.to_string());
+                };
+                press
+            }
+        };
+        notice.clear();
+        let new = match press.data.custom_id.as_str() {
+            UNSET_ID => {
+                editor.pending = Some(press);
+                None
+            }
+            ENTER_ID => match ask(ctx, &press, def, current.as_deref(), editor.message_id).await? {
+                Answer::Value(value) => Some(value),
+                // The modal was dismissed and something else pressed instead.
+                Answer::Press(other) => {
+                    waiting = Some(*other);
+                    continue;
+                }
+                Answer::Closed => return Ok(ENDED.to_string()),
+            },
+            _ => {
+                let value = selected(&press).join(",");
+                editor.pending = Some(press);
+                Some(value)
+            }
+        };
+
+        if let Some(value) = &new {
+            if let Err(reason) = def.kind.validate(value) {
+                notice = format!(
+                    "⚠️ `{}` isn't valid: {} (e.g. `{}`).\n\n",
+                    shown_value(value, SHOWN_VALUE_CHARS),
+                    reason,
+                    def.example
+                );
+                continue;
+            }
+            if !matches!(target, Target::Global) {
+                if let Some(problem) = doctor::live_problem(ctx, def.kind, value).await? {
+                    notice = format!("⚠️ `{}` can't be used: {}.\n\n", shown_value(value, SHOWN_VALUE_CHARS), problem);
+                    continue;
+                }
+            }
+        }
+        if new == current {
+            notice = "That's the current value already.\n\n".to_string();
+            continue;
+        }
+
+        let content = format!(
+            "**Config editor** — save this change?\n`{}.{}` in {}: {} → {}",
+            def.scope,
+            def.key,
+            target.describe(),
+            format_value(def, current.as_deref()),
+            format_value(def, new.as_deref())
+        );
+        let buttons = CreateActionRow::Buttons(vec![
+            CreateButton::new(SAVE_ID).label("Save").style(serenity::ButtonStyle::Success),
+            CreateButton::new(BACK_ID).label("Change value").style(serenity::ButtonStyle::Secondary),
+            CreateButton::new(CANCEL_ID).label("Cancel").style(serenity::ButtonStyle::Secondary),
+        ]);
+        editor.show(content, vec![buttons]).await?;
+        let Some(confirm) = editor.next().await? else {
+            return Ok(ENDED.to_string());
+        };
+        let save = confirm.data.custom_id == SAVE_ID;
+        editor.pending = Some(confirm);
+        if save {
+            target.write(db, def, new.as_deref(), ctx.author().id.get()).await?;
+            return Ok(format!(
+                "✅ Saved `{}.{}` in {}: {} → {}",
+                def.scope,
+                def.key,
+                target.describe(),
+                format_value(def, current.as_deref()),
+                format_value(def, new.as_deref())
+            ));
+        }
+    }
+}
+
+/// The value step's text: what the setting is and its current value.
+fn describe(def: &SettingDef, target: Target, current: Option<&str>) -> String {
+    let how = match def.kind {
+        ValueKind::Channel(_) => "Pick a channel",
+        ValueKind::RoleList => "Pick one or more roles",
+        ValueKind::UserList => "Pick one or more users",
+        ValueKind::Bool => "Pick a value",
+        _ => "Press **Enter value**",
+    };
+    format!(
+        "**Config editor** — `{}.{}` ({}, {})\n{}\nIn {}: {}\n\n{}.",
+        def.scope,
+        def.key,
+        def.level.label(),
+        def.kind.type_name(),
+        def.description,
+        target.describe(),
+        format_value(def, current),
+        how
+    )
+}
+
+/// The input for a setting's kind: pickers for Discord objects and booleans,
+/// a button opening a text modal for everything else.
+fn value_components(def: &SettingDef, is_set: bool) -> Vec<CreateActionRow> {
+    let mut rows = Vec::new();
+    let mut buttons = Vec::new();
+    match def.kind {
+        ValueKind::Channel(_) => rows.push(CreateActionRow::SelectMenu(
+            CreateSelectMenu::new(
+                VALUE_ID,
+                CreateSelectMenuKind::Channel {
+                    channel_types: Some(vec![serenity::ChannelType::Text, serenity::ChannelType::News]),
+                    default_channels: None,
+                },
+            )
+            .placeholder("Channel"),
+        )),
+        ValueKind::RoleList => rows.push(CreateActionRow::SelectMenu(
+            CreateSelectMenu::new(VALUE_ID, CreateSelectMenuKind::Role { default_roles: None })
+                .placeholder("Roles")
+                .min_values(1)
+                .max_values(25),
+        )),
+        ValueKind::UserList => rows.push(CreateActionRow::SelectMenu(
+            CreateSelectMenu::new(VALUE_ID, CreateSelectMenuKind::User { default_users: None })
+                .placeholder("Users")
+                .min_values(1)
+                .max_values(25),
+        )),
+        ValueKind::Bool => rows.push(CreateActionRow::SelectMenu(
+            CreateSelectMenu::new(
+                VALUE_ID,
+                CreateSelectMenuKind::String {
+                    options: vec![
+                        CreateSelectMenuOption::new("true", "true"),
+                        CreateSelectMenuOption::new("false", "false"),
+                    ],
+                },
+            )
+            .placeholder("Value"),
+        )),
+        _ => buttons.push(CreateButton::new(ENTER_ID).label("Enter value").style(serenity::ButtonStyle::Primary)),
+    }
+    if is_set {
+        buttons.push(CreateButton::new(UNSET_ID).label("Unset").style(serenity::ButtonStyle::Danger));
+    }
+    buttons.push(CreateButton::new(CANCEL_ID).label("Cancel").style(serenity::ButtonStyle::Secondary));
+    rows.push(CreateActionRow::Buttons(buttons));
+    rows
+}
+
+enum Answer {
+    Value(String),
+    Press(Box<ComponentInteraction>),
+    /// Cancelled or timed out.
+    Closed,
+}
+
+/// Opens a text modal for the value, prefilled with the current one. A
+/// dismissed modal can't be detected, so a press on the editor message while
+/// waiting counts as moving on.
+async fn ask(
+    ctx: Context<'_>,
+    press: &ComponentInteraction,
+    def: &SettingDef,
+    current: Option<&str>,
+    message_id: MessageId,
+) -> Result<Answer, Error> {
+    let modal_id = format!("config_edit_modal:{}", press.id);
+    let style = match def.kind {
+        ValueKind::CsvList | ValueKind::NamedUrlList | ValueKind::OverrideList(_) | ValueKind::Text(_) => {
+            serenity::InputTextStyle::Paragraph
+        }
+        _ => serenity::InputTextStyle::Short,
+    };
+    let mut input = CreateInputText::new(style, "Value", "value").placeholder(clip(def.example, 100)).required(true);
+    if let Some(current) = current {
+        input = input.value(clip(current, 4000));
+    }
+    let modal = CreateModal::new(&modal_id, clip(&format!("{}.{}", def.scope, def.key), 45))
+        .components(vec![CreateActionRow::InputText(input)]);
+    press.create_response(ctx, CreateInteractionResponse::Modal(modal)).await?;
+
+    let submitted = serenity::ModalInteractionCollector::new(ctx.serenity_context())
+        .author_id(ctx.author().id)
+        .custom_ids(vec![modal_id])
+        .timeout(STEP_TIMEOUT);
+    tokio::select! {
+        submit = submitted => {
+            let Some(submit) = submit else {
+                return Ok(Answer::Closed);
+            };
+            submit.create_response(ctx, CreateInteractionResponse::Acknowledge).await?;
+            let value = submit
+                .data
+                .components
+                .iter()
+                .flat_map(|row| &row.components)
+                .find_map(|c| match c {
+                    serenity::ActionRowComponent::InputText(input) => input.value.clone(),
+                    _ => None,
+                })
+                .unwrap_or_default();
+            Ok(Answer::Value(value.trim().to_string()))
+        }
+        other = wait(ctx, message_id) => Ok(match other? {
+            Some(other) => Answer::Press(Box::new(other)),
+            None => Answer::Closed,
+        }),
+    }
+}
+
+/// The member's next press on the editor message, `None` on cancel or
+/// timeout. The cancel press is acknowledged here.
+async fn wait(ctx: Context<'_>, message_id: MessageId) -> Result<Option<ComponentInteraction>, Error> {
+    let press = serenity::ComponentInteractionCollector::new(ctx.serenity_context())
+        .author_id(ctx.author().id)
+        .message_id(message_id)
+        .timeout(STEP_TIMEOUT)
+        .await;
+    match press {
+        Some(press) if press.data.custom_id == CANCEL_ID => {
+            press.create_response(ctx, CreateInteractionResponse::Acknowledge).await?;
+            Ok(None)
+        }
+        press => Ok(press),
+    }
+}
+
+/// Values picked in a select menu, as setting values.
+fn selected(press: &ComponentInteraction) -> Vec<String> {
+    match &press.data.kind {
+        ComponentInteractionDataKind::StringSelect { values } => values.clone(),
+        ComponentInteractionDataKind::ChannelSelect { values } => values.iter().map(|v| v.to_string()).collect(),
+        ComponentInteractionDataKind::RoleSelect { values } => values.iter().map(|v| v.to_string()).collect(),
+        ComponentInteractionDataKind::UserSelect { values } => values.iter().map(|v| v.to_string()).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// A value as shown in the editor; channels, roles and users as mentions.
+fn format_value(def: &SettingDef, value: Option<&str>) -> String {
+    let Some(value) = value else {
+        return "(unset)".to_string();
+    };
+    match def.kind {
+        ValueKind::Channel(_) => format!("<#{}> (`{}`)", value, value),
+        ValueKind::RoleList => value.split(',').map(|id| format!("<@&{}>", id.trim())).collect::<Vec<_>>().join(", "),
+        ValueKind::UserList => value.split(',').map(|id| format!("<@{}>", id.trim())).collect::<Vec<_>>().join(", "),
+        _ => format!("`{}`", shown_value(value, SHOWN_VALUE_CHARS)),
+    }
+}
+
+fn cancel_row() -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(CANCEL_ID).label("Cancel").style(serenity::ButtonStyle::Secondary)
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_shown_as_mentions() {
+        let mod_channel = find_setting("speedrun", "mod_channel").unwrap();
+        assert_eq!(format_value(mod_channel, Some("123")), "<#123> (`123`)");
+        let mod_role = find_setting("speedrun", "mod_role").unwrap();
+        assert_eq!(format_value(mod_role, Some("1, 2")), "<@&1>, <@&2>");
+        assert_eq!(format_value(mod_role, None), "(unset)");
+        let ignored = find_setting("cobe", "ignore_users").unwrap();
+        assert_eq!(format_value(ignored, Some("3")), "<@3>");
+        let games = find_setting("speedrun", "games").unwrap();
+        assert_eq!(format_value(games, Some("smz3")), "`smz3`");
+    }
+
+    #[test]
+    fn longest_persona_is_clipped() {
+        let persona = find_setting("llm", "persona").unwrap();
+        let ValueKind::Text(max) = persona.kind else {
+            panic!("persona is free text");
+        };
+        let longest = "a".repeat(max);
+        let shown = format_value(persona, Some(&longest));
+        assert_eq!(shown.chars().count(), SHOWN_VALUE_CHARS + 2);
+        assert!(shown.ends_with("…`"));
+    }
+}
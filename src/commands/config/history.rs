@@ -0,0 +1,159 @@
+use poise::serenity_prelude as serenity;
+
+use super::{doctor, find_setting, join_within, shown_value, unknown_setting_text, Level, MESSAGE_LIMIT, SHOWN_VALUE_CHARS};
+use crate::db::SettingChange;
+use crate::{permissions, Context, Error};
+
+/// Changes shown by `config history`.
+const HISTORY_LIMIT: i64 = 15;
+
+/// Shows recent changes to this server's and the global settings
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn history(
+    ctx: Context<'_>,
+    #[description = "Setting scope (e.g. speedrun)"] scope: Option<String>,
+    #[description = "Setting key"] key: Option<String>,
+) -> Result<(), Error> {
+    if let Some(scope) = &scope {
+        if !permissions::require(ctx, &permissions::config_group(scope)).await? {
+            return Ok(());
+        }
+    }
+    let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
+    // Only show scopes the member may manage.
+    let grants = permissions::grants(ctx).await?;
+    let changes: Vec<SettingChange> = ctx
+        .data()
+        .db
+        .setting_history(guild_id, scope.as_deref(), key.as_deref(), HISTORY_LIMIT)
+        .await?
+        .into_iter()
+        .filter(|change| grants.allows(&permissions::config_group(&change.scope)))
+        .collect();
+    let content = if changes.is_empty() {
+        "No recorded setting changes.".to_string()
+    } else {
+        let lines: Vec<String> = changes.iter().map(format_change).collect();
+        let header = "**Setting changes** (newest first; undo one with `config revert <id>`)";
+        let room = MESSAGE_LIMIT - header.chars().count() - 1;
+        format!("{}\n{}", header, join_within(&lines, room))
+    };
+    // Name who changed what without pinging them.
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .allowed_mentions(serenity::CreateAllowedMentions::new()),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Restores the value a setting had before a recorded change
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn revert(
+    ctx: Context<'_>,
+    #[description = "Change id (see config history)"] id: i64,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
+    let db = &ctx.data().db;
+    let change = match db.setting_change(id).await? {
+        // Other servers' changes are not this server's to undo.
+        Some(change) if change.guild_id.is_none_or(|g| g == guild_id) => change,
+        _ => {
+            ctx.say(format!("No change #{} for this server. See `config history`.", id)).await?;
+            return Ok(());
+        }
+    };
+    let level = match (change.guild_id, change.channel_id) {
+        (Some(_), Some(_)) => Level::Channel,
+        (Some(_), None) => Level::Server,
+        (None, _) => Level::Global,
+    };
+    if !permissions::require_config_write(ctx, &change.scope, level == Level::Global).await? {
+        return Ok(());
+    }
+    let Some(def) = find_setting(&change.scope, &change.key) else {
+        ctx.say(unknown_setting_text(&change.scope, &change.key)).await?;
+        return Ok(());
+    };
+    // A channel-level setting's server-wide value is recorded as a server change.
+    let matches = def.level == level || (def.level == Level::Channel && level == Level::Server);
+    if !matches {
+        ctx.say(format!(
+            "`{}.{}` is now a {} setting, so change #{} can't be reverted.",
+            change.scope,
+            change.key,
+            def.level.label(),
+            id
+        ))
+        .await?;
+        return Ok(());
+    }
+    if let Some(old) = &change.old_value {
+        if let Err(reason) = def.kind.validate(old) {
+            let old = shown_value(old, SHOWN_VALUE_CHARS);
+            ctx.say(format!("The old value `{}` is no longer valid: {}.", old, reason)).await?;
+            return Ok(());
+        }
+        if level != Level::Global {
+            if let Some(problem) = doctor::live_problem(ctx, def.kind, old).await? {
+                let old = shown_value(old, SHOWN_VALUE_CHARS);
+                ctx.say(format!("The old value `{}` can't be restored: {}.", old, problem)).await?;
+                return Ok(());
+            }
+        }
+    }
+
+    let by = ctx.author().id.get();
+    let (scope, key) = (change.scope.as_str(), change.key.as_str());
+    match (level, change.old_value.as_deref()) {
+        (Level::Channel, Some(old)) => {
+            let channel_id = change.channel_id.unwrap_or_default();
+            db.set_channel_setting(guild_id, channel_id, scope, key, old, by).await?
+        }
+        (Level::Channel, None) => {
+            db.delete_channel_setting(guild_id, change.channel_id.unwrap_or_default(), scope, key, by).await?;
+        }
+        (Level::Server, Some(old)) => db.set_guild_setting(guild_id, scope, key, old, by).await?,
+        (Level::Server, None) => {
+            db.delete_guild_setting(guild_id, scope, key, by).await?;
+        }
+        (Level::Global, Some(old)) => db.set_global_setting(scope, key, old, by).await?,
+        (Level::Global, None) => {
+            db.delete_global_setting(scope, key, by).await?;
+        }
+    }
+    ctx.say(format!(
+        "Reverted change #{}: {} setting `{}.{}` is {}.",
+        id,
+        level.label(),
+        scope,
+        key,
+        match &change.old_value {
+            Some(old) => format!("`{}` again", shown_value(old, SHOWN_VALUE_CHARS)),
+            None => "unset again".to_string(),
+        }
+    ))
+    .await?;
+    Ok(())
+}
+
+fn format_change(change: &SettingChange) -> String {
+    let value = |v: &Option<String>| v.as_ref().map_or_else(|| "(unset)".to_string(), |v| format!("`{}`", shown_value(v, SHOWN_VALUE_CHARS)));
+    let level = match (change.guild_id, change.channel_id) {
+        (Some(_), Some(channel)) => format!("<#{}>", channel),
+        (Some(_), None) => "server".to_string(),
+        (None, _) => "global".to_string(),
+    };
+    format!(
+        "`#{}` <t:{}:R> <@{}> — {} `{}.{}`: {} → {}",
+        change.id,
+        change.changed_at,
+        change.changed_by,
+        level,
+        change.scope,
+        change.key,
+        value(&change.old_value),
+        value(&change.new_value)
+    )
+}
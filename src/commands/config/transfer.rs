@@ -0,0 +1,358 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use poise::serenity_prelude as serenity;
+use serde::{Deserialize, Serialize};
+
+use super::{doctor, find_setting, join_within, shown_value, Level, KNOWN_SETTINGS, MESSAGE_LIMIT, SHOWN_VALUE_CHARS};
+use crate::{permissions, Context, Error};
+
+/// Format version of exported files; bumped if the layout ever changes.
+const FORMAT_VERSION: u32 = 1;
+
+/// How long the import confirmation buttons stay live.
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Room for the list of changes in the import messages, leaving space for
+/// the file name and the text around it.
+const LISTED_CHANGES_CHARS: usize = MESSAGE_LIMIT - 300;
+
+/// Largest attachment `config import` accepts.
+const MAX_IMPORT_BYTES: u32 = 256 * 1024;
+
+/// `scope -> key -> value`.
+type Section = BTreeMap<String, BTreeMap<String, String>>;
+
+/// An exported settings file: this server's settings and, optionally, the
+/// global ones. Channel-level settings export their server-wide value;
+/// per-channel overrides are tied to this server's channels and aren't
+/// exported.
+#[derive(Serialize, Deserialize, Default)]
+struct SettingsFile {
+    version: u32,
+    /// Server the file was exported from; informational only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    guild_id: Option<u64>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    server: Section,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    global: Section,
+}
+
+/// One setting an import would change.
+#[derive(Debug, PartialEq)]
+struct Change {
+    level: Level,
+    scope: String,
+    key: String,
+    old: Option<String>,
+    new: String,
+}
+
+/// Exports this server's settings (and optionally the global ones) as a JSON file
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn export(
+    ctx: Context<'_>,
+    #[description = "Only export this scope (e.g. speedrun)"] scope: Option<String>,
+    #[description = "Include global (all servers) settings"] global: Option<bool>,
+) -> Result<(), Error> {
+    if let Some(scope) = &scope {
+        if !permissions::require(ctx, &permissions::config_group(scope)).await? {
+            return Ok(());
+        }
+    }
+    let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
+    let mut file = read_settings(ctx, guild_id, scope.as_deref(), global.unwrap_or(false)).await?;
+    // Without a scope, export the scopes the member may manage.
+    let grants = permissions::grants(ctx).await?;
+    for section in [&mut file.server, &mut file.global] {
+        section.retain(|scope, _| grants.allows(&permissions::config_group(scope)));
+    }
+    let count = file.server.values().chain(file.global.values()).map(BTreeMap::len).sum::<usize>();
+    let json = serde_json::to_string_pretty(&file)?;
+    let attachment = serenity::CreateAttachment::bytes(json, format!("shaktool-config-{}.json", guild_id));
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!(
+                "Exported {} setting(s). Load them into a server with `config import`.",
+                count
+            ))
+            .attachment(attachment),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Imports settings from an exported JSON file, after showing the changes
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn import(
+    ctx: Context<'_>,
+    #[description = "JSON file from config export"] file: serenity::Attachment,
+) -> Result<(), Error> {
+    if file.size > MAX_IMPORT_BYTES {
+        ctx.say(format!("`{}` is too large to be a settings export.", file.filename)).await?;
+        return Ok(());
+    }
+    let parsed = match serde_json::from_slice::<SettingsFile>(&file.download().await?) {
+        Ok(parsed) if parsed.version == FORMAT_VERSION => parsed,
+        Ok(parsed) => {
+            ctx.say(format!("Unsupported export format version {} (expected {}).", parsed.version, FORMAT_VERSION))
+                .await?;
+            return Ok(());
+        }
+        Err(e) => {
+            ctx.say(format!("`{}` is not a settings export: {}", file.filename, e)).await?;
+            return Ok(());
+        }
+    };
+
+    let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
+    let current = read_settings(ctx, guild_id, None, true).await?;
+    let grants = permissions::grants(ctx).await?;
+    let mut denied = Vec::new();
+    for scope in parsed.server.keys().chain(parsed.global.keys()) {
+        let group = permissions::config_group(scope);
+        if !grants.allows(&group) {
+            denied.push(format!("`{}`: you need `{}` to import these settings", scope, group));
+        }
+    }
+    let owner = permissions::is_owner(ctx);
+    let changes = match plan_import(&parsed, &current) {
+        Ok(changes) => {
+            if !owner {
+                for change in changes.iter().filter(|c| c.level == Level::Global) {
+                    denied.push(format!("`{}.{}`: {}", change.scope, change.key, permissions::GLOBAL_DENIED));
+                }
+            }
+            // Ids in the file must also exist in this server.
+            for change in changes.iter().filter(|c| c.level != Level::Global) {
+                let def = find_setting(&change.scope, &change.key).ok_or("planned an unknown setting")?;
+                if let Some(problem) = doctor::live_problem(ctx, def.kind, &change.new).await? {
+                    denied.push(format!("`{}.{}`: {}", change.scope, change.key, problem));
+                }
+            }
+            Ok(changes)
+        }
+        Err(errors) => Err(errors),
+    };
+    let changes = match changes {
+        Ok(changes) if denied.is_empty() => changes,
+        Ok(_) => {
+            ctx.say(format!("Nothing was imported:\n{}", denied.join("\n"))).await?;
+            return Ok(());
+        }
+        Err(mut errors) => {
+            errors.extend(denied);
+            ctx.say(format!("Nothing was imported; fix these entries first:\n{}", errors.join("\n"))).await?;
+            return Ok(());
+        }
+    };
+    if changes.is_empty() {
+        ctx.say("Every setting in the file already has that value; nothing to import.").await?;
+        return Ok(());
+    }
+
+    let confirm_id = format!("config_import_confirm:{}", ctx.id());
+    let cancel_id = format!("config_import_cancel:{}", ctx.id());
+    let buttons = serenity::CreateActionRow::Buttons(vec![
+        serenity::CreateButton::new(&confirm_id).label("Apply").style(serenity::ButtonStyle::Success),
+        serenity::CreateButton::new(&cancel_id).label("Cancel").style(serenity::ButtonStyle::Secondary),
+    ]);
+    let reply = ctx
+        .send(
+            poise::CreateReply::default()
+                .content(format!(
+                    "Importing `{}` would change {} setting(s):\n{}\n\nSettings not in the file are left as they are.",
+                    file.filename,
+                    changes.len(),
+                    format_changes(&changes, LISTED_CHANGES_CHARS)
+                ))
+                .components(vec![buttons]),
+        )
+        .await?;
+
+    let pressed = serenity::ComponentInteractionCollector::new(ctx.serenity_context())
+        .author_id(ctx.author().id)
+        .custom_ids(vec![confirm_id.clone(), cancel_id])
+        .timeout(CONFIRM_TIMEOUT)
+        .await;
+    let outcome = match &pressed {
+        Some(press) if press.data.custom_id == confirm_id => {
+            apply(ctx, guild_id, &changes).await?;
+            format!("✅ Imported {} setting(s) from `{}`.", changes.len(), file.filename)
+        }
+        Some(_) => "Import cancelled; nothing was changed.".to_string(),
+        None => "Import timed out; nothing was changed.".to_string(),
+    };
+    if let Some(press) = pressed {
+        press.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge).await?;
+    }
+    reply
+        .edit(
+            ctx,
+            poise::CreateReply::default()
+                .content(format!("{}\n{}", outcome, format_changes(&changes, LISTED_CHANGES_CHARS)))
+                .components(Vec::new()),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Reads the stored values of every known setting (optionally one scope),
+/// server ones for `guild_id` and, with `include_global`, the global ones.
+async fn read_settings(
+    ctx: Context<'_>,
+    guild_id: u64,
+    scope: Option<&str>,
+    include_global: bool,
+) -> Result<SettingsFile, Error> {
+    let db = &ctx.data().db;
+    let mut file = SettingsFile { version: FORMAT_VERSION, guild_id: Some(guild_id), ..Default::default() };
+    for def in KNOWN_SETTINGS.iter().filter(|d| scope.is_none_or(|s| s == d.scope)) {
+        let (value, section) = match def.level {
+            Level::Channel | Level::Server => (db.get_guild_setting(guild_id, def.scope, def.key).await?, &mut file.server),
+            Level::Global if include_global => (db.get_global_setting(def.scope, def.key).await?, &mut file.global),
+            Level::Global => continue,
+        };
+        if let Some(value) = value {
+            section.entry(def.scope.to_string()).or_default().insert(def.key.to_string(), value);
+        }
+    }
+    Ok(file)
+}
+
+/// Validates every entry of `file` against the settings registry and returns
+/// the ones that differ from `current`, or every problem found.
+fn plan_import(file: &SettingsFile, current: &SettingsFile) -> Result<Vec<Change>, Vec<String>> {
+    let mut changes = Vec::new();
+    let mut errors = Vec::new();
+    for (level, section, existing) in [
+        (Level::Server, &file.server, &current.server),
+        (Level::Global, &file.global, &current.global),
+    ] {
+        for (scope, values) in section {
+            for (key, value) in values {
+                let Some(def) = find_setting(scope, key) else {
+                    errors.push(format!("`{}.{}`: unknown setting", scope, key));
+                    continue;
+                };
+                if def.level.exported_as() != level {
+                    errors.push(format!(
+                        "`{}.{}`: is a {} setting, but the file lists it as {}",
+                        scope,
+                        key,
+                        def.level.exported_as().label(),
+                        level.label()
+                    ));
+                    continue;
+                }
+                let value = value.trim();
+                if let Err(reason) = def.kind.validate(value) {
+                    errors.push(format!("`{}.{}`: {} (e.g. `{}`)", scope, key, reason, def.example));
+                    continue;
+                }
+                let old = existing.get(scope).and_then(|v| v.get(key)).cloned();
+                if old.as_deref() != Some(value) {
+                    changes.push(Change {
+                        level,
+                        scope: scope.clone(),
+                        key: key.clone(),
+                        old,
+                        new: value.to_string(),
+                    });
+                }
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(changes)
+    } else {
+        Err(errors)
+    }
+}
+
+async fn apply(ctx: Context<'_>, guild_id: u64, changes: &[Change]) -> Result<(), Error> {
+    let db = &ctx.data().db;
+    let by = ctx.author().id.get();
+    for change in changes {
+        match change.level {
+            Level::Channel | Level::Server => {
+                db.set_guild_setting(guild_id, &change.scope, &change.key, &change.new, by).await?
+            }
+            Level::Global => db.set_global_setting(&change.scope, &change.key, &change.new, by).await?,
+        }
+    }
+    Ok(())
+}
+
+/// One line per change, as many as fit in `max_chars`.
+fn format_changes(changes: &[Change], max_chars: usize) -> String {
+    let lines: Vec<String> = changes
+        .iter()
+        .map(|c| {
+            let new = shown_value(&c.new, SHOWN_VALUE_CHARS);
+            match &c.old {
+                Some(old) => {
+                    let old = shown_value(old, SHOWN_VALUE_CHARS);
+                    format!("{} `{}.{}`: `{}` → `{}`", c.level.label(), c.scope, c.key, old, new)
+                }
+                None => format!("{} `{}.{}`: (unset) → `{}`", c.level.label(), c.scope, c.key, new),
+            }
+        })
+        .collect();
+    join_within(&lines, max_chars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ValueKind;
+    use super::*;
+
+    fn file(json: &str) -> SettingsFile {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn import_reports_only_changed_settings() {
+        let current = file(r#"{"version":1,"server":{"speedrun":{"games":"smz3","mod_channel":"1"}}}"#);
+        let incoming = file(
+            r#"{"version":1,"server":{"speedrun":{"games":"smz3","mod_channel":"2"}},"global":{"speedrun":{"threshold":"60"}}}"#,
+        );
+        let changes = plan_import(&incoming, &current).unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].key, "mod_channel");
+        assert_eq!(changes[0].old.as_deref(), Some("1"));
+        assert_eq!(changes[1].level, Level::Global);
+        assert_eq!(changes[1].old, None);
+    }
+
+    #[test]
+    fn import_rejects_unknown_invalid_and_misplaced_settings() {
+        let incoming = file(
+            r#"{"version":1,"server":{"speedrun":{"bogus":"1","mod_channel":"general","threshold":"50"}}}"#,
+        );
+        let errors = plan_import(&incoming, &SettingsFile::default()).unwrap_err();
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+    }
+
+    #[test]
+    fn import_messages_fit_with_the_longest_persona() {
+        let def = find_setting("llm", "persona").unwrap();
+        let ValueKind::Text(max) = def.kind else {
+            panic!("persona is free text");
+        };
+        let longest = "a".repeat(max);
+        let changes: Vec<Change> = (0..30)
+            .map(|_| Change {
+                level: Level::Server,
+                scope: "llm".to_string(),
+                key: "persona".to_string(),
+                old: Some(longest.clone()),
+                new: longest.clone(),
+            })
+            .collect();
+        let listed = format_changes(&changes, LISTED_CHANGES_CHARS);
+        assert!(listed.chars().count() <= LISTED_CHANGES_CHARS);
+        assert!(listed.ends_with(" more"), "{}", listed);
+    }
+}
@@ -0,0 +1,70 @@
+use crate::db::backup::{self, BackupConfig};
+use crate::{Context, Error};
+
+/// Shows database statistics (bot owners only); subcommands: `stats`, `backup`
+#[poise::command(prefix_command, slash_command, owners_only, subcommands("stats", "backup"))]
+pub async fn db(ctx: Context<'_>) -> Result<(), Error> {
+    stats_inner(ctx).await
+}
+
+/// Shows row counts per task and key prefix, and the database file size (bot owners only)
+#[poise::command(prefix_command, slash_command, owners_only)]
+pub async fn stats(ctx: Context<'_>) -> Result<(), Error> {
+    stats_inner(ctx).await
+}
+
+/// Writes a timestamped backup of the live database to `BACKUP_DIR` (bot owners only)
+#[poise::command(prefix_command, slash_command, owners_only)]
+pub async fn backup(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(config) = BackupConfig::from_env() else {
+        ctx.say("Backups are disabled; set `BACKUP_DIR` to enable them.").await?;
+        return Ok(());
+    };
+    ctx.defer().await?;
+    let backup = backup::create(&ctx.data().db, &config).await?;
+    ctx.say(format!(
+        "Backed up to `{}` ({}, schema v{}). Kept the newest {} backups, deleted {}.",
+        backup.path.display(),
+        format_bytes(backup.size as i64),
+        backup.version,
+        config.keep,
+        backup.pruned
+    ))
+    .await?;
+    Ok(())
+}
+
+async fn stats_inner(ctx: Context<'_>) -> Result<(), Error> {
+    let db = &ctx.data().db;
+    let (size, free) = db.file_size().await?;
+    let mut output = format!(
+        "**Database**\nFile size: {} ({} free) | settings: {} rows\n\n**Task state**\n",
+        format_bytes(size),
+        format_bytes(free),
+        db.settings_count().await?
+    );
+    let stats = db.task_state_stats().await?;
+    if stats.is_empty() {
+        output.push_str("No entries.\n");
+    }
+    for row in &stats {
+        output.push_str(&format!("`{}` `{}` — {} rows", row.task, row.prefix, row.rows));
+        if row.expiring > 0 {
+            output.push_str(&format!(" ({} expiring)", row.expiring));
+        }
+        output.push('\n');
+    }
+    ctx.say(output).await?;
+    Ok(())
+}
+
+fn format_bytes(bytes: i64) -> String {
+    let bytes = bytes as f64;
+    if bytes >= 1024.0 * 1024.0 {
+        format!("{:.1} MiB", bytes / (1024.0 * 1024.0))
+    } else if bytes >= 1024.0 {
+        format!("{:.1} KiB", bytes / 1024.0)
+    } else {
+        format!("{} B", bytes)
+    }
+}
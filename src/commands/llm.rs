@@ -0,0 +1,99 @@
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude as serenity;
+
+use crate::api::llm::LlmUsage;
+use crate::db::llm_usage::UsageTotal;
+use crate::util::llm_usage::{period_starts, Budget};
+use crate::{Context, Error};
+
+/// Users listed in `llm usage`, heaviest first.
+const TOP_USERS: usize = 10;
+
+/// Shows this server's language model usage this month; subcommands: `usage`
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "ADMINISTRATOR",
+    subcommands("usage")
+)]
+pub async fn llm(ctx: Context<'_>) -> Result<(), Error> {
+    usage_inner(ctx, false).await
+}
+
+/// Shows this server's language model tokens by feature and user, and its budgets
+#[poise::command(prefix_command, slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+pub async fn usage(
+    ctx: Context<'_>,
+    #[description = "\"today\" or \"month\" (default month)"] period: Option<String>,
+) -> Result<(), Error> {
+    let today = match period.as_deref().map(str::to_ascii_lowercase).as_deref() {
+        None | Some("month") => false,
+        Some("today") | Some("day") => true,
+        Some(other) => {
+            ctx.say(format!("Unknown period `{}`; use `today` or `month`.", other)).await?;
+            return Ok(());
+        }
+    };
+    usage_inner(ctx, today).await
+}
+
+async fn usage_inner(ctx: Context<'_>, today: bool) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
+    let db = &ctx.data().db;
+    let (day_start, month_start) = period_starts(Utc::now().date_naive());
+    let since = if today { day_start } else { month_start };
+    let budget = Budget::load(db, guild_id).await?;
+    let features = db.llm_usage_by_feature(guild_id, since).await?;
+    let users = db.llm_usage_by_user(guild_id, since).await?;
+
+    let mut output = format!(
+        "**Language model usage {}** (since {} UTC)\nToday: {}\nThis month: {}\n",
+        if today { "today" } else { "this month" },
+        DateTime::from_timestamp(since, 0).unwrap_or_default().format("%Y-%m-%d"),
+        budget_line(budget.used_today, budget.daily),
+        budget_line(budget.used_this_month, budget.monthly),
+    );
+    if features.is_empty() {
+        output.push_str("\nNo calls yet.");
+    } else {
+        output.push_str("\n**By feature**\n");
+        for total in &features {
+            output.push_str(&format!("`{}` — {}\n", total.key, totals(total)));
+        }
+        if !users.is_empty() {
+            output.push_str("\n**By user**\n");
+            for total in users.iter().take(TOP_USERS) {
+                output.push_str(&format!("<@{}> — {}\n", total.key, totals(total)));
+            }
+            if users.len() > TOP_USERS {
+                output.push_str(&format!("…and {} more\n", users.len() - TOP_USERS));
+            }
+        }
+    }
+    ctx.send(
+        poise::CreateReply::default()
+            .content(output)
+            .allowed_mentions(serenity::CreateAllowedMentions::new()),
+    )
+    .await?;
+    Ok(())
+}
+
+fn budget_line(used: u64, budget: Option<u64>) -> String {
+    match budget {
+        Some(budget) => format!("{} of {} tokens", used, budget),
+        None => format!("{} tokens (no budget)", used),
+    }
+}
+
+fn totals(total: &UsageTotal) -> String {
+    let LlmUsage { input_tokens, output_tokens } = total.usage;
+    format!(
+        "{} tokens in {} call(s) ({} in, {} out)",
+        total.usage.total(),
+        total.calls,
+        input_tokens,
+        output_tokens
+    )
+}
@@ -5,3 +5,9 @@ pub mod quad;
 pub mod smz3;
 pub mod config;
 pub mod speedrun;
+pub mod tasks;
+pub mod db;
+pub mod permissions;
+pub mod brain;
+pub mod ask;
+pub mod llm;
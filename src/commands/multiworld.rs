@@ -5,6 +5,7 @@ use serenity::{builder::{CreateEmbed}, framework::standard::{CommandResult, macr
 use std::{collections::{HashMap}, error::Error};
 use maplit::hashmap;
 use tracing::{error, info};
+use crate::api::SendMetered;
 use crate::util::slugid;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -195,7 +196,7 @@ async fn mw_reaction_update(added: bool, ctx: &Context, reaction: &Reaction) ->
             let client = reqwest::Client::new();
             let res = client.post(url)
                 .json(&options)
-                .send()
+                .send_metered()
                 .await;
 
             if let Ok(response) = res {
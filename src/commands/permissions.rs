@@ -0,0 +1,120 @@
+use poise::serenity_prelude as serenity;
+
+use crate::db::PermissionTarget;
+use crate::permissions::{find_group, GROUPS};
+use crate::{Context, Error};
+
+/// Lists the command groups granted in this server; subcommands grant or revoke them
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    required_permissions = "ADMINISTRATOR",
+    subcommands("grant", "revoke")
+)]
+pub async fn permissions(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
+    let grants = ctx.data().db.list_permissions(guild_id).await?;
+    let mut output = String::from("**Permission groups** (administrators can use all of them)\n");
+    for group in GROUPS {
+        let holders: Vec<String> = grants
+            .iter()
+            .filter(|(permission, _)| permission == group.name)
+            .map(|(_, target)| format_target(*target))
+            .collect();
+        output.push_str(&format!(
+            "`{}` — {}\n   Granted to: {}\n",
+            group.name,
+            group.description,
+            if holders.is_empty() { "nobody".to_string() } else { holders.join(", ") }
+        ));
+    }
+    output.push_str("\nSubcommands: `grant <group> <role or user>`, `revoke <group> <role or user>`");
+    ctx.send(
+        poise::CreateReply::default()
+            .content(output)
+            .allowed_mentions(serenity::CreateAllowedMentions::new()),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Grants a command group to a role or a user in this server
+#[poise::command(prefix_command, slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+pub async fn grant(
+    ctx: Context<'_>,
+    #[description = "Permission group (see the permissions command)"] group: String,
+    #[description = "Role to grant it to"] role: Option<serenity::Role>,
+    #[description = "User to grant it to"] user: Option<serenity::User>,
+) -> Result<(), Error> {
+    let Some(target) = resolve(ctx, &group, role, user).await? else {
+        return Ok(());
+    };
+    let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
+    let text = if ctx.data().db.grant_permission(guild_id, &group, target).await? {
+        format!("Granted `{}` to {}.", group, format_target(target))
+    } else {
+        format!("{} already has `{}`.", format_target(target), group)
+    };
+    say_quietly(ctx, text).await
+}
+
+/// Revokes a command group from a role or a user in this server
+#[poise::command(prefix_command, slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+pub async fn revoke(
+    ctx: Context<'_>,
+    #[description = "Permission group (see the permissions command)"] group: String,
+    #[description = "Role to revoke it from"] role: Option<serenity::Role>,
+    #[description = "User to revoke it from"] user: Option<serenity::User>,
+) -> Result<(), Error> {
+    let Some(target) = resolve(ctx, &group, role, user).await? else {
+        return Ok(());
+    };
+    let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
+    let text = if ctx.data().db.revoke_permission(guild_id, &group, target).await? {
+        format!("Revoked `{}` from {}.", group, format_target(target))
+    } else {
+        format!("{} doesn't have `{}`.", format_target(target), group)
+    };
+    say_quietly(ctx, text).await
+}
+
+/// Checks the group exists and exactly one of role/user was given.
+async fn resolve(
+    ctx: Context<'_>,
+    group: &str,
+    role: Option<serenity::Role>,
+    user: Option<serenity::User>,
+) -> Result<Option<PermissionTarget>, Error> {
+    if find_group(group).is_none() {
+        let names: Vec<String> = GROUPS.iter().map(|g| format!("`{}`", g.name)).collect();
+        ctx.say(format!("Unknown permission group `{}`. Groups: {}", group, names.join(", "))).await?;
+        return Ok(None);
+    }
+    match (role, user) {
+        (Some(role), None) => Ok(Some(PermissionTarget::Role(role.id.get()))),
+        (None, Some(user)) => Ok(Some(PermissionTarget::User(user.id.get()))),
+        _ => {
+            ctx.say("Give either a role or a user.").await?;
+            Ok(None)
+        }
+    }
+}
+
+fn format_target(target: PermissionTarget) -> String {
+    match target {
+        PermissionTarget::Role(id) => format!("<@&{}>", id),
+        PermissionTarget::User(id) => format!("<@{}>", id),
+    }
+}
+
+/// Replies naming roles and users without pinging them.
+async fn say_quietly(ctx: Context<'_>, text: String) -> Result<(), Error> {
+    ctx.send(
+        poise::CreateReply::default()
+            .content(text)
+            .allowed_mentions(serenity::CreateAllowedMentions::new()),
+    )
+    .await?;
+    Ok(())
+}
@@ -5,6 +5,7 @@ use serde_json::{json, Map, Value};
 
 const SCOPE: &str = "quad";
 const SITES_KEY: &str = "sites";
+const DEFAULT_SITE_KEY: &str = "default_site";
 const LIVE_SITE: &str = "live";
 const QUAD_COLOR_PENDING: u32 = 0x4F86C6;
 const QUAD_COLOR_SUCCESS: u32 = 0x48A868;
@@ -34,6 +35,7 @@ struct QuadCommandOptions<'a> {
 }
 
 /// Generates a Quad randomizer seed
+#[allow(clippy::too_many_arguments)]
 #[poise::command(prefix_command, slash_command, aliases("csrando", "combo"))]
 pub async fn quad(
     ctx: Context<'_>,
@@ -287,8 +289,21 @@ fn looks_like_option_pair(token: &str) -> bool {
         .is_some_and(|(key, _)| !key.is_empty())
 }
 
+/// The named site, or the channel's `quad.default_site` (falling back to the
+/// server's, then the global one, then live) when none is given.
 async fn resolve_site(ctx: Context<'_>, site: Option<&str>) -> Result<QuadSite, String> {
-    let requested = site.unwrap_or(LIVE_SITE).trim();
+    let default = match site {
+        Some(_) => None,
+        None => {
+            let guild_id = ctx.guild_id().map(|g| g.get()).unwrap_or_default();
+            ctx.data()
+                .db
+                .resolve_setting(guild_id, ctx.channel_id().get(), SCOPE, DEFAULT_SITE_KEY)
+                .await
+                .map_err(|error| format!("Couldn't read the default Quad site: {}", error))?
+        }
+    };
+    let requested = site.or(default.as_deref()).unwrap_or(LIVE_SITE).trim();
     if requested.is_empty() || requested.eq_ignore_ascii_case(LIVE_SITE) {
         return Ok(live_site());
     }
@@ -1020,7 +1035,7 @@ fn metadata_default_value(setting: &Value) -> Option<Value> {
                 .unwrap_or_else(|| json!([])),
         ),
         "Slider" => Some(setting.get("default").cloned().unwrap_or_else(|| json!(0))),
-        "Toggle" => Some(setting.get("default").cloned().unwrap_or_else(|| json!(false))),
+        "Toggle" => Some(setting.get("default").cloned().unwrap_or(json!(false))),
         "Input" => Some(setting.get("default").cloned().unwrap_or_else(|| json!(""))),
         "Generic" => Some(setting.get("default").cloned().unwrap_or(Value::Null)),
         _ => None,
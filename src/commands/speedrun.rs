@@ -2,14 +2,14 @@ use crate::tasks::speedrun::{
     enter_review, DemoResult, Mode, ReviewResult, SpeedrunDebugReport, SpeedrunDebugResult,
     SpeedrunMonitor,
 };
-use crate::{Context, Error};
+use crate::{permissions, Context, Error};
 
 /// Speedrun.com moderation tools
 #[poise::command(
     prefix_command,
     slash_command,
     guild_only,
-    required_permissions = "ADMINISTRATOR",
+    check = "permissions::speedrun_any",
     subcommands("debug", "demo", "showcase", "review")
 )]
 pub async fn speedrun(ctx: Context<'_>) -> Result<(), Error> {
@@ -18,7 +18,7 @@ pub async fn speedrun(ctx: Context<'_>) -> Result<(), Error> {
 }
 
 /// Open a discussion thread on a tracked run and set it pending review
-#[poise::command(prefix_command, slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+#[poise::command(prefix_command, slash_command, guild_only, check = "permissions::speedrun_review")]
 pub async fn review(
     ctx: Context<'_>,
     #[description = "speedrun.com run id or run URL"] run: String,
@@ -79,7 +79,7 @@ fn parse_run_id(input: &str) -> Option<String> {
 }
 
 /// Post fake scenario submissions showing flags, scores and announcements
-#[poise::command(prefix_command, slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+#[poise::command(prefix_command, slash_command, guild_only, check = "permissions::speedrun_demo")]
 pub async fn showcase(ctx: Context<'_>) -> Result<(), Error> {
     ctx.defer().await?;
 
@@ -106,7 +106,7 @@ pub async fn showcase(ctx: Context<'_>) -> Result<(), Error> {
 }
 
 /// Post recent submissions to the mod log as a demo; nothing is sent to speedrun.com
-#[poise::command(prefix_command, slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+#[poise::command(prefix_command, slash_command, guild_only, check = "permissions::speedrun_demo")]
 pub async fn demo(
     ctx: Context<'_>,
     #[description = "speedrun.com game abbreviation"] game: String,
@@ -141,7 +141,7 @@ pub async fn demo(
 }
 
 /// Dry-run recent submissions through the moderation pipeline
-#[poise::command(prefix_command, slash_command, guild_only, required_permissions = "ADMINISTRATOR")]
+#[poise::command(prefix_command, slash_command, guild_only, check = "permissions::speedrun_debug")]
 pub async fn debug(
     ctx: Context<'_>,
     #[description = "speedrun.com game abbreviation"] game: String,
@@ -0,0 +1,141 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::tasks::{Schedule, TaskInfo};
+use crate::{Context, Error};
+
+/// Shows background task status; subcommands trigger, pause or resume a task
+#[poise::command(
+    prefix_command,
+    slash_command,
+    check = "crate::permissions::owner_or_admin",
+    subcommands("run", "pause", "resume")
+)]
+pub async fn tasks(ctx: Context<'_>) -> Result<(), Error> {
+    let infos = ctx.data().tasks.list().await?;
+    let mut output = String::from("**Background tasks**\n");
+    for info in &infos {
+        output.push_str(&format_task(info));
+    }
+    output.push_str("\nSubcommands: `run <name>`, `pause <name>`, `resume <name>`");
+    ctx.say(output).await?;
+    Ok(())
+}
+
+/// Runs a background task immediately, even if it is paused (bot owners only)
+#[poise::command(prefix_command, slash_command, owners_only)]
+pub async fn run(
+    ctx: Context<'_>,
+    #[description = "Task name (see the tasks command)"] name: String,
+) -> Result<(), Error> {
+    if ctx.data().tasks.trigger(&name) {
+        ctx.say(format!("Triggered `{}`; check `tasks` for the result.", name)).await?;
+    } else {
+        ctx.say(unknown_task_text(&name)).await?;
+    }
+    Ok(())
+}
+
+/// Pauses the scheduled runs of a background task, across restarts (bot owners only)
+#[poise::command(prefix_command, slash_command, owners_only)]
+pub async fn pause(
+    ctx: Context<'_>,
+    #[description = "Task name (see the tasks command)"] name: String,
+) -> Result<(), Error> {
+    if ctx.data().tasks.set_paused(&name, true).await? {
+        ctx.say(format!("Paused `{}`. `tasks run {}` still runs it on demand.", name, name)).await?;
+    } else {
+        ctx.say(unknown_task_text(&name)).await?;
+    }
+    Ok(())
+}
+
+/// Resumes the scheduled runs of a paused background task (bot owners only)
+#[poise::command(prefix_command, slash_command, owners_only)]
+pub async fn resume(
+    ctx: Context<'_>,
+    #[description = "Task name (see the tasks command)"] name: String,
+) -> Result<(), Error> {
+    if ctx.data().tasks.set_paused(&name, false).await? {
+        ctx.say(format!("Resumed `{}`.", name)).await?;
+    } else {
+        ctx.say(unknown_task_text(&name)).await?;
+    }
+    Ok(())
+}
+
+fn unknown_task_text(name: &str) -> String {
+    format!("Unknown task `{}`. Use `tasks` to list the registered tasks.", name)
+}
+
+fn format_task(info: &TaskInfo) -> String {
+    let status = &info.status;
+    let state = if status.running {
+        "🔄 running"
+    } else if info.paused {
+        "⏸️ paused"
+    } else if status.last_error.is_some() {
+        "⚠️ failing"
+    } else {
+        "✅ scheduled"
+    };
+    let mut output = format!(
+        "\n`{}` — {} | {} | runs: {} | failures: {}\n",
+        info.name,
+        state,
+        format_schedule(&info.schedule),
+        status.runs,
+        status.failures
+    );
+    output.push_str(&format!(
+        "Last start: {} | last finish: {} | took: {}\n",
+        format_time(status.last_started),
+        format_time(status.last_finished),
+        status.last_duration.map_or_else(|| "n/a".to_string(), format_duration)
+    ));
+    if let Some(error) = &status.last_error {
+        let error: String = error.chars().take(300).collect();
+        output.push_str(&format!("Last error: `{}`\n", error));
+    }
+    output
+}
+
+fn format_schedule(schedule: &Schedule) -> String {
+    match schedule {
+        Schedule::Every(period) => format!("every {}", format_duration(*period)),
+        Schedule::Cron(cron) => format!("cron `{}`", cron.source()),
+        Schedule::PerGuild { scope, key } => format!("per server (`{}.{}`)", scope, key),
+    }
+}
+
+fn format_time(time: Option<DateTime<Utc>>) -> String {
+    match time {
+        Some(time) => format!("<t:{}:R>", time.timestamp()),
+        None => "never".to_string(),
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h{:02}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{:.1}s", duration.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_duration;
+    use std::time::Duration;
+
+    #[test]
+    fn formats_durations_by_magnitude() {
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1.5s");
+        assert_eq!(format_duration(Duration::from_secs(120)), "2m00s");
+        assert_eq!(format_duration(Duration::from_secs(3 * 3600 + 5 * 60)), "3h05m");
+    }
+}
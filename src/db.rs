@@ -1,21 +1,48 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
 use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
 use sqlx::{Row, SqlitePool};
 
 use crate::Error;
 
+pub mod backup;
+pub mod brain;
+pub mod llm_usage;
+pub mod wiki;
+mod migrations;
+
 /// Sentinel guild id under which global (bot-wide) settings are stored.
 const GLOBAL_GUILD: i64 = 0;
 
-/// Simple persistent key-value store backed by SQLite.
+/// Sentinel channel id of server-wide and global settings.
+const NO_CHANNEL: i64 = 0;
+
+/// Simple persistent key-value store backed by SQLite. The schema is
+/// versioned and upgraded at startup by the migrations in [`migrations`].
 ///
-/// Two tables are provided:
+/// The tables:
 /// - `settings`: user-facing configuration, scoped by feature and either
-///   per-guild or global (managed via the `config` command)
+///   per-channel, per-guild or global (managed via the `config` command),
+///   resolved channel → guild → global; every change is
+///   recorded in `settings_history` with who made it
+/// - `permissions`: command groups granted to roles and users per guild (see
+///   [`crate::permissions`])
 /// - `task_state`: internal persistence for background tasks (seen items,
-///   cached lookups, etc.)
+///   cached lookups, etc.), optionally expiring
+/// - `brain_*`: the chatter brains' graphs, who taught them what, and who
+///   opted out (see [`brain`])
+/// - `wiki_pages`, `wiki_chunks`: the wiki search index (see [`wiki`])
+/// - `llm_usage`: tokens used by every language model call (see [`llm_usage`])
 #[derive(Clone)]
 pub struct Db {
     pool: SqlitePool,
+    /// Connections for chatter brain replies (see [`Db::brain`]).
+    brain_pool: SqlitePool,
+    /// The decoded wiki chunks of the last embedding model searched (see
+    /// [`Db::wiki_chunks`]).
+    wiki_cache: Arc<tokio::sync::Mutex<Option<wiki::ChunkCache>>>,
 }
 
 impl Db {
@@ -27,65 +54,250 @@ impl Db {
 
         let pool = SqlitePoolOptions::new()
             .max_connections(4)
-            .connect_with(options)
+            .connect_with(options.clone())
             .await?;
 
-        migrate_settings(&pool).await?;
+        migrations::run(&pool).await?;
 
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS task_state (
-                task TEXT NOT NULL,
-                key TEXT NOT NULL,
-                value TEXT NOT NULL,
-                PRIMARY KEY (task, key)
-            )",
-        )
-        .execute(&pool)
-        .await?;
+        let brain_pool = SqlitePoolOptions::new()
+            .max_connections(2)
+            .connect_with(options.read_only(true))
+            .await?;
 
-        Ok(Db { pool })
+        Ok(Db { pool, brain_pool, wiki_cache: Default::default() })
+    }
+
+    /// Closes the connection pool, waiting for queries in progress. Used on
+    /// shutdown so the WAL is checkpointed cleanly.
+    pub async fn close(&self) {
+        self.brain_pool.close().await;
+        self.pool.close().await;
+    }
+
+    /// Runs a trivial query, for health checks.
+    pub async fn ping(&self) -> Result<(), Error> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
     }
 
     pub async fn get_global_setting(&self, scope: &str, key: &str) -> Result<Option<String>, Error> {
-        self.get(GLOBAL_GUILD, scope, key).await
+        self.get(GLOBAL_GUILD, NO_CHANNEL, scope, key).await
     }
 
-    pub async fn set_global_setting(&self, scope: &str, key: &str, value: &str) -> Result<(), Error> {
-        self.set(GLOBAL_GUILD, scope, key, value).await
+    /// Sets a global setting on behalf of user `changed_by`.
+    pub async fn set_global_setting(&self, scope: &str, key: &str, value: &str, changed_by: u64) -> Result<(), Error> {
+        self.change(GLOBAL_GUILD, NO_CHANNEL, scope, key, Some(value), changed_by).await?;
+        Ok(())
     }
 
-    pub async fn delete_global_setting(&self, scope: &str, key: &str) -> Result<bool, Error> {
-        self.delete(GLOBAL_GUILD, scope, key).await
+    /// Removes a global setting on behalf of user `changed_by`; `false` if
+    /// it wasn't set.
+    pub async fn delete_global_setting(&self, scope: &str, key: &str, changed_by: u64) -> Result<bool, Error> {
+        Ok(self.change(GLOBAL_GUILD, NO_CHANNEL, scope, key, None, changed_by).await?.is_some())
     }
 
     pub async fn list_global_settings(&self, scope: &str) -> Result<Vec<(String, String)>, Error> {
-        self.list(GLOBAL_GUILD, scope).await
+        self.list(GLOBAL_GUILD, NO_CHANNEL, scope).await
     }
 
     pub async fn get_guild_setting(&self, guild_id: u64, scope: &str, key: &str) -> Result<Option<String>, Error> {
-        self.get(guild_id as i64, scope, key).await
+        self.get(guild_id as i64, NO_CHANNEL, scope, key).await
     }
 
-    pub async fn set_guild_setting(&self, guild_id: u64, scope: &str, key: &str, value: &str) -> Result<(), Error> {
-        self.set(guild_id as i64, scope, key, value).await
+    /// Sets a per-guild setting on behalf of user `changed_by`.
+    pub async fn set_guild_setting(
+        &self,
+        guild_id: u64,
+        scope: &str,
+        key: &str,
+        value: &str,
+        changed_by: u64,
+    ) -> Result<(), Error> {
+        self.change(guild_id as i64, NO_CHANNEL, scope, key, Some(value), changed_by).await?;
+        Ok(())
     }
 
-    pub async fn delete_guild_setting(&self, guild_id: u64, scope: &str, key: &str) -> Result<bool, Error> {
-        self.delete(guild_id as i64, scope, key).await
+    /// Removes a per-guild setting on behalf of user `changed_by`; `false`
+    /// if it wasn't set.
+    pub async fn delete_guild_setting(&self, guild_id: u64, scope: &str, key: &str, changed_by: u64) -> Result<bool, Error> {
+        Ok(self.change(guild_id as i64, NO_CHANNEL, scope, key, None, changed_by).await?.is_some())
     }
 
     pub async fn list_guild_settings(&self, guild_id: u64, scope: &str) -> Result<Vec<(String, String)>, Error> {
-        self.list(guild_id as i64, scope).await
+        self.list(guild_id as i64, NO_CHANNEL, scope).await
+    }
+
+    pub async fn get_channel_setting(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        scope: &str,
+        key: &str,
+    ) -> Result<Option<String>, Error> {
+        self.get(guild_id as i64, channel_id as i64, scope, key).await
+    }
+
+    /// Sets a per-channel override on behalf of user `changed_by`.
+    pub async fn set_channel_setting(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        scope: &str,
+        key: &str,
+        value: &str,
+        changed_by: u64,
+    ) -> Result<(), Error> {
+        self.change(guild_id as i64, channel_id as i64, scope, key, Some(value), changed_by).await?;
+        Ok(())
+    }
+
+    /// Removes a per-channel override on behalf of user `changed_by`;
+    /// `false` if there was none.
+    pub async fn delete_channel_setting(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        scope: &str,
+        key: &str,
+        changed_by: u64,
+    ) -> Result<bool, Error> {
+        Ok(self.change(guild_id as i64, channel_id as i64, scope, key, None, changed_by).await?.is_some())
+    }
+
+    pub async fn list_channel_settings(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        scope: &str,
+    ) -> Result<Vec<(String, String)>, Error> {
+        self.list(guild_id as i64, channel_id as i64, scope).await
+    }
+
+    /// Every channel override in a guild as `(channel_id, scope, key, value)`.
+    pub async fn list_channel_overrides(&self, guild_id: u64) -> Result<Vec<(u64, String, String, String)>, Error> {
+        let rows = sqlx::query(
+            "SELECT channel_id, scope, key, value FROM settings
+             WHERE guild_id = ? AND channel_id != ? ORDER BY scope, key, channel_id",
+        )
+        .bind(guild_id as i64)
+        .bind(NO_CHANNEL)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| (r.get::<i64, _>("channel_id") as u64, r.get("scope"), r.get("key"), r.get("value")))
+            .collect())
+    }
+
+    /// The value that applies in a channel: its own override, else the
+    /// server's value, else the global one.
+    pub async fn resolve_setting(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        scope: &str,
+        key: &str,
+    ) -> Result<Option<String>, Error> {
+        let row = sqlx::query(
+            "SELECT value FROM settings
+             WHERE scope = ? AND key = ?
+               AND ((guild_id = ? AND channel_id IN (?, ?)) OR (guild_id = ? AND channel_id = ?))
+             ORDER BY channel_id = ? DESC, guild_id = ? DESC
+             LIMIT 1",
+        )
+        .bind(scope)
+        .bind(key)
+        .bind(guild_id as i64)
+        .bind(channel_id as i64)
+        .bind(NO_CHANNEL)
+        .bind(GLOBAL_GUILD)
+        .bind(NO_CHANNEL)
+        .bind(channel_id as i64)
+        .bind(guild_id as i64)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|r| r.get("value")))
+    }
+
+    /// Every value of a scope that can apply in a channel, read with one
+    /// query, for looking up many of its settings at once.
+    pub async fn scope_values(&self, guild_id: u64, channel_id: u64, scope: &str) -> Result<ScopeValues, Error> {
+        let rows = sqlx::query(
+            "SELECT guild_id, channel_id, key, value FROM settings
+             WHERE scope = ? AND ((guild_id = ? AND channel_id IN (?, ?)) OR (guild_id = ? AND channel_id = ?))",
+        )
+        .bind(scope)
+        .bind(guild_id as i64)
+        .bind(channel_id as i64)
+        .bind(NO_CHANNEL)
+        .bind(GLOBAL_GUILD)
+        .bind(NO_CHANNEL)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut values = ScopeValues::default();
+        for row in rows {
+            let (guild, channel): (i64, i64) = (row.get("guild_id"), row.get("channel_id"));
+            let (key, value): (String, String) = (row.get("key"), row.get("value"));
+            if channel != NO_CHANNEL {
+                values.channel.insert(key, value);
+                continue;
+            }
+            if guild == guild_id as i64 {
+                values.server.insert(key.clone(), value.clone());
+            }
+            if guild == GLOBAL_GUILD {
+                values.global.insert(key, value);
+            }
+        }
+        Ok(values)
+    }
+
+    /// Recent changes visible from a guild — its own (server and channel)
+    /// settings and the global ones — newest first, optionally narrowed to a
+    /// scope and key.
+    pub async fn setting_history(
+        &self,
+        guild_id: u64,
+        scope: Option<&str>,
+        key: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<SettingChange>, Error> {
+        let rows = sqlx::query(
+            "SELECT * FROM settings_history
+             WHERE guild_id IN (?, ?) AND (? IS NULL OR scope = ?) AND (? IS NULL OR key = ?)
+             ORDER BY id DESC LIMIT ?",
+        )
+        .bind(guild_id as i64)
+        .bind(GLOBAL_GUILD)
+        .bind(scope)
+        .bind(scope)
+        .bind(key)
+        .bind(key)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.iter().map(SettingChange::from_row).collect())
+    }
+
+    pub async fn setting_change(&self, id: i64) -> Result<Option<SettingChange>, Error> {
+        let row = sqlx::query("SELECT * FROM settings_history WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.as_ref().map(SettingChange::from_row))
     }
 
-    /// Returns `(guild_id, value)` for every guild that has the setting.
+    /// Returns `(guild_id, value)` for every guild that has the setting
+    /// server-wide (channel overrides aren't included).
     pub async fn guild_setting_values(&self, scope: &str, key: &str) -> Result<Vec<(u64, String)>, Error> {
         let rows = sqlx::query(
-            "SELECT guild_id, value FROM settings WHERE scope = ? AND key = ? AND guild_id != ?",
+            "SELECT guild_id, value FROM settings WHERE scope = ? AND key = ? AND guild_id != ? AND channel_id = ?
+             ORDER BY guild_id",
         )
         .bind(scope)
         .bind(key)
         .bind(GLOBAL_GUILD)
+        .bind(NO_CHANNEL)
         .fetch_all(&self.pool)
         .await?;
         Ok(rows
@@ -94,65 +306,216 @@ impl Db {
             .collect())
     }
 
-    async fn get(&self, guild_id: i64, scope: &str, key: &str) -> Result<Option<String>, Error> {
-        let row = sqlx::query("SELECT value FROM settings WHERE guild_id = ? AND scope = ? AND key = ?")
-            .bind(guild_id)
-            .bind(scope)
-            .bind(key)
-            .fetch_optional(&self.pool)
-            .await?;
+    async fn get(&self, guild_id: i64, channel_id: i64, scope: &str, key: &str) -> Result<Option<String>, Error> {
+        let row = sqlx::query(
+            "SELECT value FROM settings WHERE guild_id = ? AND channel_id = ? AND scope = ? AND key = ?",
+        )
+        .bind(guild_id)
+        .bind(channel_id)
+        .bind(scope)
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await?;
         Ok(row.map(|r| r.get("value")))
     }
 
-    async fn set(&self, guild_id: i64, scope: &str, key: &str, value: &str) -> Result<(), Error> {
+    /// Sets (`Some`) or removes (`None`) a setting and records the change in
+    /// `settings_history`, atomically. Returns the previous value. A change
+    /// that leaves the value as it was isn't recorded.
+    async fn change(
+        &self,
+        guild_id: i64,
+        channel_id: i64,
+        scope: &str,
+        key: &str,
+        value: Option<&str>,
+        changed_by: u64,
+    ) -> Result<Option<String>, Error> {
+        let mut tx = self.pool.begin().await?;
+        let old: Option<String> = sqlx::query(
+            "SELECT value FROM settings WHERE guild_id = ? AND channel_id = ? AND scope = ? AND key = ?",
+        )
+        .bind(guild_id)
+        .bind(channel_id)
+        .bind(scope)
+        .bind(key)
+        .fetch_optional(&mut *tx)
+        .await?
+        .map(|r| r.get("value"));
+        if old.as_deref() == value {
+            return Ok(old);
+        }
+
+        match value {
+            Some(value) => {
+                sqlx::query(
+                    "INSERT INTO settings (guild_id, channel_id, scope, key, value) VALUES (?, ?, ?, ?, ?)
+                     ON CONFLICT (guild_id, channel_id, scope, key) DO UPDATE SET value = excluded.value",
+                )
+                .bind(guild_id)
+                .bind(channel_id)
+                .bind(scope)
+                .bind(key)
+                .bind(value)
+                .execute(&mut *tx)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM settings WHERE guild_id = ? AND channel_id = ? AND scope = ? AND key = ?")
+                    .bind(guild_id)
+                    .bind(channel_id)
+                    .bind(scope)
+                    .bind(key)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
         sqlx::query(
-            "INSERT INTO settings (guild_id, scope, key, value) VALUES (?, ?, ?, ?)
-             ON CONFLICT (guild_id, scope, key) DO UPDATE SET value = excluded.value",
+            "INSERT INTO settings_history
+                (guild_id, channel_id, scope, key, old_value, new_value, changed_by, changed_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
         )
         .bind(guild_id)
+        .bind(channel_id)
         .bind(scope)
         .bind(key)
+        .bind(&old)
         .bind(value)
+        .bind(changed_by as i64)
+        .bind(now())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(old)
+    }
+
+    async fn list(&self, guild_id: i64, channel_id: i64, scope: &str) -> Result<Vec<(String, String)>, Error> {
+        let rows = sqlx::query(
+            "SELECT key, value FROM settings WHERE guild_id = ? AND channel_id = ? AND scope = ? ORDER BY key",
+        )
+        .bind(guild_id)
+        .bind(channel_id)
+        .bind(scope)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(|r| (r.get("key"), r.get("value"))).collect())
+    }
+
+    /// Grants `permission` in a guild; `false` if it was already granted.
+    pub async fn grant_permission(
+        &self,
+        guild_id: u64,
+        permission: &str,
+        target: PermissionTarget,
+    ) -> Result<bool, Error> {
+        let (kind, id) = target.parts();
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO permissions (guild_id, permission, target_kind, target_id) VALUES (?, ?, ?, ?)",
+        )
+        .bind(guild_id as i64)
+        .bind(permission)
+        .bind(kind)
+        .bind(id as i64)
         .execute(&self.pool)
         .await?;
-        Ok(())
+        Ok(result.rows_affected() > 0)
     }
 
-    async fn delete(&self, guild_id: i64, scope: &str, key: &str) -> Result<bool, Error> {
-        let result = sqlx::query("DELETE FROM settings WHERE guild_id = ? AND scope = ? AND key = ?")
-            .bind(guild_id)
-            .bind(scope)
-            .bind(key)
-            .execute(&self.pool)
-            .await?;
+    /// Revokes a grant; `false` if there was none.
+    pub async fn revoke_permission(
+        &self,
+        guild_id: u64,
+        permission: &str,
+        target: PermissionTarget,
+    ) -> Result<bool, Error> {
+        let (kind, id) = target.parts();
+        let result = sqlx::query(
+            "DELETE FROM permissions WHERE guild_id = ? AND permission = ? AND target_kind = ? AND target_id = ?",
+        )
+        .bind(guild_id as i64)
+        .bind(permission)
+        .bind(kind)
+        .bind(id as i64)
+        .execute(&self.pool)
+        .await?;
         Ok(result.rows_affected() > 0)
     }
 
-    async fn list(&self, guild_id: i64, scope: &str) -> Result<Vec<(String, String)>, Error> {
-        let rows = sqlx::query("SELECT key, value FROM settings WHERE guild_id = ? AND scope = ? ORDER BY key")
-            .bind(guild_id)
-            .bind(scope)
-            .fetch_all(&self.pool)
-            .await?;
-        Ok(rows.into_iter().map(|r| (r.get("key"), r.get("value"))).collect())
+    /// Every grant in a guild as `(permission, target)`, sorted by permission.
+    pub async fn list_permissions(&self, guild_id: u64) -> Result<Vec<(String, PermissionTarget)>, Error> {
+        let rows = sqlx::query(
+            "SELECT permission, target_kind, target_id FROM permissions WHERE guild_id = ?
+             ORDER BY permission, target_kind, target_id",
+        )
+        .bind(guild_id as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|r| {
+                let id = r.get::<i64, _>("target_id") as u64;
+                let target = match r.get::<String, _>("target_kind").as_str() {
+                    "role" => PermissionTarget::Role(id),
+                    "user" => PermissionTarget::User(id),
+                    _ => return None,
+                };
+                Some((r.get("permission"), target))
+            })
+            .collect())
+    }
+
+    /// Every permission granted in a guild to the user or any of their
+    /// roles.
+    pub async fn granted_permissions(
+        &self,
+        guild_id: u64,
+        user_id: u64,
+        role_ids: &[u64],
+    ) -> Result<HashSet<String>, Error> {
+        let grants = self.list_permissions(guild_id).await?;
+        Ok(grants
+            .into_iter()
+            .filter(|(_, target)| match target {
+                PermissionTarget::User(id) => *id == user_id,
+                PermissionTarget::Role(id) => role_ids.contains(id),
+            })
+            .map(|(permission, _)| permission)
+            .collect())
+    }
+
+    /// Whether `permission` is granted in a guild to the user or any of
+    /// their roles.
+    pub async fn has_permission(
+        &self,
+        guild_id: u64,
+        permission: &str,
+        user_id: u64,
+        role_ids: &[u64],
+    ) -> Result<bool, Error> {
+        Ok(self.granted_permissions(guild_id, user_id, role_ids).await?.contains(permission))
     }
 
     pub async fn get_task_state(&self, task: &str, key: &str) -> Result<Option<String>, Error> {
-        let row = sqlx::query("SELECT value FROM task_state WHERE task = ? AND key = ?")
-            .bind(task)
-            .bind(key)
-            .fetch_optional(&self.pool)
-            .await?;
+        let row = sqlx::query(
+            "SELECT value FROM task_state WHERE task = ? AND key = ? AND (expires_at IS NULL OR expires_at > ?)",
+        )
+        .bind(task)
+        .bind(key)
+        .bind(now())
+        .fetch_optional(&self.pool)
+        .await?;
         Ok(row.map(|r| r.get("value")))
     }
 
     /// Returns all `(key, value)` task state entries whose key starts with `prefix`.
     pub async fn list_task_state(&self, task: &str, prefix: &str) -> Result<Vec<(String, String)>, Error> {
         let rows = sqlx::query(
-            "SELECT key, value FROM task_state WHERE task = ? AND key LIKE ? || '%' ORDER BY key",
+            "SELECT key, value FROM task_state WHERE task = ? AND key LIKE ? || '%'
+             AND (expires_at IS NULL OR expires_at > ?) ORDER BY key",
         )
         .bind(task)
         .bind(prefix)
+        .bind(now())
         .fetch_all(&self.pool)
         .await?;
         Ok(rows.into_iter().map(|r| (r.get("key"), r.get("value"))).collect())
@@ -161,12 +524,16 @@ impl Db {
     /// Atomically removes and returns a task state entry. `None` means it
     /// didn't exist — e.g. a concurrent resolver claimed it first.
     pub async fn claim_task_state(&self, task: &str, key: &str) -> Result<Option<String>, Error> {
-        let row = sqlx::query("DELETE FROM task_state WHERE task = ? AND key = ? RETURNING value")
-            .bind(task)
-            .bind(key)
-            .fetch_optional(&self.pool)
-            .await?;
-        Ok(row.map(|r| r.get("value")))
+        let row = sqlx::query(
+            "DELETE FROM task_state WHERE task = ? AND key = ? RETURNING value, expires_at",
+        )
+        .bind(task)
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row
+            .filter(|r| r.get::<Option<i64>, _>("expires_at").is_none_or(|t| t > now()))
+            .map(|r| r.get("value")))
     }
 
     pub async fn delete_task_state(&self, task: &str, key: &str) -> Result<(), Error> {
@@ -178,18 +545,199 @@ impl Db {
         Ok(())
     }
 
-    pub async fn set_task_state(&self, task: &str, key: &str, value: &str) -> Result<(), Error> {
+    /// Stores a task state entry. With `expires`, the entry disappears that
+    /// long from now: reads stop seeing it at once, and the cleanup task
+    /// deletes it later. Overwriting an entry replaces its expiry too.
+    pub async fn set_task_state(
+        &self,
+        task: &str,
+        key: &str,
+        value: &str,
+        expires: Option<Duration>,
+    ) -> Result<(), Error> {
+        let expires_at = expires.map(|ttl| now().saturating_add(ttl.as_secs() as i64));
         sqlx::query(
-            "INSERT INTO task_state (task, key, value) VALUES (?, ?, ?)
-             ON CONFLICT (task, key) DO UPDATE SET value = excluded.value",
+            "INSERT INTO task_state (task, key, value, expires_at) VALUES (?, ?, ?, ?)
+             ON CONFLICT (task, key) DO UPDATE SET value = excluded.value, expires_at = excluded.expires_at",
         )
         .bind(task)
         .bind(key)
         .bind(value)
+        .bind(expires_at)
         .execute(&self.pool)
         .await?;
         Ok(())
     }
+
+    /// Deletes expired task state entries, returning how many were removed.
+    pub async fn purge_expired_task_state(&self) -> Result<u64, Error> {
+        let result = sqlx::query("DELETE FROM task_state WHERE expires_at <= ?")
+            .bind(now())
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Row counts of task state grouped by task and key prefix (the part of
+    /// the key before the first `:`), with how many of those rows expire.
+    pub async fn task_state_stats(&self) -> Result<Vec<TaskStateStats>, Error> {
+        let rows = sqlx::query(
+            "SELECT task,
+                    CASE WHEN instr(key, ':') > 0 THEN substr(key, 1, instr(key, ':') - 1) ELSE key END AS prefix,
+                    COUNT(*) AS rows,
+                    COUNT(expires_at) AS expiring
+             FROM task_state GROUP BY task, prefix ORDER BY task, prefix",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| TaskStateStats {
+                task: r.get("task"),
+                prefix: r.get("prefix"),
+                rows: r.get("rows"),
+                expiring: r.get("expiring"),
+            })
+            .collect())
+    }
+
+    /// Number of stored settings (per-server and global).
+    pub async fn settings_count(&self) -> Result<i64, Error> {
+        let row = sqlx::query("SELECT COUNT(*) AS count FROM settings").fetch_one(&self.pool).await?;
+        Ok(row.get("count"))
+    }
+
+    /// Size of the main database file in bytes (excluding the WAL), and how
+    /// much of it is free pages that a `VACUUM` would reclaim.
+    pub async fn file_size(&self) -> Result<(i64, i64), Error> {
+        let row = sqlx::query(
+            "SELECT p.page_count * s.page_size AS size, f.freelist_count * s.page_size AS free
+             FROM pragma_page_count() p, pragma_page_size() s, pragma_freelist_count() f",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok((row.get("size"), row.get("free")))
+    }
+}
+
+/// Who a permission is granted to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PermissionTarget {
+    Role(u64),
+    User(u64),
+}
+
+impl PermissionTarget {
+    fn parts(self) -> (&'static str, u64) {
+        match self {
+            PermissionTarget::Role(id) => ("role", id),
+            PermissionTarget::User(id) => ("user", id),
+        }
+    }
+}
+
+/// One recorded setting change; `None` values mean unset.
+pub struct SettingChange {
+    pub id: i64,
+    /// `None` for a global setting.
+    pub guild_id: Option<u64>,
+    /// `Some` for a per-channel override.
+    pub channel_id: Option<u64>,
+    pub scope: String,
+    pub key: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub changed_by: u64,
+    /// Unix time.
+    pub changed_at: i64,
+}
+
+impl SettingChange {
+    fn from_row(row: &sqlx::sqlite::SqliteRow) -> Self {
+        let guild_id: i64 = row.get("guild_id");
+        let channel_id: i64 = row.get("channel_id");
+        SettingChange {
+            id: row.get("id"),
+            guild_id: (guild_id != GLOBAL_GUILD).then_some(guild_id as u64),
+            channel_id: (channel_id != NO_CHANNEL).then_some(channel_id as u64),
+            scope: row.get("scope"),
+            key: row.get("key"),
+            old_value: row.get("old_value"),
+            new_value: row.get("new_value"),
+            changed_by: row.get::<i64, _>("changed_by") as u64,
+            changed_at: row.get("changed_at"),
+        }
+    }
+}
+
+/// One row of [`Db::task_state_stats`].
+pub struct TaskStateStats {
+    pub task: String,
+    pub prefix: String,
+    pub rows: i64,
+    pub expiring: i64,
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+/// A scope's settings for one channel, from [`Db::scope_values`].
+#[derive(Default)]
+pub struct ScopeValues {
+    channel: HashMap<String, String>,
+    server: HashMap<String, String>,
+    global: HashMap<String, String>,
+}
+
+impl ScopeValues {
+    /// The value that applies in the channel, like [`Db::resolve_setting`].
+    pub fn resolve(&self, key: &str) -> Option<&str> {
+        self.channel.get(key).or_else(|| self.server.get(key)).or_else(|| self.global.get(key)).map(String::as_str)
+    }
+
+    /// The server's own value, like [`Db::get_guild_setting`].
+    pub fn server(&self, key: &str) -> Option<&str> {
+        self.server.get(key).map(String::as_str)
+    }
+}
+
+/// A database file in the temp directory for tests, deleted with its WAL
+/// files when dropped, so even a failing test cleans up.
+#[cfg(test)]
+pub(crate) struct TempDb(std::path::PathBuf);
+
+#[cfg(test)]
+impl TempDb {
+    pub(crate) fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("shaktool-test-{}-{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        TempDb(path)
+    }
+
+    pub(crate) fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+
+    /// Connects to the database, migrating it.
+    pub(crate) async fn db(&self) -> Db {
+        Db::connect(self.path()).await.unwrap()
+    }
+
+    /// A pool on the file without running the migrations.
+    pub(crate) async fn raw_pool(&self) -> SqlitePool {
+        let options = SqliteConnectOptions::new().filename(&self.0).create_if_missing(true);
+        SqlitePoolOptions::new().max_connections(1).connect_with(options).await.unwrap()
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", self.path(), suffix));
+        }
+    }
 }
 
 #[cfg(test)]
@@ -201,50 +749,115 @@ mod tests {
         let path = std::env::temp_dir().join(format!("shaktool-test-{}.db", std::process::id()));
         let db = Db::connect(path.to_str().unwrap()).await.unwrap();
 
-        db.set_task_state("test", "key", "value").await.unwrap();
+        db.set_task_state("test", "key", "value", None).await.unwrap();
         assert_eq!(db.claim_task_state("test", "key").await.unwrap(), Some("value".to_string()));
         assert_eq!(db.claim_task_state("test", "key").await.unwrap(), None);
 
         drop(db);
         let _ = std::fs::remove_file(&path);
     }
-}
 
-/// Creates the settings table, upgrading a pre-guild-id table by moving its
-/// rows to the global guild.
-async fn migrate_settings(pool: &SqlitePool) -> Result<(), Error> {
-    let columns = sqlx::query("SELECT name FROM pragma_table_info('settings')")
-        .fetch_all(pool)
-        .await?;
-    let table_exists = !columns.is_empty();
-    let has_guild_id = columns.iter().any(|r| r.get::<String, _>("name") == "guild_id");
+    #[tokio::test]
+    async fn setting_changes_are_recorded() {
+        let path = std::env::temp_dir().join(format!("shaktool-test-history-{}.db", std::process::id()));
+        let db = Db::connect(path.to_str().unwrap()).await.unwrap();
 
-    if table_exists && !has_guild_id {
-        sqlx::query("ALTER TABLE settings RENAME TO settings_v1").execute(pool).await?;
+        db.set_guild_setting(1, "speedrun", "games", "smz3", 10).await.unwrap();
+        db.set_guild_setting(1, "speedrun", "games", "smz3", 10).await.unwrap();
+        db.set_global_setting("speedrun", "threshold", "60", 11).await.unwrap();
+        db.set_guild_setting(2, "speedrun", "games", "sm", 12).await.unwrap();
+        assert!(db.delete_guild_setting(1, "speedrun", "games", 10).await.unwrap());
+
+        // Guild 1 sees its own changes and global ones; the no-op isn't recorded.
+        let history = db.setting_history(1, None, None, 10).await.unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].old_value.as_deref(), Some("smz3"));
+        assert_eq!(history[0].new_value, None);
+        assert_eq!(history[1].guild_id, None);
+        assert_eq!(history[1].changed_by, 11);
+        assert_eq!(db.setting_history(1, Some("speedrun"), Some("threshold"), 10).await.unwrap().len(), 1);
+
+        let first = db.setting_change(history[2].id).await.unwrap().unwrap();
+        assert_eq!((first.old_value, first.new_value.as_deref()), (None, Some("smz3")));
+
+        db.close().await;
+        let _ = std::fs::remove_file(&path);
     }
 
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS settings (
-            guild_id INTEGER NOT NULL,
-            scope TEXT NOT NULL,
-            key TEXT NOT NULL,
-            value TEXT NOT NULL,
-            PRIMARY KEY (guild_id, scope, key)
-        )",
-    )
-    .execute(pool)
-    .await?;
+    #[tokio::test]
+    async fn channel_settings_fall_back_to_server_then_global() {
+        let path = std::env::temp_dir().join(format!("shaktool-test-channel-{}.db", std::process::id()));
+        let db = Db::connect(path.to_str().unwrap()).await.unwrap();
 
-    if table_exists && !has_guild_id {
-        sqlx::query(
-            "INSERT INTO settings (guild_id, scope, key, value)
-             SELECT ?, scope, key, value FROM settings_v1",
-        )
-        .bind(GLOBAL_GUILD)
-        .execute(pool)
-        .await?;
-        sqlx::query("DROP TABLE settings_v1").execute(pool).await?;
+        assert_eq!(db.resolve_setting(1, 10, "quad", "default_site").await.unwrap(), None);
+        db.set_global_setting("quad", "default_site", "global", 1).await.unwrap();
+        assert_eq!(db.resolve_setting(1, 10, "quad", "default_site").await.unwrap().as_deref(), Some("global"));
+        db.set_guild_setting(1, "quad", "default_site", "server", 1).await.unwrap();
+        assert_eq!(db.resolve_setting(1, 10, "quad", "default_site").await.unwrap().as_deref(), Some("server"));
+        db.set_channel_setting(1, 10, "quad", "default_site", "channel", 1).await.unwrap();
+        assert_eq!(db.resolve_setting(1, 10, "quad", "default_site").await.unwrap().as_deref(), Some("channel"));
+
+        // Other channels and servers don't see the override.
+        assert_eq!(db.resolve_setting(1, 11, "quad", "default_site").await.unwrap().as_deref(), Some("server"));
+        assert_eq!(db.resolve_setting(2, 10, "quad", "default_site").await.unwrap().as_deref(), Some("global"));
+        // The override doesn't leak into the server-wide value.
+        assert_eq!(db.get_guild_setting(1, "quad", "default_site").await.unwrap().as_deref(), Some("server"));
+        assert_eq!(db.guild_setting_values("quad", "default_site").await.unwrap(), vec![(1, "server".to_string())]);
+        // Read all at once, the scope resolves the same way.
+        let values = db.scope_values(1, 10, "quad").await.unwrap();
+        assert_eq!((values.resolve("default_site"), values.server("default_site")), (Some("channel"), Some("server")));
+        let values = db.scope_values(2, 10, "quad").await.unwrap();
+        assert_eq!((values.resolve("default_site"), values.server("default_site")), (Some("global"), None));
+
+        assert!(db.delete_channel_setting(1, 10, "quad", "default_site", 1).await.unwrap());
+        assert_eq!(db.resolve_setting(1, 10, "quad", "default_site").await.unwrap().as_deref(), Some("server"));
+
+        db.close().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn permissions_match_user_or_role() {
+        let path = std::env::temp_dir().join(format!("shaktool-test-permissions-{}.db", std::process::id()));
+        let db = Db::connect(path.to_str().unwrap()).await.unwrap();
+
+        assert!(db.grant_permission(1, "speedrun.review", PermissionTarget::Role(5)).await.unwrap());
+        assert!(!db.grant_permission(1, "speedrun.review", PermissionTarget::Role(5)).await.unwrap());
+        assert!(db.grant_permission(1, "config.quad", PermissionTarget::User(7)).await.unwrap());
+
+        assert!(db.has_permission(1, "speedrun.review", 9, &[4, 5]).await.unwrap());
+        assert!(!db.has_permission(2, "speedrun.review", 9, &[5]).await.unwrap());
+        assert!(db.has_permission(1, "config.quad", 7, &[]).await.unwrap());
+        assert!(!db.has_permission(1, "config.speedrun", 7, &[]).await.unwrap());
+        let granted = db.granted_permissions(1, 7, &[5]).await.unwrap();
+        assert_eq!(granted, HashSet::from(["config.quad".to_string(), "speedrun.review".to_string()]));
+
+        assert!(db.revoke_permission(1, "speedrun.review", PermissionTarget::Role(5)).await.unwrap());
+        assert!(!db.has_permission(1, "speedrun.review", 9, &[5]).await.unwrap());
+        assert_eq!(db.list_permissions(1).await.unwrap(), vec![("config.quad".to_string(), PermissionTarget::User(7))]);
+
+        db.close().await;
+        let _ = std::fs::remove_file(&path);
     }
 
-    Ok(())
+    #[tokio::test]
+    async fn expired_task_state_is_hidden_and_purged() {
+        let path = std::env::temp_dir().join(format!("shaktool-test-expiry-{}.db", std::process::id()));
+        let db = Db::connect(path.to_str().unwrap()).await.unwrap();
+
+        db.set_task_state("test", "keep", "1", None).await.unwrap();
+        db.set_task_state("test", "later", "1", Some(Duration::from_secs(3600))).await.unwrap();
+        db.set_task_state("test", "gone", "1", Some(Duration::ZERO)).await.unwrap();
+
+        assert_eq!(db.get_task_state("test", "gone").await.unwrap(), None);
+        assert_eq!(db.list_task_state("test", "").await.unwrap().len(), 2);
+        assert_eq!(db.purge_expired_task_state().await.unwrap(), 1);
+
+        let stats = db.task_state_stats().await.unwrap();
+        assert_eq!(stats.iter().map(|s| s.rows).sum::<i64>(), 2);
+        assert_eq!(stats.iter().map(|s| s.expiring).sum::<i64>(), 1);
+
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
 }
@@ -0,0 +1,210 @@
+use std::path::{Path, PathBuf};
+
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::Row;
+
+use super::{migrations, Db};
+use crate::Error;
+
+/// Backup file names are `shaktool-<UTC timestamp>-<random>.db`, so they
+/// sort by age, and backups taken within the same second don't collide.
+const PREFIX: &str = "shaktool-";
+const SUFFIX: &str = ".db";
+
+/// Where backups go and how many are kept, from `BACKUP_DIR` and
+/// `BACKUP_KEEP` (default 7). Backups are disabled without `BACKUP_DIR`.
+#[derive(Clone)]
+pub struct BackupConfig {
+    pub dir: PathBuf,
+    pub keep: usize,
+}
+
+impl BackupConfig {
+    pub fn from_env() -> Option<Self> {
+        let dir = std::env::var("BACKUP_DIR").ok().filter(|d| !d.is_empty())?;
+        let keep = std::env::var("BACKUP_KEEP").ok().and_then(|k| k.parse().ok()).unwrap_or(7).max(1);
+        Some(BackupConfig { dir: PathBuf::from(dir), keep })
+    }
+}
+
+/// A backup just written by [`create`].
+pub struct Backup {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Schema version of the backed-up database.
+    pub version: i64,
+    /// Old backups deleted to stay within the retention count.
+    pub pruned: usize,
+}
+
+/// Writes a consistent copy of the live database with `VACUUM INTO`, which
+/// doesn't block the bot, then deletes the oldest backups beyond
+/// `config.keep`. The copy is written under a temporary name and renamed, so
+/// an interrupted backup never looks complete.
+pub async fn create(db: &Db, config: &BackupConfig) -> Result<Backup, Error> {
+    tokio::fs::create_dir_all(&config.dir).await?;
+    let name = format!(
+        "{}{}-{:08x}{}",
+        PREFIX,
+        chrono::Utc::now().format("%Y%m%dT%H%M%SZ"),
+        rand::random::<u32>(),
+        SUFFIX
+    );
+    let path = config.dir.join(&name);
+    let partial = config.dir.join(format!("{}.partial", name));
+
+    if let Err(e) = write(db, &partial, &path).await {
+        let _ = tokio::fs::remove_file(&partial).await;
+        return Err(e);
+    }
+
+    let size = tokio::fs::metadata(&path).await?.len();
+    let version = migrations::current_version(&db.pool).await?;
+    let pruned = prune(&config.dir, config.keep)?;
+    Ok(Backup { path, size, version, pruned })
+}
+
+/// Vacuums the database into `partial`, then renames it to `path`.
+async fn write(db: &Db, partial: &Path, path: &Path) -> Result<(), Error> {
+    sqlx::query("VACUUM INTO ?")
+        .bind(partial.to_str().ok_or("backup path is not valid UTF-8")?)
+        .execute(&db.pool)
+        .await?;
+    tokio::fs::rename(partial, path).await?;
+    Ok(())
+}
+
+/// Deletes all but the newest `keep` backups in `dir`, returning how many
+/// were deleted. Other files in the directory are left alone.
+fn prune(dir: &Path, keep: usize) -> std::io::Result<usize> {
+    let mut backups: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(PREFIX) && n.ends_with(SUFFIX))
+        })
+        .collect();
+    backups.sort();
+    let excess = backups.len().saturating_sub(keep);
+    for path in &backups[..excess] {
+        std::fs::remove_file(path)?;
+    }
+    Ok(excess)
+}
+
+/// Replaces the database at `target` with `backup`, for `shaktool-rs restore
+/// <file>` while the bot is stopped. The backup must pass SQLite's integrity
+/// check and have a schema version this build understands; older versions
+/// are upgraded by the migrations on next start. Returns the backup's
+/// version.
+pub async fn restore(backup: &Path, target: &Path) -> Result<i64, Error> {
+    let options = SqliteConnectOptions::new().filename(backup).read_only(true);
+    let pool = SqlitePoolOptions::new().max_connections(1).connect_with(options).await?;
+    let integrity: String = sqlx::query("PRAGMA integrity_check").fetch_one(&pool).await?.get(0);
+    let version = migrations::current_version(&pool).await?;
+    pool.close().await;
+
+    if integrity != "ok" {
+        return Err(format!("{} failed the integrity check: {}", backup.display(), integrity).into());
+    }
+    if version > migrations::latest_version() {
+        return Err(format!(
+            "{} has schema version {}, newer than this build supports ({})",
+            backup.display(),
+            version,
+            migrations::latest_version()
+        )
+        .into());
+    }
+
+    // Copy next to the target first so the final swap is a rename, and drop
+    // the old WAL so it isn't replayed onto the restored file.
+    let staged = target.with_extension("restore");
+    tokio::fs::copy(backup, &staged).await?;
+    for suffix in ["-wal", "-shm"] {
+        let mut sidecar = target.as_os_str().to_owned();
+        sidecar.push(suffix);
+        match tokio::fs::remove_file(&sidecar).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    tokio::fs::rename(&staged, target).await?;
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("shaktool-backup-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn backup_restores_into_a_new_database() {
+        let dir = temp_dir("roundtrip");
+        let db = Db::connect(dir.join("live.db").to_str().unwrap()).await.unwrap();
+        db.set_guild_setting(1, "speedrun", "games", "smz3", 1).await.unwrap();
+
+        let config = BackupConfig { dir: dir.join("backups"), keep: 7 };
+        let backup = create(&db, &config).await.unwrap();
+        assert_eq!(backup.version, migrations::latest_version());
+        db.close().await;
+
+        let target = dir.join("restored.db");
+        assert_eq!(restore(&backup.path, &target).await.unwrap(), migrations::latest_version());
+        let restored = Db::connect(target.to_str().unwrap()).await.unwrap();
+        assert_eq!(restored.get_guild_setting(1, "speedrun", "games").await.unwrap().as_deref(), Some("smz3"));
+        restored.close().await;
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn backups_in_the_same_second_get_their_own_files() {
+        let dir = temp_dir("same-second");
+        let db = Db::connect(dir.join("live.db").to_str().unwrap()).await.unwrap();
+        let config = BackupConfig { dir: dir.join("backups"), keep: 7 };
+        let first = create(&db, &config).await.unwrap();
+        let second = create(&db, &config).await.unwrap();
+        assert_ne!(first.path, second.path);
+        assert!(first.path.exists() && second.path.exists());
+        db.close().await;
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn restore_refuses_newer_schema() {
+        let dir = temp_dir("newer");
+        let path = dir.join("backup.db");
+        let db = Db::connect(path.to_str().unwrap()).await.unwrap();
+        sqlx::query("INSERT INTO schema_version (version, description, applied_at) VALUES (?, 'future', '')")
+            .bind(migrations::latest_version() + 1)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        db.close().await;
+
+        assert!(restore(&path, &dir.join("target.db")).await.is_err());
+        assert!(!dir.join("target.db").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn prune_keeps_newest_backups_only() {
+        let dir = temp_dir("prune");
+        for name in ["shaktool-20260101T000000Z.db", "shaktool-20260102T000000Z.db", "shaktool-20260103T000000Z.db", "notes.txt"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        assert_eq!(prune(&dir, 2).unwrap(), 1);
+        assert!(!dir.join("shaktool-20260101T000000Z.db").exists());
+        assert!(dir.join("shaktool-20260103T000000Z.db").exists());
+        assert!(dir.join("notes.txt").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,518 @@
+//! Storage for the chatter brains (see `util::cobe`): the queries of cobe 2's
+//! `Graph`, against the `brain_*` tables. Callers pass a connection so a
+//! learn runs in one transaction and a reply reuses one connection.
+//!
+//! Every server has its own brain, identified by its guild id; nodes carry
+//! it, edges belong to the brain of their nodes, and tokens (with their
+//! stems) are shared by all brains. The edges each learned text added are
+//! kept as a contribution of its author so it can be unlearned again; the
+//! text itself isn't stored.
+
+use std::path::Path;
+
+use sqlx::pool::PoolConnection;
+use sqlx::{Row, Sqlite, SqliteConnection, Transaction};
+
+use super::{now, Db};
+use crate::Error;
+
+/// Tokens per context node.
+pub const ORDER: usize = 3;
+
+/// The empty token that pads the start and end of every learned text.
+pub const END_TOKEN: i64 = 1;
+
+/// Stand-in id for a single space between tokens. It is never stored: spaces
+/// become the `has_space` flag of an edge.
+pub const SPACE_TOKEN: i64 = -1;
+
+/// The brain shared by servers that opt into it (and used in DMs).
+pub const SHARED_BRAIN: u64 = 0;
+
+/// The size of one brain.
+pub struct BrainStats {
+    pub tokens: i64,
+    pub nodes: i64,
+    pub edges: i64,
+}
+
+impl Db {
+    /// A connection for a series of brain reads, such as a reply. It comes
+    /// from a pool of its own, so replies searching for a while never hold
+    /// up other queries.
+    pub async fn brain(&self) -> Result<PoolConnection<Sqlite>, Error> {
+        Ok(self.brain_pool.acquire().await?)
+    }
+
+    /// A transaction for learning.
+    pub async fn brain_transaction(&self) -> Result<Transaction<'static, Sqlite>, Error> {
+        Ok(self.pool.begin().await?)
+    }
+
+    /// The size of `brain`; its tokens are those ending one of its nodes.
+    pub async fn brain_stats(&self, brain: u64) -> Result<BrainStats, Error> {
+        let row = sqlx::query(
+            "SELECT (SELECT count(DISTINCT token2_id) FROM brain_nodes WHERE brain = ?1) AS tokens,
+                    (SELECT count(*) FROM brain_nodes WHERE brain = ?1) AS nodes,
+                    (SELECT count(*) FROM brain_edges
+                     WHERE prev_node IN (SELECT id FROM brain_nodes WHERE brain = ?1)) AS edges",
+        )
+        .bind(brain as i64)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(BrainStats { tokens: row.get("tokens"), nodes: row.get("nodes"), edges: row.get("edges") })
+    }
+
+    /// Forgets everything `brain` learned. Returns the number of nodes
+    /// removed.
+    pub async fn reset_brain(&self, brain: u64) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM brain_edges WHERE prev_node IN (SELECT id FROM brain_nodes WHERE brain = ?)")
+            .bind(brain as i64)
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query("DELETE FROM brain_nodes WHERE brain = ?").bind(brain as i64).execute(&mut *tx).await?;
+        sqlx::query("DELETE FROM brain_contributions WHERE brain = ?").bind(brain as i64).execute(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn brain_opted_out(&self, user_id: u64) -> Result<bool, Error> {
+        let row = sqlx::query("SELECT 1 FROM brain_opt_outs WHERE user_id = ?")
+            .bind(user_id as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    /// Opts a user out of (or back into) being learned from. Returns whether
+    /// anything changed.
+    pub async fn set_brain_opt_out(&self, user_id: u64, opted_out: bool) -> Result<bool, Error> {
+        let result = if opted_out {
+            sqlx::query("INSERT OR IGNORE INTO brain_opt_outs (user_id, since) VALUES (?, ?)")
+                .bind(user_id as i64)
+                .bind(now())
+                .execute(&self.pool)
+                .await?
+        } else {
+            sqlx::query("DELETE FROM brain_opt_outs WHERE user_id = ?").bind(user_id as i64).execute(&self.pool).await?
+        };
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Copies a Python cobe brain file (`bot.brain`) into the shared brain of
+    /// a database that hasn't learned anything yet, keeping its ids. Only
+    /// order-3 brains with the default (Cobe) tokenizer can be read. Token
+    /// stems are computed afresh, with the same stemmer cobe uses.
+    pub async fn import_cobe_brain(&self, path: &Path) -> Result<BrainStats, Error> {
+        if !path.is_file() {
+            return Err(format!("{} is not a file", path.display()).into());
+        }
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query("ATTACH DATABASE ? AS cobe")
+            .bind(path.to_str().ok_or("brain path is not valid UTF-8")?)
+            .execute(&mut *conn)
+            .await?;
+        let result = copy_cobe_brain(&mut conn).await;
+        sqlx::query("DETACH DATABASE cobe").execute(&mut *conn).await?;
+        result?;
+        self.brain_stats(SHARED_BRAIN).await
+    }
+}
+
+async fn copy_cobe_brain(conn: &mut SqliteConnection) -> Result<(), Error> {
+    let info = |attribute: &'static str| {
+        sqlx::query_scalar::<_, String>("SELECT text FROM cobe.info WHERE attribute = ?").bind(attribute)
+    };
+    let version = info("version").fetch_optional(&mut *conn).await.map_err(|_| "not a cobe brain")?;
+    if version.as_deref() != Some("2") {
+        return Err(format!("can't read a version {} cobe brain", version.unwrap_or_default()).into());
+    }
+    let order = info("order").fetch_optional(&mut *conn).await?;
+    if order.as_deref() != Some("3") {
+        return Err(format!("can't read an order {} cobe brain", order.unwrap_or_default()).into());
+    }
+    if info("tokenizer").fetch_optional(&mut *conn).await?.as_deref() == Some("MegaHAL") {
+        return Err("can't read a cobe brain using the MegaHAL tokenizer".into());
+    }
+    let end: Option<i64> =
+        sqlx::query_scalar("SELECT id FROM cobe.tokens WHERE text = ''").fetch_optional(&mut *conn).await?;
+    if end != Some(END_TOKEN) {
+        return Err("the cobe brain's end token isn't token 1".into());
+    }
+
+    let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+    let existing: i64 = sqlx::query_scalar("SELECT count(*) FROM brain_nodes").fetch_one(&mut *tx).await?;
+    if existing > 0 {
+        return Err("the brains already have learned text; reset them before importing".into());
+    }
+    sqlx::query("DELETE FROM brain_token_stems").execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM brain_tokens").execute(&mut *tx).await?;
+    sqlx::query("INSERT INTO brain_tokens (id, text, is_word) SELECT id, text, is_word FROM cobe.tokens")
+        .execute(&mut *tx)
+        .await?;
+    fill_stems(&mut tx).await?;
+    // Node counts are rebuilt by the edge triggers.
+    sqlx::query(
+        "INSERT INTO brain_nodes (id, brain, count, token0_id, token1_id, token2_id)
+         SELECT id, ?, 0, token0_id, token1_id, token2_id FROM cobe.nodes",
+    )
+    .bind(SHARED_BRAIN as i64)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "INSERT INTO brain_edges (id, prev_node, next_node, count, has_space)
+         SELECT id, prev_node, next_node, count, has_space FROM cobe.edges",
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn token_id(conn: &mut SqliteConnection, text: &str) -> Result<Option<i64>, Error> {
+    Ok(sqlx::query_scalar("SELECT id FROM brain_tokens WHERE text = ?").bind(text).fetch_optional(conn).await?)
+}
+
+/// The id of a token, created (with its stem) if it's new.
+pub async fn create_token(
+    conn: &mut SqliteConnection,
+    text: &str,
+    is_word: bool,
+    stem: Option<&str>,
+) -> Result<i64, Error> {
+    if let Some(id) = token_id(&mut *conn, text).await? {
+        return Ok(id);
+    }
+    let result = sqlx::query("INSERT INTO brain_tokens (text, is_word) VALUES (?, ?)")
+        .bind(text)
+        .bind(is_word)
+        .execute(&mut *conn)
+        .await?;
+    let id = result.last_insert_rowid();
+    if let Some(stem) = stem {
+        add_stem(conn, id, stem).await?;
+    }
+    Ok(id)
+}
+
+async fn add_stem(conn: &mut SqliteConnection, token: i64, stem: &str) -> Result<(), Error> {
+    sqlx::query("INSERT OR REPLACE INTO brain_token_stems (token_id, stem) VALUES (?, ?)")
+        .bind(token)
+        .bind(stem)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Stems every token that has none yet (see [`cobe::stem`](crate::util::cobe::stem)).
+async fn fill_stems(conn: &mut SqliteConnection) -> Result<(), Error> {
+    let rows = sqlx::query("SELECT id, text FROM brain_tokens WHERE id NOT IN (SELECT token_id FROM brain_token_stems)")
+        .fetch_all(&mut *conn)
+        .await?;
+    for row in rows {
+        if let Some(stem) = crate::util::cobe::stem(row.get("text")) {
+            add_stem(&mut *conn, row.get("id"), &stem).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Those of `ids` that are words `brain` has learned.
+pub async fn known_words(conn: &mut SqliteConnection, brain: u64, ids: &[i64]) -> Result<Vec<i64>, Error> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let placeholders = vec!["?"; ids.len()].join(",");
+    let sql = format!(
+        "SELECT id FROM brain_tokens t WHERE id IN ({}) AND is_word = 1
+         AND EXISTS (SELECT 1 FROM brain_nodes WHERE brain = ? AND token0_id = t.id)",
+        placeholders
+    );
+    let mut query = sqlx::query_scalar(&sql);
+    for id in ids {
+        query = query.bind(*id);
+    }
+    query = query.bind(brain as i64);
+    Ok(query.fetch_all(conn).await?)
+}
+
+/// The tokens with this stem that `brain` has learned.
+pub async fn tokens_with_stem(conn: &mut SqliteConnection, brain: u64, stem: &str) -> Result<Vec<i64>, Error> {
+    Ok(sqlx::query_scalar(
+        "SELECT token_id FROM brain_token_stems s WHERE stem = ?
+         AND EXISTS (SELECT 1 FROM brain_nodes WHERE brain = ? AND token0_id = s.token_id)",
+    )
+    .bind(stem)
+    .bind(brain as i64)
+    .fetch_all(conn)
+    .await?)
+}
+
+/// A random token other than the end token that `brain` has learned, for
+/// babbling.
+pub async fn random_token(conn: &mut SqliteConnection, brain: u64) -> Result<Option<i64>, Error> {
+    Ok(sqlx::query_scalar(
+        "SELECT token0_id FROM brain_nodes WHERE brain = ?1 AND token0_id <> ?2
+         LIMIT 1 OFFSET abs(random())
+             % max((SELECT count(*) FROM brain_nodes WHERE brain = ?1 AND token0_id <> ?2), 1)",
+    )
+    .bind(brain as i64)
+    .bind(END_TOKEN)
+    .fetch_optional(conn)
+    .await?)
+}
+
+pub async fn node_id(conn: &mut SqliteConnection, brain: u64, tokens: [i64; ORDER]) -> Result<Option<i64>, Error> {
+    Ok(sqlx::query_scalar(
+        "SELECT id FROM brain_nodes WHERE brain = ? AND token0_id = ? AND token1_id = ? AND token2_id = ?",
+    )
+    .bind(brain as i64)
+    .bind(tokens[0])
+    .bind(tokens[1])
+    .bind(tokens[2])
+    .fetch_optional(conn)
+    .await?)
+}
+
+/// The id of a context node, created if it's new.
+pub async fn create_node(conn: &mut SqliteConnection, brain: u64, tokens: [i64; ORDER]) -> Result<i64, Error> {
+    if let Some(id) = node_id(&mut *conn, brain, tokens).await? {
+        return Ok(id);
+    }
+    let result =
+        sqlx::query("INSERT INTO brain_nodes (brain, count, token0_id, token1_id, token2_id) VALUES (?, 0, ?, ?, ?)")
+            .bind(brain as i64)
+            .bind(tokens[0])
+            .bind(tokens[1])
+            .bind(tokens[2])
+            .execute(conn)
+            .await?;
+    Ok(result.last_insert_rowid())
+}
+
+/// Counts one more step from `prev` to `next`, returning the edge's id.
+pub async fn add_edge(conn: &mut SqliteConnection, prev: i64, next: i64, has_space: bool) -> Result<i64, Error> {
+    Ok(sqlx::query_scalar(
+        "INSERT INTO brain_edges (prev_node, next_node, has_space, count) VALUES (?, ?, ?, 1)
+         ON CONFLICT (prev_node, next_node, has_space) DO UPDATE SET count = count + 1
+         RETURNING id",
+    )
+    .bind(prev)
+    .bind(next)
+    .bind(has_space)
+    .fetch_one(conn)
+    .await?)
+}
+
+/// Takes one step along `edge` back out, removing the edge once its count
+/// reaches zero. Returns the edge's nodes, or `None` if it's already gone.
+pub async fn remove_edge(conn: &mut SqliteConnection, edge: i64) -> Result<Option<(i64, i64)>, Error> {
+    let row = sqlx::query("UPDATE brain_edges SET count = count - 1 WHERE id = ? RETURNING prev_node, next_node")
+        .bind(edge)
+        .fetch_optional(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM brain_edges WHERE id = ? AND count <= 0").bind(edge).execute(conn).await?;
+    Ok(row.map(|r| (r.get("prev_node"), r.get("next_node"))))
+}
+
+/// Deletes those of `nodes` no edge leads into or out of anymore, returning
+/// the tokens of the deleted nodes.
+pub async fn prune_nodes(conn: &mut SqliteConnection, nodes: &[i64]) -> Result<Vec<i64>, Error> {
+    let mut tokens = Vec::new();
+    for node in nodes {
+        let row = sqlx::query(
+            "DELETE FROM brain_nodes WHERE id = ?1
+             AND NOT EXISTS (SELECT 1 FROM brain_edges WHERE prev_node = ?1)
+             AND NOT EXISTS (SELECT 1 FROM brain_edges WHERE next_node = ?1)
+             RETURNING token0_id, token1_id, token2_id",
+        )
+        .bind(node)
+        .fetch_optional(&mut *conn)
+        .await?;
+        if let Some(row) = row {
+            tokens.extend([row.get::<i64, _>(0), row.get(1), row.get(2)]);
+        }
+    }
+    Ok(tokens)
+}
+
+/// Deletes those of `tokens` (and their stems) no brain uses anymore. Every
+/// token of a learned text starts one of its nodes, so a token no node
+/// starts with is unused.
+pub async fn prune_tokens(conn: &mut SqliteConnection, tokens: &[i64]) -> Result<(), Error> {
+    for token in tokens.iter().filter(|&&t| t != END_TOKEN) {
+        let unused: bool = sqlx::query_scalar("SELECT NOT EXISTS (SELECT 1 FROM brain_nodes WHERE token0_id = ?)")
+            .bind(token)
+            .fetch_one(&mut *conn)
+            .await?;
+        if unused {
+            sqlx::query("DELETE FROM brain_token_stems WHERE token_id = ?").bind(token).execute(&mut *conn).await?;
+            sqlx::query("DELETE FROM brain_tokens WHERE id = ?").bind(token).execute(&mut *conn).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Records that `user_id` taught `brain` a text in `guild_id`, as the edges
+/// learning it added.
+pub async fn add_contribution(
+    conn: &mut SqliteConnection,
+    brain: u64,
+    guild_id: u64,
+    user_id: u64,
+    edges: &[i64],
+) -> Result<(), Error> {
+    sqlx::query("INSERT INTO brain_contributions (brain, guild_id, user_id, edges, learned_at) VALUES (?, ?, ?, ?, ?)")
+        .bind(brain as i64)
+        .bind(guild_id as i64)
+        .bind(user_id as i64)
+        .bind(edge_list(edges))
+        .bind(now())
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// How a contribution's edges are stored.
+fn edge_list(edges: &[i64]) -> String {
+    edges.iter().map(i64::to_string).collect::<Vec<_>>().join(",")
+}
+
+/// Up to `limit` of a user's recorded contributions as `(id, edges)`, in
+/// every server or only those made in `guild_id`.
+pub async fn contributions(
+    conn: &mut SqliteConnection,
+    user_id: u64,
+    guild_id: Option<u64>,
+    limit: i64,
+) -> Result<Vec<(i64, Vec<i64>)>, Error> {
+    let rows = sqlx::query(
+        "SELECT id, edges FROM brain_contributions WHERE user_id = ?1 AND (?2 IS NULL OR guild_id = ?2) LIMIT ?3",
+    )
+    .bind(user_id as i64)
+    .bind(guild_id.map(|g| g as i64))
+    .bind(limit)
+    .fetch_all(conn)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|r| {
+            let edges: String = r.get("edges");
+            (r.get("id"), edges.split(',').filter_map(|e| e.parse().ok()).collect())
+        })
+        .collect())
+}
+
+pub async fn delete_contribution(conn: &mut SqliteConnection, id: i64) -> Result<(), Error> {
+    sqlx::query("DELETE FROM brain_contributions WHERE id = ?").bind(id).execute(conn).await?;
+    Ok(())
+}
+
+/// A random node of `brain` starting with `token`.
+pub async fn random_node_with_token(conn: &mut SqliteConnection, brain: u64, token: i64) -> Result<Option<i64>, Error> {
+    Ok(sqlx::query_scalar(
+        "SELECT id FROM brain_nodes WHERE brain = ?1 AND token0_id = ?2
+         LIMIT 1 OFFSET abs(random()) % max((SELECT count(*) FROM brain_nodes WHERE brain = ?1 AND token0_id = ?2), 1)",
+    )
+    .bind(brain as i64)
+    .bind(token)
+    .fetch_optional(conn)
+    .await?)
+}
+
+/// A random edge out of (`forward`) or into `node`, as `(edge, other node)`.
+pub async fn random_edge(conn: &mut SqliteConnection, node: i64, forward: bool) -> Result<Option<(i64, i64)>, Error> {
+    let sql = if forward {
+        "SELECT id, next_node AS other FROM brain_edges WHERE prev_node = ?1
+         LIMIT 1 OFFSET abs(random()) % max((SELECT count(*) FROM brain_edges WHERE prev_node = ?1), 1)"
+    } else {
+        "SELECT id, prev_node AS other FROM brain_edges WHERE next_node = ?1
+         LIMIT 1 OFFSET abs(random()) % max((SELECT count(*) FROM brain_edges WHERE next_node = ?1), 1)"
+    };
+    let row = sqlx::query(sql).bind(node).fetch_optional(conn).await?;
+    Ok(row.map(|r| (r.get("id"), r.get("other"))))
+}
+
+/// `log2 P(edge | its first node)` and whether a space follows the edge's
+/// token, for scoring.
+pub async fn edge_info(conn: &mut SqliteConnection, edge: i64) -> Result<(f64, bool), Error> {
+    let row = sqlx::query(
+        "SELECT e.count AS edge_count, n.count AS node_count, e.has_space
+         FROM brain_edges e JOIN brain_nodes n ON e.prev_node = n.id WHERE e.id = ?",
+    )
+    .bind(edge)
+    .fetch_one(conn)
+    .await?;
+    let (edge_count, node_count): (i64, i64) = (row.get("edge_count"), row.get("node_count"));
+    let logprob = if node_count > 0 { (edge_count as f64).log2() - (node_count as f64).log2() } else { 0.0 };
+    Ok((logprob, row.get("has_space")))
+}
+
+/// The text an edge contributes to a reply: the last token of its first
+/// node, and whether a space follows.
+pub async fn edge_text(conn: &mut SqliteConnection, edge: i64) -> Result<(String, bool), Error> {
+    let row = sqlx::query(
+        "SELECT t.text, e.has_space FROM brain_edges e
+         JOIN brain_nodes n ON e.prev_node = n.id
+         JOIN brain_tokens t ON n.token2_id = t.id
+         WHERE e.id = ?",
+    )
+    .bind(edge)
+    .fetch_one(conn)
+    .await?;
+    Ok((row.get("text"), row.get("has_space")))
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::SqliteConnectOptions;
+    use sqlx::ConnectOptions;
+
+    use super::*;
+    use crate::db::TempDb;
+
+    /// Writes a tiny cobe 2 brain that learned "a b c" (no spaces).
+    async fn write_cobe_brain(path: &Path) {
+        let mut conn = SqliteConnectOptions::new().filename(path).create_if_missing(true).connect().await.unwrap();
+        sqlx::raw_sql(
+            "CREATE TABLE info (attribute TEXT NOT NULL PRIMARY KEY, text TEXT NOT NULL);
+             INSERT INTO info VALUES ('version', '2'), ('order', '3'), ('tokenizer', 'Cobe');
+             CREATE TABLE tokens (id INTEGER PRIMARY KEY AUTOINCREMENT, text TEXT UNIQUE NOT NULL, is_word INTEGER NOT NULL);
+             INSERT INTO tokens VALUES (1, '', 0), (2, 'a', 1), (3, 'b', 1), (4, 'c', 1);
+             CREATE TABLE nodes (id INTEGER PRIMARY KEY AUTOINCREMENT, count INTEGER NOT NULL,
+                 token0_id INTEGER, token1_id INTEGER, token2_id INTEGER);
+             INSERT INTO nodes VALUES (1, 1, 1, 1, 1), (2, 1, 1, 1, 2), (3, 1, 1, 2, 3), (4, 1, 2, 3, 4),
+                 (5, 1, 3, 4, 1), (6, 1, 4, 1, 1);
+             CREATE TABLE edges (id INTEGER PRIMARY KEY AUTOINCREMENT, prev_node INTEGER NOT NULL,
+                 next_node INTEGER NOT NULL, count INTEGER NOT NULL, has_space INTEGER NOT NULL);
+             INSERT INTO edges VALUES (1, 1, 2, 1, 0), (2, 2, 3, 1, 0), (3, 3, 4, 1, 0), (4, 4, 5, 1, 0),
+                 (5, 5, 6, 1, 0), (6, 6, 1, 1, 0);",
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn imports_a_cobe_brain_once() {
+        let brain = TempDb::new("bot-brain");
+        let temp = TempDb::new("import");
+        write_cobe_brain(Path::new(brain.path())).await;
+        let db = temp.db().await;
+
+        let stats = db.import_cobe_brain(Path::new(brain.path())).await.unwrap();
+        // Tokens counted are those the nodes end in: a, b, c and the end token.
+        assert_eq!((stats.tokens, stats.nodes, stats.edges), (4, 6, 6));
+        let mut conn = db.brain().await.unwrap();
+        assert_eq!(token_id(&mut conn, "b").await.unwrap(), Some(3));
+        assert_eq!(tokens_with_stem(&mut conn, SHARED_BRAIN, "b").await.unwrap(), vec![3]);
+        // Counts come from the edges: one step into the start node.
+        let (logprob, _) = edge_info(&mut conn, 1).await.unwrap();
+        assert_eq!(logprob, 0.0);
+        drop(conn);
+
+        assert!(db.import_cobe_brain(Path::new(brain.path())).await.is_err(), "a second import must not merge");
+        db.close().await;
+    }
+}
@@ -0,0 +1,149 @@
+//! Token usage of language model calls, for reports and per-server budgets
+//! (see `util::llm_usage`).
+
+use sqlx::Row;
+
+use super::{now, Db};
+use crate::api::llm::LlmUsage;
+use crate::Error;
+
+/// A recorded call: what it was for and who it was for.
+pub struct UsageEntry<'a> {
+    pub guild_id: Option<u64>,
+    pub user_id: Option<u64>,
+    pub feature: &'a str,
+    pub model: &'a str,
+    pub usage: LlmUsage,
+}
+
+/// Calls and tokens of one feature or user over a period.
+#[derive(Debug, PartialEq)]
+pub struct UsageTotal {
+    /// The feature name or user id.
+    pub key: String,
+    pub calls: u64,
+    pub usage: LlmUsage,
+}
+
+impl Db {
+    pub async fn record_llm_usage(&self, entry: &UsageEntry<'_>) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO llm_usage (at, guild_id, user_id, feature, model, input_tokens, output_tokens)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(now())
+        .bind(entry.guild_id.map(|id| id as i64))
+        .bind(entry.user_id.map(|id| id as i64))
+        .bind(entry.feature)
+        .bind(entry.model)
+        .bind(entry.usage.input_tokens as i64)
+        .bind(entry.usage.output_tokens as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Tokens used for a server since `since` (Unix seconds).
+    pub async fn llm_tokens_since(&self, guild_id: u64, since: i64) -> Result<u64, Error> {
+        let row = sqlx::query(
+            "SELECT COALESCE(SUM(input_tokens + output_tokens), 0) AS tokens FROM llm_usage
+             WHERE guild_id = ? AND at >= ?",
+        )
+        .bind(guild_id as i64)
+        .bind(since)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(row.get::<i64, _>("tokens") as u64)
+    }
+
+    /// Calls made for a user's use of `feature` in a server since `since`.
+    pub async fn llm_calls_since(&self, guild_id: u64, user_id: u64, feature: &str, since: i64) -> Result<u64, Error> {
+        let row = sqlx::query(
+            "SELECT COUNT(*) AS calls FROM llm_usage WHERE guild_id = ? AND user_id = ? AND feature = ? AND at >= ?",
+        )
+        .bind(guild_id as i64)
+        .bind(user_id as i64)
+        .bind(feature)
+        .bind(since)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(row.get::<i64, _>("calls") as u64)
+    }
+
+    /// A server's usage since `since` per feature, most tokens first.
+    pub async fn llm_usage_by_feature(&self, guild_id: u64, since: i64) -> Result<Vec<UsageTotal>, Error> {
+        self.llm_usage_by("feature", guild_id, since).await
+    }
+
+    /// A server's usage since `since` per user, most tokens first. Calls not
+    /// made for a user aren't included.
+    pub async fn llm_usage_by_user(&self, guild_id: u64, since: i64) -> Result<Vec<UsageTotal>, Error> {
+        self.llm_usage_by("user_id", guild_id, since).await
+    }
+
+    /// `column` is one of ours, never user input.
+    async fn llm_usage_by(&self, column: &str, guild_id: u64, since: i64) -> Result<Vec<UsageTotal>, Error> {
+        let rows = sqlx::query(&format!(
+            "SELECT CAST({column} AS TEXT) AS key, COUNT(*) AS calls, SUM(input_tokens) AS input_tokens,
+                 SUM(output_tokens) AS output_tokens
+             FROM llm_usage WHERE guild_id = ? AND at >= ? AND {column} IS NOT NULL
+             GROUP BY {column} ORDER BY SUM(input_tokens + output_tokens) DESC"
+        ))
+        .bind(guild_id as i64)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .iter()
+            .map(|r| UsageTotal {
+                key: r.get("key"),
+                calls: r.get::<i64, _>("calls") as u64,
+                usage: LlmUsage {
+                    input_tokens: r.get::<i64, _>("input_tokens") as u64,
+                    output_tokens: r.get::<i64, _>("output_tokens") as u64,
+                },
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::TempDb;
+
+    #[tokio::test]
+    async fn usage_adds_up_per_server_feature_and_user() {
+        let temp = TempDb::new("llm-usage");
+        let db = temp.db().await;
+        let entry = |guild_id, user_id, feature, input_tokens| UsageEntry {
+            guild_id,
+            user_id,
+            feature,
+            model: "m",
+            usage: LlmUsage { input_tokens, output_tokens: 10 },
+        };
+        db.record_llm_usage(&entry(Some(1), Some(7), "ask", 100)).await.unwrap();
+        db.record_llm_usage(&entry(Some(1), Some(8), "ask", 50)).await.unwrap();
+        db.record_llm_usage(&entry(Some(1), Some(7), "chat", 500)).await.unwrap();
+        db.record_llm_usage(&entry(Some(2), Some(7), "ask", 1000)).await.unwrap();
+        db.record_llm_usage(&entry(None, None, "wiki_index", 1000)).await.unwrap();
+
+        assert_eq!(db.llm_tokens_since(1, 0).await.unwrap(), 680);
+        assert_eq!(db.llm_tokens_since(1, now() + 60).await.unwrap(), 0);
+        let features = db.llm_usage_by_feature(1, 0).await.unwrap();
+        assert_eq!(
+            features,
+            vec![
+                UsageTotal { key: "chat".to_string(), calls: 1, usage: LlmUsage { input_tokens: 500, output_tokens: 10 } },
+                UsageTotal { key: "ask".to_string(), calls: 2, usage: LlmUsage { input_tokens: 150, output_tokens: 20 } },
+            ]
+        );
+        let users = db.llm_usage_by_user(1, 0).await.unwrap();
+        assert_eq!((users[0].key.as_str(), users[0].usage.total()), ("7", 620));
+        assert_eq!(db.llm_calls_since(1, 7, "ask", 0).await.unwrap(), 1);
+        assert_eq!(db.llm_calls_since(1, 7, "ask", now() + 60).await.unwrap(), 0);
+
+        db.close().await;
+    }
+}
@@ -0,0 +1,302 @@
+use futures::future::BoxFuture;
+use sqlx::{Row, SqliteConnection, SqlitePool};
+use tracing::info;
+
+use super::GLOBAL_GUILD;
+use crate::Error;
+
+/// One schema change. Migrations are numbered from 1 and applied in order;
+/// a database's version is the highest migration applied to it.
+///
+/// To change the schema, append a migration here — never edit or reorder
+/// one that has shipped, since existing databases already applied it.
+struct Migration {
+    version: i64,
+    description: &'static str,
+    step: Step,
+}
+
+enum Step {
+    /// Plain SQL, usually embedded with `include_str!` from `migrations/`.
+    Sql(&'static str),
+    /// Rust code, for changes that depend on what's already in the database.
+    Rust(for<'c> fn(&'c mut SqliteConnection) -> BoxFuture<'c, Result<(), Error>>),
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "settings keyed by guild, task_state",
+        step: Step::Rust(initial_schema),
+    },
+    Migration {
+        version: 2,
+        description: "task_state expiry",
+        step: Step::Sql(include_str!("migrations/0002_task_state_expiry.sql")),
+    },
+    Migration {
+        version: 3,
+        description: "settings history",
+        step: Step::Sql(include_str!("migrations/0003_settings_history.sql")),
+    },
+    Migration {
+        version: 4,
+        description: "permission grants",
+        step: Step::Sql(include_str!("migrations/0004_permissions.sql")),
+    },
+    Migration {
+        version: 5,
+        description: "per-channel settings",
+        step: Step::Sql(include_str!("migrations/0005_channel_settings.sql")),
+    },
+    Migration {
+        version: 6,
+        description: "native chatter brain",
+        step: Step::Sql(include_str!("migrations/0006_brain.sql")),
+    },
+    Migration {
+        version: 7,
+        description: "chatter brain per server",
+        step: Step::Sql(include_str!("migrations/0007_guild_brains.sql")),
+    },
+    Migration {
+        version: 8,
+        description: "chatter brain contributions and opt-outs",
+        step: Step::Sql(include_str!("migrations/0008_brain_contributions.sql")),
+    },
+    Migration {
+        version: 9,
+        description: "wiki search index",
+        step: Step::Sql(include_str!("migrations/0009_wiki_index.sql")),
+    },
+    Migration {
+        version: 10,
+        description: "llm usage",
+        step: Step::Sql(include_str!("migrations/0010_llm_usage.sql")),
+    },
+];
+
+/// The schema version this build creates and understands.
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+/// Reads the schema version of a database; 0 if it predates versioning.
+pub async fn current_version(pool: &SqlitePool) -> Result<i64, Error> {
+    let exists = sqlx::query("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'schema_version'")
+        .fetch_optional(pool)
+        .await?
+        .is_some();
+    if !exists {
+        return Ok(0);
+    }
+    let row = sqlx::query("SELECT COALESCE(MAX(version), 0) AS version FROM schema_version")
+        .fetch_one(pool)
+        .await?;
+    Ok(row.get("version"))
+}
+
+/// Brings the database up to [`latest_version`], applying every pending
+/// migration in a single transaction: either all of them apply or none do.
+/// Refuses to touch a database from a newer build.
+pub async fn run(pool: &SqlitePool) -> Result<(), Error> {
+    migrate_to(pool, latest_version()).await
+}
+
+pub(super) async fn migrate_to(pool: &SqlitePool, target: i64) -> Result<(), Error> {
+    let current = current_version(pool).await?;
+    if current > latest_version() {
+        return Err(format!(
+            "database schema version {} is newer than this build supports ({}); refusing to start",
+            current,
+            latest_version()
+        )
+        .into());
+    }
+
+    let pending: Vec<&Migration> = MIGRATIONS
+        .iter()
+        .filter(|m| m.version > current && m.version <= target)
+        .collect();
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        )",
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    for migration in pending {
+        info!("Applying database migration {}: {}", migration.version, migration.description);
+        match migration.step {
+            Step::Sql(sql) => {
+                sqlx::raw_sql(sql).execute(&mut *tx).await?;
+            }
+            Step::Rust(step) => step(&mut tx).await?,
+        }
+        sqlx::query("INSERT INTO schema_version (version, description, applied_at) VALUES (?, ?, ?)")
+            .bind(migration.version)
+            .bind(migration.description)
+            .bind(chrono::Utc::now().to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Version 1: the schema as it was before migrations were versioned. Creates
+/// the tables if missing and upgrades a pre-guild-id settings table by moving
+/// its rows to the global guild.
+fn initial_schema(conn: &mut SqliteConnection) -> BoxFuture<'_, Result<(), Error>> {
+    Box::pin(async move {
+        let columns = sqlx::query("SELECT name FROM pragma_table_info('settings')")
+            .fetch_all(&mut *conn)
+            .await?;
+        let table_exists = !columns.is_empty();
+        let has_guild_id = columns.iter().any(|r| r.get::<String, _>("name") == "guild_id");
+
+        if table_exists && !has_guild_id {
+            sqlx::query("ALTER TABLE settings RENAME TO settings_v1").execute(&mut *conn).await?;
+        }
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS settings (
+                guild_id INTEGER NOT NULL,
+                scope TEXT NOT NULL,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (guild_id, scope, key)
+            )",
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        if table_exists && !has_guild_id {
+            sqlx::query(
+                "INSERT INTO settings (guild_id, scope, key, value)
+                 SELECT ?, scope, key, value FROM settings_v1",
+            )
+            .bind(GLOBAL_GUILD)
+            .execute(&mut *conn)
+            .await?;
+            sqlx::query("DROP TABLE settings_v1").execute(&mut *conn).await?;
+        }
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS task_state (
+                task TEXT NOT NULL,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (task, key)
+            )",
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{Db, TempDb};
+
+    /// Checks a fully migrated database works through the public API.
+    async fn assert_usable(db: &Db) {
+        db.set_guild_setting(1, "speedrun", "games", "smz3", 1).await.unwrap();
+        assert_eq!(db.get_guild_setting(1, "speedrun", "games").await.unwrap().as_deref(), Some("smz3"));
+        db.set_task_state("test", "key", "value", None).await.unwrap();
+        assert_eq!(db.get_task_state("test", "key").await.unwrap().as_deref(), Some("value"));
+    }
+
+    #[tokio::test]
+    async fn upgrades_from_every_past_version() {
+        for version in 0..=latest_version() {
+            let temp = TempDb::new(&format!("migrate-v{}", version));
+            {
+                let pool = temp.raw_pool().await;
+                migrate_to(&pool, version).await.unwrap();
+                assert_eq!(current_version(&pool).await.unwrap(), version);
+                pool.close().await;
+            }
+            let db = Db::connect(temp.path()).await.unwrap();
+            assert_eq!(current_version(&db.pool).await.unwrap(), latest_version(), "from v{}", version);
+            assert_usable(&db).await;
+            db.close().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn upgrades_unversioned_database_keeping_data() {
+        let temp = TempDb::new("migrate-unversioned");
+        {
+            let pool = temp.raw_pool().await;
+            sqlx::raw_sql(
+                "CREATE TABLE settings (guild_id INTEGER NOT NULL, scope TEXT NOT NULL, key TEXT NOT NULL,
+                     value TEXT NOT NULL, PRIMARY KEY (guild_id, scope, key));
+                 CREATE TABLE task_state (task TEXT NOT NULL, key TEXT NOT NULL, value TEXT NOT NULL,
+                     PRIMARY KEY (task, key));
+                 INSERT INTO settings VALUES (42, 'speedrun', 'games', 'supermetroid');
+                 INSERT INTO task_state VALUES ('speedrun_monitor', 'seen:abc', '1');",
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+            pool.close().await;
+        }
+        let db = Db::connect(temp.path()).await.unwrap();
+        assert_eq!(
+            db.get_guild_setting(42, "speedrun", "games").await.unwrap().as_deref(),
+            Some("supermetroid")
+        );
+        assert_eq!(db.get_task_state("speedrun_monitor", "seen:abc").await.unwrap().as_deref(), Some("1"));
+        db.close().await;
+    }
+
+    #[tokio::test]
+    async fn upgrades_pre_guild_settings_to_global() {
+        let temp = TempDb::new("migrate-preguild");
+        {
+            let pool = temp.raw_pool().await;
+            sqlx::raw_sql(
+                "CREATE TABLE settings (scope TEXT NOT NULL, key TEXT NOT NULL, value TEXT NOT NULL,
+                     PRIMARY KEY (scope, key));
+                 INSERT INTO settings VALUES ('speedrun', 'threshold', '40');",
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+            pool.close().await;
+        }
+        let db = Db::connect(temp.path()).await.unwrap();
+        assert_eq!(db.get_global_setting("speedrun", "threshold").await.unwrap().as_deref(), Some("40"));
+        assert_usable(&db).await;
+        db.close().await;
+    }
+
+    #[tokio::test]
+    async fn refuses_newer_schema() {
+        let temp = TempDb::new("migrate-newer");
+        {
+            let db = Db::connect(temp.path()).await.unwrap();
+            sqlx::query("INSERT INTO schema_version (version, description, applied_at) VALUES (?, 'future', '')")
+                .bind(latest_version() + 1)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+            db.close().await;
+        }
+        let error = Db::connect(temp.path()).await.err().expect("newer schema must be refused");
+        assert!(error.to_string().contains("newer than this build"), "{}", error);
+    }
+}
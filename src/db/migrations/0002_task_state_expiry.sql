@@ -0,0 +1,4 @@
+-- Optional expiry for task state entries, as a unix timestamp. NULL never
+-- expires. Expired rows are hidden from reads and deleted by the cleanup task.
+ALTER TABLE task_state ADD COLUMN expires_at INTEGER;
+CREATE INDEX task_state_expires_at ON task_state (expires_at) WHERE expires_at IS NOT NULL;
@@ -0,0 +1,13 @@
+-- Every change made to a setting. guild_id 0 is a global setting; a NULL
+-- value means unset. changed_by is the Discord user id, changed_at unix time.
+CREATE TABLE settings_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    scope TEXT NOT NULL,
+    key TEXT NOT NULL,
+    old_value TEXT,
+    new_value TEXT,
+    changed_by INTEGER NOT NULL,
+    changed_at INTEGER NOT NULL
+);
+CREATE INDEX settings_history_setting ON settings_history (guild_id, scope, key);
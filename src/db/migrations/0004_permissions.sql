@@ -0,0 +1,9 @@
+-- Command groups granted per guild to a role or a user (target_kind 'role'
+-- or 'user'); see src/permissions.rs for the groups.
+CREATE TABLE permissions (
+    guild_id INTEGER NOT NULL,
+    permission TEXT NOT NULL,
+    target_kind TEXT NOT NULL,
+    target_id INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, permission, target_kind, target_id)
+);
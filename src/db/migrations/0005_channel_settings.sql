@@ -0,0 +1,17 @@
+-- Per-channel settings: channel_id 0 is a server-wide (or, with guild_id 0,
+-- global) value; anything else overrides it in that channel. SQLite can't
+-- change a primary key in place, so the table is rebuilt.
+CREATE TABLE settings_new (
+    guild_id INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL DEFAULT 0,
+    scope TEXT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (guild_id, channel_id, scope, key)
+);
+INSERT INTO settings_new (guild_id, channel_id, scope, key, value)
+    SELECT guild_id, 0, scope, key, value FROM settings;
+DROP TABLE settings;
+ALTER TABLE settings_new RENAME TO settings;
+
+ALTER TABLE settings_history ADD COLUMN channel_id INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,48 @@
+-- The chatter brain (see util::cobe), cobe 2's graph in native tables.
+-- Tokens are words, punctuation runs and whitespace other than a single
+-- space (which is a flag on edges instead); token 1 is the empty end token.
+CREATE TABLE brain_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    text TEXT UNIQUE NOT NULL,
+    is_word INTEGER NOT NULL
+);
+INSERT INTO brain_tokens (id, text, is_word) VALUES (1, '', 0);
+
+-- The lowercase stem of every word token (and `:)` or `:(` for smileys), so
+-- a reply can pivot on any form of an input word.
+CREATE TABLE brain_token_stems (
+    token_id INTEGER PRIMARY KEY REFERENCES brain_tokens(id),
+    stem TEXT NOT NULL
+);
+CREATE INDEX brain_token_stems_stem ON brain_token_stems (stem);
+
+-- Nodes are order-3 contexts: three consecutive tokens.
+CREATE TABLE brain_nodes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    count INTEGER NOT NULL,
+    token0_id INTEGER NOT NULL,
+    token1_id INTEGER NOT NULL,
+    token2_id INTEGER NOT NULL
+);
+CREATE UNIQUE INDEX brain_nodes_tokens ON brain_nodes (token0_id, token1_id, token2_id);
+-- To find tokens no longer used by any node.
+CREATE INDEX brain_nodes_token0 ON brain_nodes (token0_id);
+
+-- Edges link consecutive contexts and count how often that step was learned.
+CREATE TABLE brain_edges (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    prev_node INTEGER NOT NULL REFERENCES brain_nodes(id),
+    next_node INTEGER NOT NULL REFERENCES brain_nodes(id),
+    count INTEGER NOT NULL,
+    has_space INTEGER NOT NULL
+);
+CREATE UNIQUE INDEX brain_edges_prev ON brain_edges (prev_node, next_node, has_space);
+CREATE INDEX brain_edges_next ON brain_edges (next_node);
+
+-- A node's count is the total count of the edges into it.
+CREATE TRIGGER brain_edges_insert AFTER INSERT ON brain_edges
+    BEGIN UPDATE brain_nodes SET count = count + NEW.count WHERE id = NEW.next_node; END;
+CREATE TRIGGER brain_edges_update AFTER UPDATE ON brain_edges
+    BEGIN UPDATE brain_nodes SET count = count + (NEW.count - OLD.count) WHERE id = NEW.next_node; END;
+CREATE TRIGGER brain_edges_delete AFTER DELETE ON brain_edges
+    BEGIN UPDATE brain_nodes SET count = count - OLD.count WHERE id = OLD.next_node; END;
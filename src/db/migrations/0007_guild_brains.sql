@@ -0,0 +1,7 @@
+-- Separate chatter brains per server. Nodes (and through them edges) belong
+-- to one brain: a guild id, or 0 for the shared brain. Tokens are only
+-- interned text and stay shared. Everything learned so far was learned from
+-- every server at once, so it becomes the shared brain.
+ALTER TABLE brain_nodes ADD COLUMN brain INTEGER NOT NULL DEFAULT 0;
+DROP INDEX brain_nodes_tokens;
+CREATE UNIQUE INDEX brain_nodes_tokens ON brain_nodes (brain, token0_id, token1_id, token2_id);
@@ -0,0 +1,21 @@
+-- Who taught the chatter brains what: the edges every learned text added,
+-- with its author, so a user's contributions can be unlearned without
+-- storing what they said. Text learned before this (or imported) has no
+-- author and stays.
+CREATE TABLE brain_contributions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    brain INTEGER NOT NULL,
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    -- Comma-separated brain_edges ids.
+    edges TEXT NOT NULL,
+    learned_at INTEGER NOT NULL
+);
+CREATE INDEX brain_contributions_user ON brain_contributions (user_id, guild_id);
+CREATE INDEX brain_contributions_brain ON brain_contributions (brain);
+
+-- Users who asked not to be learned from, in any server.
+CREATE TABLE brain_opt_outs (
+    user_id INTEGER PRIMARY KEY,
+    since INTEGER NOT NULL
+);
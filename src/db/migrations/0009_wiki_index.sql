@@ -0,0 +1,20 @@
+-- The wiki search index behind `ask` (see util::wiki_search): every indexed
+-- wiki page at the revision and with the embedding model it was indexed
+-- with, and its chunks of text with their embeddings (little-endian f32s).
+CREATE TABLE wiki_pages (
+    page_id INTEGER PRIMARY KEY,
+    title TEXT NOT NULL,
+    revision INTEGER NOT NULL,
+    model TEXT NOT NULL,
+    indexed_at INTEGER NOT NULL
+);
+
+CREATE TABLE wiki_chunks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    page_id INTEGER NOT NULL REFERENCES wiki_pages(page_id) ON DELETE CASCADE,
+    section TEXT NOT NULL,
+    anchor TEXT NOT NULL,
+    text TEXT NOT NULL,
+    embedding BLOB NOT NULL
+);
+CREATE INDEX wiki_chunks_page ON wiki_chunks (page_id);
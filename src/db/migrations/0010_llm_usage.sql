@@ -0,0 +1,13 @@
+-- Every language model call, for usage reports and per-server budgets.
+-- Calls not made for a server (e.g. the wiki index) have no guild_id.
+CREATE TABLE llm_usage (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    at INTEGER NOT NULL,
+    guild_id INTEGER,
+    user_id INTEGER,
+    feature TEXT NOT NULL,
+    model TEXT NOT NULL,
+    input_tokens INTEGER NOT NULL,
+    output_tokens INTEGER NOT NULL
+);
+CREATE INDEX llm_usage_guild_at ON llm_usage (guild_id, at);
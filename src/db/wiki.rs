@@ -0,0 +1,170 @@
+//! Storage for the wiki search index (see `util::wiki_search`): indexed pages
+//! and their embedded chunks.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use sqlx::Row;
+
+use super::{now, Db};
+use crate::Error;
+
+/// An indexed chunk of a wiki page, with its embedding.
+#[derive(Clone)]
+pub struct WikiChunk {
+    pub title: String,
+    pub section: String,
+    pub anchor: String,
+    pub text: String,
+    pub embedding: Vec<f32>,
+}
+
+/// A chunk of a page to store: section title, section anchor, text and
+/// embedding.
+pub struct NewChunk {
+    pub section: String,
+    pub anchor: String,
+    pub text: String,
+    pub embedding: Vec<f32>,
+}
+
+/// Chunks loaded for an embedding model, kept until the index changes:
+/// decoding every embedding for each question adds up.
+pub(super) type ChunkCache = (String, Arc<Vec<WikiChunk>>);
+
+impl Db {
+    /// Every indexed page as `page id -> (revision, embedding key)`; the key
+    /// names the model and vector length (see `util::wiki_search`).
+    pub async fn wiki_page_versions(&self) -> Result<HashMap<i64, (i64, String)>, Error> {
+        let rows = sqlx::query("SELECT page_id, revision, model FROM wiki_pages").fetch_all(&self.pool).await?;
+        Ok(rows.iter().map(|r| (r.get("page_id"), (r.get("revision"), r.get("model")))).collect())
+    }
+
+    /// Stores a page's chunks in place of those of its earlier revision.
+    pub async fn replace_wiki_page(
+        &self,
+        page_id: i64,
+        title: &str,
+        revision: i64,
+        model: &str,
+        chunks: &[NewChunk],
+    ) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM wiki_chunks WHERE page_id = ?").bind(page_id).execute(&mut *tx).await?;
+        sqlx::query(
+            "INSERT INTO wiki_pages (page_id, title, revision, model, indexed_at) VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT (page_id) DO UPDATE SET title = excluded.title, revision = excluded.revision,
+                 model = excluded.model, indexed_at = excluded.indexed_at",
+        )
+        .bind(page_id)
+        .bind(title)
+        .bind(revision)
+        .bind(model)
+        .bind(now())
+        .execute(&mut *tx)
+        .await?;
+        for chunk in chunks {
+            sqlx::query("INSERT INTO wiki_chunks (page_id, section, anchor, text, embedding) VALUES (?, ?, ?, ?, ?)")
+                .bind(page_id)
+                .bind(&chunk.section)
+                .bind(&chunk.anchor)
+                .bind(&chunk.text)
+                .bind(encode_embedding(&chunk.embedding))
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        *self.wiki_cache.lock().await = None;
+        Ok(())
+    }
+
+    /// Drops pages (and their chunks) from the index.
+    pub async fn delete_wiki_pages(&self, page_ids: &[i64]) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        for page_id in page_ids {
+            sqlx::query("DELETE FROM wiki_chunks WHERE page_id = ?").bind(page_id).execute(&mut *tx).await?;
+            sqlx::query("DELETE FROM wiki_pages WHERE page_id = ?").bind(page_id).execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        *self.wiki_cache.lock().await = None;
+        Ok(())
+    }
+
+    /// Every chunk embedded with `model` (an embedding key); chunks of
+    /// another model can't be compared with its embeddings. Cached until the
+    /// index changes.
+    pub async fn wiki_chunks(&self, model: &str) -> Result<Arc<Vec<WikiChunk>>, Error> {
+        // Held while loading, so an index change can't be overwritten by
+        // chunks read before it.
+        let mut cache = self.wiki_cache.lock().await;
+        if let Some((cached_model, chunks)) = cache.as_ref() {
+            if cached_model == model {
+                return Ok(chunks.clone());
+            }
+        }
+        let rows = sqlx::query(
+            "SELECT p.title, c.section, c.anchor, c.text, c.embedding FROM wiki_chunks c
+             JOIN wiki_pages p ON c.page_id = p.page_id WHERE p.model = ?",
+        )
+        .bind(model)
+        .fetch_all(&self.pool)
+        .await?;
+        let chunks: Arc<Vec<WikiChunk>> = Arc::new(
+            rows.iter()
+                .map(|r| WikiChunk {
+                    title: r.get("title"),
+                    section: r.get("section"),
+                    anchor: r.get("anchor"),
+                    text: r.get("text"),
+                    embedding: decode_embedding(r.get("embedding")),
+                })
+                .collect(),
+        );
+        *cache = Some((model.to_string(), chunks.clone()));
+        Ok(chunks)
+    }
+}
+
+fn encode_embedding(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+fn decode_embedding(bytes: &[u8]) -> Vec<f32> {
+    bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::TempDb;
+
+    #[tokio::test]
+    async fn reindexing_a_page_replaces_its_chunks() {
+        let temp = TempDb::new("wiki");
+        let db = temp.db().await;
+        let chunk = |text: &str, embedding: Vec<f32>| NewChunk {
+            section: "Strategy".to_string(),
+            anchor: "Strategy".to_string(),
+            text: text.to_string(),
+            embedding,
+        };
+
+        db.replace_wiki_page(7, "Ridley", 1, "m", &[chunk("old", vec![1.0]), chunk("older", vec![2.0])])
+            .await
+            .unwrap();
+        assert_eq!(db.wiki_chunks("m").await.unwrap().len(), 2);
+        db.replace_wiki_page(7, "Ridley", 2, "m", &[chunk("new", vec![0.5, -1.25])]).await.unwrap();
+        let chunks = db.wiki_chunks("m").await.unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!((chunks[0].title.as_str(), chunks[0].text.as_str()), ("Ridley", "new"));
+        assert_eq!(chunks[0].embedding, vec![0.5, -1.25]);
+        assert_eq!(db.wiki_page_versions().await.unwrap()[&7], (2, "m".to_string()));
+        assert!(db.wiki_chunks("other").await.unwrap().is_empty());
+
+        db.delete_wiki_pages(&[7]).await.unwrap();
+        assert!(db.wiki_page_versions().await.unwrap().is_empty());
+        assert!(db.wiki_chunks("m").await.unwrap().is_empty());
+
+        db.close().await;
+    }
+}
@@ -0,0 +1,228 @@
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use poise::serenity_prelude::{ConnectionStage, ShardManager};
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::db::Db;
+use crate::tasks::TaskRunner;
+use crate::{metrics, Error};
+
+/// Largest request head we read; the endpoints take no request body.
+const MAX_REQUEST: usize = 8 * 1024;
+
+/// How long the database check may take before it counts as unreachable.
+const DB_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a client may take to send its request head before the
+/// connection is dropped.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What the health endpoints check.
+#[derive(Clone)]
+pub struct Health {
+    pub shard_manager: Arc<ShardManager>,
+    pub db: Db,
+    /// Set once the bot is ready and its background tasks have started.
+    pub tasks: Arc<OnceLock<TaskRunner>>,
+}
+
+/// Serves the optional local HTTP endpoint (`HTTP_ADDR`, e.g. `127.0.0.1:9100`)
+/// until `shutdown` is cancelled:
+///
+/// - `GET /metrics` — Prometheus metrics (see [`metrics`])
+/// - `GET /healthz` — liveness: gateway connected, database reachable and
+///   every background task loop still polling, not stuck in a run, and
+///   succeeding within its expected interval
+/// - `GET /readyz` — readiness: gateway connected and database reachable
+///
+/// The health endpoints answer 200 when every check passes and 503
+/// otherwise, with a JSON body detailing each check.
+pub async fn serve(addr: &str, health: Health, shutdown: CancellationToken) -> Result<(), Error> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("HTTP endpoint listening on {}", listener.local_addr()?);
+    loop {
+        let (stream, peer) = tokio::select! {
+            _ = shutdown.cancelled() => return Ok(()),
+            accepted = listener.accept() => accepted?,
+        };
+        let health = health.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, &health).await {
+                debug!("HTTP request from {} failed: {:?}", peer, e);
+            }
+        });
+    }
+}
+
+/// Spawns [`serve`] when `HTTP_ADDR` is set.
+pub fn start_from_env(health: Health, shutdown: CancellationToken) {
+    let Ok(addr) = std::env::var("HTTP_ADDR") else {
+        return;
+    };
+    tokio::spawn(async move {
+        if let Err(e) = serve(&addr, health, shutdown).await {
+            warn!("HTTP endpoint on {} stopped: {:?}", addr, e);
+        }
+    });
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn text(status: &'static str, body: impl Into<String>) -> Self {
+        Response { status, content_type: "text/plain; charset=utf-8", body: body.into() }
+    }
+
+    fn json(ok: bool, body: serde_json::Value) -> Self {
+        Response {
+            status: if ok { "200 OK" } else { "503 Service Unavailable" },
+            content_type: "application/json",
+            body: body.to_string(),
+        }
+    }
+}
+
+async fn handle(mut stream: TcpStream, health: &Health) -> Result<(), Error> {
+    let Ok(buf) = tokio::time::timeout(READ_TIMEOUT, read_head(&mut stream)).await else {
+        return Err("timed out reading the request".into());
+    };
+    let Some(buf) = buf? else {
+        return Ok(());
+    };
+
+    let head = String::from_utf8_lossy(&buf);
+    let response = match parse_request_line(&head) {
+        Some(("GET", path)) => route(path, health).await,
+        Some(_) => Response::text("405 Method Not Allowed", "method not allowed\n"),
+        None => Response::text("400 Bad Request", "bad request\n"),
+    };
+
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Reads up to the end of the request head; `None` if the client hung up or
+/// sent more than [`MAX_REQUEST`] first.
+async fn read_head(stream: &mut TcpStream) -> Result<Option<Vec<u8>>, Error> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 || buf.len() + n > MAX_REQUEST {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    Ok(Some(buf))
+}
+
+async fn route(path: &str, health: &Health) -> Response {
+    match path {
+        "/metrics" => Response {
+            status: "200 OK",
+            content_type: "text/plain; version=0.0.4; charset=utf-8",
+            body: metrics::render(),
+        },
+        "/healthz" => check(health, true).await,
+        "/readyz" => check(health, false).await,
+        _ => Response::text("404 Not Found", "not found\n"),
+    }
+}
+
+async fn check(health: &Health, include_tasks: bool) -> Response {
+    let (gateway_ok, shards) = check_gateway(&health.shard_manager).await;
+    let (db_ok, db_error) = match tokio::time::timeout(DB_CHECK_TIMEOUT, health.db.ping()).await {
+        Ok(Ok(())) => (true, None),
+        Ok(Err(e)) => (false, Some(e.to_string())),
+        Err(_) => (false, Some("timed out".to_string())),
+    };
+    let mut ok = gateway_ok && db_ok;
+    let mut body = json!({
+        "gateway": { "ok": gateway_ok, "shards": shards },
+        "database": { "ok": db_ok, "error": db_error },
+    });
+
+    if include_tasks {
+        let tasks = match health.tasks.get() {
+            Some(runner) => match runner.health().await {
+                Ok(tasks) => tasks,
+                Err(e) => {
+                    ok = false;
+                    body["tasks_error"] = json!(e.to_string());
+                    Vec::new()
+                }
+            },
+            // Still starting up; the gateway check covers that.
+            None => Vec::new(),
+        };
+        ok &= tasks.iter().all(|t| t.ok);
+        body["tasks"] = tasks
+            .iter()
+            .map(|t| json!({ "name": t.name, "ok": t.ok, "detail": t.detail, "last_error": t.last_error }))
+            .collect();
+    }
+
+    body["status"] = json!(if ok { "ok" } else { "unhealthy" });
+    Response::json(ok, body)
+}
+
+/// The gateway is up when there is at least one shard and all are connected.
+async fn check_gateway(shard_manager: &ShardManager) -> (bool, Vec<serde_json::Value>) {
+    let runners = shard_manager.runners.lock().await;
+    let mut ok = !runners.is_empty();
+    let mut shards = Vec::new();
+    for (id, info) in runners.iter() {
+        let connected = info.stage == ConnectionStage::Connected;
+        ok &= connected;
+        shards.push(json!({
+            "id": id.0,
+            "stage": info.stage.to_string(),
+            "latency_ms": info.latency.map(|l| l.as_millis() as u64),
+        }));
+    }
+    (ok, shards)
+}
+
+/// Returns `(method, path)` from the request line, with any query string
+/// stripped from the path.
+fn parse_request_line(head: &str) -> Option<(&str, &str)> {
+    let line = head.lines().next()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?;
+    let target = parts.next()?;
+    parts.next().filter(|v| v.starts_with("HTTP/"))?;
+    let path = target.split('?').next().unwrap_or(target);
+    Some((method, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_request_line;
+
+    #[test]
+    fn parses_request_line() {
+        assert_eq!(
+            parse_request_line("GET /metrics?x=1 HTTP/1.1\r\nHost: a\r\n\r\n"),
+            Some(("GET", "/metrics"))
+        );
+        assert_eq!(parse_request_line("POST /metrics HTTP/1.0\r\n\r\n"), Some(("POST", "/metrics")));
+        assert_eq!(parse_request_line("garbage\r\n\r\n"), None);
+    }
+}
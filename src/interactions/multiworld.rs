@@ -13,7 +13,7 @@ use serenity::model::prelude::*;
 use std::collections::HashMap;
 use maplit::hashmap;
 use tracing::{error, debug};
-use crate::{util::slugid, Data};
+use crate::{api::SendMetered, util::slugid, Data};
 
 
 #[derive(Clone)]
@@ -240,7 +240,7 @@ pub async fn interaction_create_multiworld(
                     );
 
                     let url = "https://beta.samus.link/api/randomizers/smz3/generate";
-                    let res = reqwest::Client::new().post(url).json(&options).send().await;
+                    let res = reqwest::Client::new().post(url).json(&options).send_metered().await;
 
                     if let Ok(response) = res {
                         if let Ok(seed) = response.json::<serde_json::Value>().await {
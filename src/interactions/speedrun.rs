@@ -10,7 +10,7 @@ use crate::tasks::speedrun::{
     dismiss_review, enter_review, outcome_verdict, resolve_demo, resolve_pending, verdict_embed,
     ReviewResult, RunOutcome,
 };
-use crate::{Data, Error};
+use crate::{permissions, Data, Error};
 
 const SCOPE: &str = "speedrun";
 
@@ -314,14 +314,16 @@ async fn edit_untracked(
     true
 }
 
-/// Review buttons may be used by administrators, or by members holding any of
-/// the roles configured in this server's `speedrun.mod_role` (a comma-separated
-/// list of role ids).
+/// Review buttons may be used by administrators, members granted
+/// `speedrun.review` (see the `permissions` command), or members holding any
+/// of the roles configured in this server's `speedrun.mod_role` (a
+/// comma-separated list of role ids).
 async fn is_reviewer(data: &Data, member: Option<&Member>) -> Result<bool, Error> {
     let Some(member) = member else {
         return Ok(false);
     };
-    if member.permissions.is_some_and(|p| p.administrator()) {
+    let permissions = member.permissions.unwrap_or_default();
+    if permissions::member_has(&data.db, member, permissions, permissions::SPEEDRUN_REVIEW).await? {
         return Ok(true);
     }
     if let Some(setting) = data.db.get_guild_setting(member.guild_id.get(), SCOPE, "mod_role").await? {
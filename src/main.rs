@@ -1,9 +1,9 @@
-use std::{collections::HashMap, env, sync::Arc};
-use tokio::sync::{Mutex, RwLock};
+use std::{collections::HashMap, env, sync::{Arc, OnceLock}, time::{Duration, Instant}};
+use tokio::sync::RwLock;
 use serenity::model::prelude::*;
 use poise::serenity_prelude as serenity;
 
-use tracing::{error, info, debug};
+use tracing::{error, info, debug, warn};
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
 
 mod util;
@@ -12,12 +12,24 @@ mod api;
 mod interactions;
 mod db;
 mod tasks;
+mod shutdown;
+mod metrics;
+mod http;
+mod permissions;
 
-use crate::util::cobe::Cobe;
+use crate::util::cobe::Brains;
+
+/// How long shutdown waits for in-flight task runs and review actions.
+const SHUTDOWN_DEADLINE: Duration = Duration::from_secs(20);
 
 pub struct Data {
-    pub cobe: Arc<Mutex<Cobe>>,
+    pub cobe: Brains,
     pub db: db::Db,
+    pub tasks: tasks::TaskRunner,
+    pub shutdown: shutdown::Shutdown,
+    /// The language model backend, `None` when `LLM_MODEL` is unset or the
+    /// configuration is broken.
+    pub llm: Option<Arc<api::llm::LlmClient>>,
     pub multiworld_sessions: Arc<RwLock<HashMap<MessageId, interactions::multiworld::MultiworldSession>>>,
     pub multiworld_settings: Arc<RwLock<HashMap<MessageId, MessageId>>>,
 }
@@ -30,6 +42,7 @@ async fn on_error(error: poise::FrameworkError<'_, Data, Error>) {
         poise::FrameworkError::Setup { error, .. } => panic!("Failed to start bot: {:?}", error),
         poise::FrameworkError::Command { error, ctx, .. } => {
             error!("Error in command '{}': {:?}", ctx.command().name, error);
+            record_command(ctx, false).await;
         }
         error => {
             if let Err(e) = poise::builtins::on_error(error).await {
@@ -39,6 +52,14 @@ async fn on_error(error: poise::FrameworkError<'_, Data, Error>) {
     }
 }
 
+/// Records a finished command's latency, timed from the `pre_command` hook.
+async fn record_command(ctx: Context<'_>, ok: bool) {
+    let started = ctx.invocation_data::<Instant>().await.map(|started| *started);
+    if let Some(started) = started {
+        metrics::command_finished(&ctx.command().qualified_name, started.elapsed(), ok);
+    }
+}
+
 async fn event_handler(
     ctx: &serenity::Context,
     event: &serenity::FullEvent,
@@ -50,6 +71,11 @@ async fn event_handler(
             info!("Connected as {}", data_about_bot.user.name);
         }
         serenity::FullEvent::InteractionCreate { interaction } => {
+            // Review buttons act on speedrun.com and then record the result;
+            // shutdown waits for them instead of cutting them off.
+            let Some(_guard) = data.shutdown.guard().await else {
+                return Ok(());
+            };
             interactions::multiworld::interaction_create_multiworld(ctx, interaction, data).await?;
             interactions::speedrun::interaction_create_speedrun(ctx, interaction, data).await?;
         }
@@ -70,11 +96,60 @@ async fn main() {
     let subscriber = FmtSubscriber::builder().with_env_filter(EnvFilter::from_default_env()).finish();
     tracing::subscriber::set_global_default(subscriber).expect("Failed to start the logger");
 
+    let db_path = env::var("DATABASE_PATH").unwrap_or_else(|_| "shaktool.db".to_string());
+
+    // `shaktool-rs restore <backup file>`: offline restore, with the bot stopped.
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("restore") {
+        let Some(file) = args.get(1) else {
+            eprintln!("Usage: shaktool-rs restore <backup file>");
+            std::process::exit(2);
+        };
+        match db::backup::restore(std::path::Path::new(file), std::path::Path::new(&db_path)).await {
+            Ok(version) => println!("Restored {} (schema v{}) to {}", file, version, db_path),
+            Err(e) => {
+                eprintln!("Restore failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // `shaktool-rs import-brain [cobe brain]`: one-off import of the Python
+    // cobe brain into the shared native one, before anything is learned.
+    if args.first().map(String::as_str) == Some("import-brain") {
+        let file = args.get(1).map(String::as_str).unwrap_or("bot.brain");
+        let db = db::Db::connect(&db_path).await.expect("Failed to open the database");
+        match db.import_cobe_brain(std::path::Path::new(file)).await {
+            Ok(stats) => println!(
+                "Imported {} ({} tokens, {} contexts, {} edges) into {}",
+                file, stats.tokens, stats.nodes, stats.edges, db_path
+            ),
+            Err(e) => {
+                eprintln!("Import failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        db.close().await;
+        return;
+    }
+
     let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
     let prefix = env::var("COMMAND_PREFIX").unwrap_or_else(|_| "%".to_string());
-    let db_path = env::var("DATABASE_PATH").unwrap_or_else(|_| "shaktool.db".to_string());
 
     let db = db::Db::connect(&db_path).await.expect("Failed to open the database");
+    let shutdown = shutdown::Shutdown::new();
+    let started_tasks = Arc::new(OnceLock::new());
+    let llm = match api::llm::LlmClient::from_env() {
+        Ok(client) => client.map(Arc::new),
+        Err(e) => {
+            warn!("LLM backend misconfigured, language model features are off: {}", e);
+            None
+        }
+    };
+    let setup_db = db.clone();
+    let setup_shutdown = shutdown.clone();
+    let setup_tasks = started_tasks.clone();
 
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
@@ -83,6 +158,7 @@ async fn main() {
                 commands::general::version(),
                 commands::general::strat(),
                 commands::general::wiki(),
+                commands::ask::ask(),
                 commands::general::card(),
                 commands::time::time(),
                 commands::leaderboard::top(),
@@ -93,12 +169,21 @@ async fn main() {
                 commands::smz3::smz3(),
                 commands::config::config(),
                 commands::speedrun::speedrun(),
+                commands::tasks::tasks(),
+                commands::db::db(),
+                commands::permissions::permissions(),
+                commands::brain::brain(),
+                commands::brain::chatter(),
+                commands::brain::forget(),
+                commands::llm::llm(),
             ],
             prefix_options: poise::PrefixFrameworkOptions {
                 prefix: Some(prefix),
                 ..Default::default()
             },
             on_error: |error| Box::pin(on_error(error)),
+            pre_command: |ctx| Box::pin(async move { ctx.set_invocation_data(Instant::now()).await }),
+            post_command: |ctx| Box::pin(record_command(ctx, true)),
             event_handler: |ctx, event, framework, data| {
                 Box::pin(event_handler(ctx, event, framework, data))
             },
@@ -106,6 +191,10 @@ async fn main() {
         })
         .setup(move |ctx, _ready, framework| {
             let ctx = ctx.clone();
+            let db = setup_db;
+            let shutdown = setup_shutdown;
+            let started_tasks = setup_tasks;
+            let llm = llm.clone();
             Box::pin(async move {
                 let mut slash_commands =
                     poise::builtins::create_application_commands(&framework.options().commands);
@@ -124,10 +213,14 @@ async fn main() {
                         info!("Registered global slash commands");
                     }
                 }
-                tasks::start(ctx, db.clone());
+                let tasks = tasks::start(ctx, db.clone(), shutdown.clone(), llm.clone());
+                let _ = started_tasks.set(tasks.clone());
                 Ok(Data {
-                    cobe: Arc::new(Mutex::new(Cobe::new())),
+                    cobe: Brains::new(db.clone()),
                     db,
+                    tasks,
+                    shutdown,
+                    llm,
                     multiworld_sessions: Arc::new(RwLock::new(HashMap::new())),
                     multiworld_settings: Arc::new(RwLock::new(HashMap::new())),
                 })
@@ -141,13 +234,27 @@ async fn main() {
         .await
         .expect("Error creating client");
 
+    let health = http::Health {
+        shard_manager: client.shard_manager.clone(),
+        db: db.clone(),
+        tasks: started_tasks,
+    };
+    http::start_from_env(health, shutdown.token());
+
     let shard_manager = client.shard_manager.clone();
     tokio::spawn(async move {
-        tokio::signal::ctrl_c().await.expect("Could not register ctrl+c handler");
+        shutdown::signal().await;
+        info!("Shutting down");
         shard_manager.shutdown_all().await;
     });
 
     if let Err(why) = client.start().await {
         error!("Client error: {:?}", why);
     }
+
+    if !shutdown.drain(SHUTDOWN_DEADLINE).await {
+        warn!("In-flight work didn't finish within {:?}; exiting anyway", SHUTDOWN_DEADLINE);
+    }
+    db.close().await;
+    info!("Shutdown complete");
 }
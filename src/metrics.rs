@@ -0,0 +1,240 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+/// Process-wide metrics, rendered in the Prometheus text format by the
+/// `/metrics` endpoint (see [`crate::http`]). Recording is always on and
+/// cheap; the endpoint only exposes it.
+static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::default);
+
+/// Latency buckets (seconds) for commands, task runs and API calls.
+const LATENCY_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+/// Buckets for judge suspicion scores (0-100).
+const SCORE_BUCKETS: &[f64] = &[10.0, 20.0, 30.0, 40.0, 50.0, 60.0, 70.0, 80.0, 90.0, 100.0];
+
+pub fn command_finished(command: &str, latency: Duration, ok: bool) {
+    let result = if ok { "ok" } else { "error" };
+    REGISTRY.inc(
+        "shaktool_commands_total",
+        "Command invocations by command and result",
+        &[("command", command), ("result", result)],
+    );
+    REGISTRY.observe(
+        "shaktool_command_duration_seconds",
+        "Command latency from invocation to completion",
+        LATENCY_BUCKETS,
+        &[("command", command)],
+        latency.as_secs_f64(),
+    );
+}
+
+pub fn task_finished(task: &str, duration: Duration, ok: bool) {
+    let result = if ok { "ok" } else { "error" };
+    REGISTRY.inc(
+        "shaktool_task_runs_total",
+        "Background task runs by task and result",
+        &[("task", task), ("result", result)],
+    );
+    REGISTRY.observe(
+        "shaktool_task_duration_seconds",
+        "Background task run duration",
+        LATENCY_BUCKETS,
+        &[("task", task)],
+        duration.as_secs_f64(),
+    );
+}
+
+/// Outcome of one outgoing API request: `ok`, `http_error` (non-2xx status)
+/// or `failed` (no response at all).
+pub fn api_request(host: &str, outcome: &str) {
+    REGISTRY.inc(
+        "shaktool_api_requests_total",
+        "External API requests by host and outcome",
+        &[("host", host), ("outcome", outcome)],
+    );
+}
+
+pub fn speedrun_queue_size(game: &str, size: usize) {
+    REGISTRY.set(
+        "shaktool_speedrun_queue_size",
+        "Runs in the speedrun.com verification queue per game",
+        &[("game", game)],
+        size as f64,
+    );
+}
+
+pub fn judge_score(game: &str, score: u32) {
+    REGISTRY.observe(
+        "shaktool_judge_score",
+        "Suspicion scores assigned by the judge per game",
+        SCORE_BUCKETS,
+        &[("game", game)],
+        score as f64,
+    );
+}
+
+/// Renders every recorded metric in the Prometheus text exposition format.
+pub fn render() -> String {
+    REGISTRY.render()
+}
+
+type Labels = Vec<(String, String)>;
+
+#[derive(Default)]
+struct Registry {
+    families: Mutex<BTreeMap<&'static str, Family>>,
+}
+
+struct Family {
+    help: &'static str,
+    values: Values,
+}
+
+enum Values {
+    Counter(BTreeMap<Labels, f64>),
+    Gauge(BTreeMap<Labels, f64>),
+    Histogram(&'static [f64], BTreeMap<Labels, Histogram>),
+}
+
+#[derive(Clone)]
+struct Histogram {
+    /// Cumulative count per bucket upper bound, as Prometheus expects.
+    buckets: Vec<u64>,
+    count: u64,
+    sum: f64,
+}
+
+fn labels(labels: &[(&str, &str)]) -> Labels {
+    labels.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+}
+
+impl Registry {
+    fn inc(&self, name: &'static str, help: &'static str, label_pairs: &[(&str, &str)]) {
+        let mut families = self.families.lock().unwrap();
+        let family = families
+            .entry(name)
+            .or_insert_with(|| Family { help, values: Values::Counter(BTreeMap::new()) });
+        if let Values::Counter(values) = &mut family.values {
+            *values.entry(labels(label_pairs)).or_default() += 1.0;
+        }
+    }
+
+    fn set(&self, name: &'static str, help: &'static str, label_pairs: &[(&str, &str)], value: f64) {
+        let mut families = self.families.lock().unwrap();
+        let family = families
+            .entry(name)
+            .or_insert_with(|| Family { help, values: Values::Gauge(BTreeMap::new()) });
+        if let Values::Gauge(values) = &mut family.values {
+            values.insert(labels(label_pairs), value);
+        }
+    }
+
+    fn observe(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        bounds: &'static [f64],
+        label_pairs: &[(&str, &str)],
+        value: f64,
+    ) {
+        let mut families = self.families.lock().unwrap();
+        let family = families
+            .entry(name)
+            .or_insert_with(|| Family { help, values: Values::Histogram(bounds, BTreeMap::new()) });
+        if let Values::Histogram(bounds, values) = &mut family.values {
+            let histogram = values.entry(labels(label_pairs)).or_insert_with(|| Histogram {
+                buckets: vec![0; bounds.len()],
+                count: 0,
+                sum: 0.0,
+            });
+            for (bucket, bound) in histogram.buckets.iter_mut().zip(bounds.iter()) {
+                if value <= *bound {
+                    *bucket += 1;
+                }
+            }
+            histogram.count += 1;
+            histogram.sum += value;
+        }
+    }
+
+    fn render(&self) -> String {
+        let families = self.families.lock().unwrap();
+        let mut out = String::new();
+        for (name, family) in families.iter() {
+            let kind = match family.values {
+                Values::Counter(_) => "counter",
+                Values::Gauge(_) => "gauge",
+                Values::Histogram(..) => "histogram",
+            };
+            let _ = writeln!(out, "# HELP {} {}", name, family.help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            match &family.values {
+                Values::Counter(values) | Values::Gauge(values) => {
+                    for (labels, value) in values {
+                        let _ = writeln!(out, "{}{} {}", name, format_labels(labels, None), value);
+                    }
+                }
+                Values::Histogram(bounds, values) => {
+                    for (labels, histogram) in values {
+                        for (bound, count) in bounds.iter().zip(&histogram.buckets) {
+                            let le = bound.to_string();
+                            let _ = writeln!(out, "{}_bucket{} {}", name, format_labels(labels, Some(&le)), count);
+                        }
+                        let _ = writeln!(
+                            out,
+                            "{}_bucket{} {}",
+                            name,
+                            format_labels(labels, Some("+Inf")),
+                            histogram.count
+                        );
+                        let _ = writeln!(out, "{}_sum{} {}", name, format_labels(labels, None), histogram.sum);
+                        let _ = writeln!(out, "{}_count{} {}", name, format_labels(labels, None), histogram.count);
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+fn format_labels(labels: &Labels, le: Option<&str>) -> String {
+    let mut pairs: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, escape_label(v)))
+        .collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{}\"", le));
+    }
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_counters_gauges_and_histograms() {
+        let registry = Registry::default();
+        registry.inc("test_total", "A counter", &[("command", "quad")]);
+        registry.inc("test_total", "A counter", &[("command", "quad")]);
+        registry.set("test_gauge", "A gauge", &[("game", "say \"hi\"")], 3.0);
+        registry.observe("test_seconds", "A histogram", &[1.0, 5.0], &[], 2.0);
+
+        let text = registry.render();
+        assert!(text.contains("# TYPE test_total counter\ntest_total{command=\"quad\"} 2\n"));
+        assert!(text.contains("test_gauge{game=\"say \\\"hi\\\"\"} 3\n"));
+        assert!(text.contains("test_seconds_bucket{le=\"1\"} 0\n"));
+        assert!(text.contains("test_seconds_bucket{le=\"5\"} 1\n"));
+        assert!(text.contains("test_seconds_bucket{le=\"+Inf\"} 1\n"));
+        assert!(text.contains("test_seconds_sum 2\ntest_seconds_count 1\n"));
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::HashSet;
+
+use poise::serenity_prelude as serenity;
+
+use crate::db::Db;
+use crate::{Context, Error};
+
+/// A group of commands a server can grant to roles or users with the
+/// `permissions` command. Administrators can always use every group.
+pub struct PermissionGroup {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+pub const CONFIG_SPEEDRUN: &str = "config.speedrun";
+pub const CONFIG_QUAD: &str = "config.quad";
+pub const CONFIG_COBE: &str = "config.cobe";
+pub const CONFIG_LLM: &str = "config.llm";
+pub const SPEEDRUN_DEMO: &str = "speedrun.demo";
+pub const SPEEDRUN_DEBUG: &str = "speedrun.debug";
+pub const SPEEDRUN_REVIEW: &str = "speedrun.review";
+
+/// Why a member can't change a global setting.
+pub const GLOBAL_DENIED: &str = "Global settings affect every server, so only bot owners can change them.";
+
+/// Every grantable group. `config.<scope>` groups cover the `config`
+/// subcommands for settings in that scope, so each settings scope needs one.
+/// They only cover changing this server's settings: global settings affect
+/// every server, so only bot owners change them.
+pub const GROUPS: &[PermissionGroup] = &[
+    PermissionGroup {
+        name: CONFIG_SPEEDRUN,
+        description: "View and change this server's `speedrun` settings",
+    },
+    PermissionGroup {
+        name: CONFIG_QUAD,
+        description: "Quad site management: view and change the `quad` settings",
+    },
+    PermissionGroup {
+        name: CONFIG_COBE,
+        description: "Chatter brain controls: view and change the `cobe` settings",
+    },
+    PermissionGroup {
+        name: CONFIG_LLM,
+        description: "Language model budgets: view and change the `llm` settings",
+    },
+    PermissionGroup {
+        name: SPEEDRUN_DEMO,
+        description: "`speedrun demo` and `speedrun showcase`",
+    },
+    PermissionGroup {
+        name: SPEEDRUN_DEBUG,
+        description: "`speedrun debug`",
+    },
+    PermissionGroup {
+        name: SPEEDRUN_REVIEW,
+        description: "`speedrun review` and the review buttons (as does the `speedrun.mod_role` setting)",
+    },
+];
+
+pub fn find_group(name: &str) -> Option<&'static PermissionGroup> {
+    GROUPS.iter().find(|g| g.name == name)
+}
+
+/// The group covering `config` for a settings scope.
+pub fn config_group(scope: &str) -> String {
+    format!("config.{}", scope)
+}
+
+/// The groups a member may use.
+pub enum Grants {
+    /// Administrators may use every group.
+    All,
+    Granted(HashSet<String>),
+}
+
+impl Grants {
+    pub fn allows(&self, permission: &str) -> bool {
+        match self {
+            Grants::All => true,
+            Grants::Granted(groups) => groups.contains(permission),
+        }
+    }
+}
+
+/// Whether a member is an administrator or has been granted `permission`.
+pub async fn member_has(
+    db: &Db,
+    member: &serenity::Member,
+    permissions: serenity::Permissions,
+    permission: &str,
+) -> Result<bool, Error> {
+    if permissions.administrator() {
+        return Ok(true);
+    }
+    let roles: Vec<u64> = member.roles.iter().map(|r| r.get()).collect();
+    db.has_permission(member.guild_id.get(), permission, member.user.id.get(), &roles).await
+}
+
+/// Whether the invoking member may use `permission`, without replying.
+pub async fn allowed(ctx: Context<'_>, permission: &str) -> Result<bool, Error> {
+    let Some(member) = ctx.author_member().await else {
+        return Ok(false);
+    };
+    member_has(&ctx.data().db, &member, member_permissions(ctx, &member), permission).await
+}
+
+/// Every group the invoking member may use, read with one query. For
+/// checking many groups at once, e.g. to list what they may manage.
+pub async fn grants(ctx: Context<'_>) -> Result<Grants, Error> {
+    let Some(member) = ctx.author_member().await else {
+        return Ok(Grants::Granted(HashSet::new()));
+    };
+    if member_permissions(ctx, &member).administrator() {
+        return Ok(Grants::All);
+    }
+    let roles: Vec<u64> = member.roles.iter().map(|r| r.get()).collect();
+    let groups = ctx.data().db.granted_permissions(member.guild_id.get(), member.user.id.get(), &roles).await?;
+    Ok(Grants::Granted(groups))
+}
+
+/// Whether the author is one of the bot's owners.
+pub fn is_owner(ctx: Context<'_>) -> bool {
+    ctx.framework().options().owners.contains(&ctx.author().id)
+}
+
+/// The invoking member's server-wide permissions.
+fn member_permissions(ctx: Context<'_>, member: &serenity::Member) -> serenity::Permissions {
+    match member.permissions {
+        Some(permissions) => permissions,
+        None => match ctx.guild() {
+            Some(guild) => guild.member_permissions(member),
+            None => serenity::Permissions::empty(),
+        },
+    }
+}
+
+/// Whether the author is a bot owner or an administrator of this server,
+/// replying when not. Entry check for commands whose subcommands are for bot
+/// owners only, so owners can use them anywhere.
+pub async fn owner_or_admin(ctx: Context<'_>) -> Result<bool, Error> {
+    if is_owner(ctx) {
+        return Ok(true);
+    }
+    if let Some(member) = ctx.author_member().await {
+        if member_permissions(ctx, &member).administrator() {
+            return Ok(true);
+        }
+    }
+    ctx.send(
+        poise::CreateReply::default()
+            .content("You need Administrator to use this.")
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(false)
+}
+
+/// Like [`allowed`], but tells the member what they're missing when denied.
+pub async fn require(ctx: Context<'_>, permission: &str) -> Result<bool, Error> {
+    if allowed(ctx, permission).await? {
+        return Ok(true);
+    }
+    deny(ctx, &format!("`{}`", permission)).await?;
+    Ok(false)
+}
+
+/// Whether the author may change a `config` setting of `scope`, replying
+/// when not: global settings need a bot owner, this server's settings the
+/// scope's `config.<scope>` group.
+pub async fn require_config_write(ctx: Context<'_>, scope: &str, global: bool) -> Result<bool, Error> {
+    if !global {
+        return require(ctx, &config_group(scope)).await;
+    }
+    if is_owner(ctx) {
+        return Ok(true);
+    }
+    ctx.send(
+        poise::CreateReply::default()
+            .content(GLOBAL_DENIED)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(false)
+}
+
+/// Whether the invoking member may use any of the groups starting with
+/// `prefix` (e.g. `config.`), replying when denied. Entry check for parent
+/// commands whose subcommands check a specific group.
+async fn require_any(ctx: Context<'_>, prefix: &str) -> Result<bool, Error> {
+    let grants = grants(ctx).await?;
+    if GROUPS.iter().any(|g| g.name.starts_with(prefix) && grants.allows(g.name)) {
+        return Ok(true);
+    }
+    deny(ctx, &format!("a `{}*` permission", prefix)).await?;
+    Ok(false)
+}
+
+async fn deny(ctx: Context<'_>, what: &str) -> Result<(), Error> {
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!("You need {} (or Administrator) to use this. See `permissions`.", what))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+// Poise `check`s, shared by the commands each group covers.
+
+pub async fn config_any(ctx: Context<'_>) -> Result<bool, Error> {
+    require_any(ctx, "config.").await
+}
+
+pub async fn speedrun_any(ctx: Context<'_>) -> Result<bool, Error> {
+    require_any(ctx, "speedrun.").await
+}
+
+pub async fn speedrun_demo(ctx: Context<'_>) -> Result<bool, Error> {
+    require(ctx, SPEEDRUN_DEMO).await
+}
+
+pub async fn speedrun_debug(ctx: Context<'_>) -> Result<bool, Error> {
+    require(ctx, SPEEDRUN_DEBUG).await
+}
+
+pub async fn speedrun_review(ctx: Context<'_>) -> Result<bool, Error> {
+    require(ctx, SPEEDRUN_REVIEW).await
+}
@@ -0,0 +1,93 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::{OwnedRwLockReadGuard, RwLock};
+use tokio_util::sync::CancellationToken;
+
+/// Coordinates a graceful shutdown: signals background tasks to stop and
+/// waits for in-flight work (task runs, review button handlers) to finish,
+/// so nothing is cut off between acting on speedrun.com and recording it.
+///
+/// Work that must not be interrupted holds a [`guard`](Shutdown::guard) for
+/// its duration; [`drain`](Shutdown::drain) waits for every guard to drop.
+#[derive(Clone)]
+pub struct Shutdown {
+    token: CancellationToken,
+    in_flight: Arc<RwLock<()>>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Shutdown {
+            token: CancellationToken::new(),
+            in_flight: Arc::new(RwLock::new(())),
+        }
+    }
+
+    /// Cancelled once shutdown starts. Long-running work should check it
+    /// between units of work and stop early.
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Marks a unit of work as in flight until the guard drops. `None` once
+    /// shutdown has started, in which case the work should not begin.
+    pub async fn guard(&self) -> Option<OwnedRwLockReadGuard<()>> {
+        if self.token.is_cancelled() {
+            return None;
+        }
+        let guard = self.in_flight.clone().read_owned().await;
+        (!self.token.is_cancelled()).then_some(guard)
+    }
+
+    /// Starts shutdown and waits up to `deadline` for in-flight work to
+    /// finish. Returns `false` if the deadline passed first.
+    pub async fn drain(&self, deadline: Duration) -> bool {
+        self.token.cancel();
+        tokio::time::timeout(deadline, self.in_flight.write()).await.is_ok()
+    }
+}
+
+/// Resolves on ctrl-c, or on SIGTERM (what container runtimes send) on Unix.
+pub async fn signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate()).expect("Could not register SIGTERM handler");
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result.expect("Could not register ctrl+c handler"),
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.expect("Could not register ctrl+c handler");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn drain_waits_for_in_flight_work() {
+        let shutdown = Shutdown::new();
+        let finished = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let guard = shutdown.guard().await.unwrap();
+        let worker_finished = finished.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            worker_finished.store(true, std::sync::atomic::Ordering::SeqCst);
+            drop(guard);
+        });
+        assert!(shutdown.drain(Duration::from_secs(5)).await);
+        assert!(finished.load(std::sync::atomic::Ordering::SeqCst));
+        assert!(shutdown.guard().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn drain_gives_up_at_deadline() {
+        let shutdown = Shutdown::new();
+        let _guard = shutdown.guard().await.unwrap();
+        assert!(!shutdown.drain(Duration::from_millis(20)).await);
+        assert!(shutdown.token().is_cancelled());
+    }
+}
@@ -0,0 +1,53 @@
+use async_trait::async_trait;
+use tracing::{info, warn};
+
+use super::schedule::Cron;
+use super::{Schedule, Task, TaskContext};
+use crate::db::backup::{self, BackupConfig};
+use crate::Error;
+
+/// Backs up the database to `BACKUP_DIR` on the cron schedule in
+/// `BACKUP_SCHEDULE` (default daily at 03:00 UTC). Only registered when
+/// backups are configured.
+pub struct DatabaseBackup {
+    config: BackupConfig,
+    schedule: Cron,
+}
+
+const DEFAULT_SCHEDULE: &str = "0 3 * * *";
+
+impl DatabaseBackup {
+    pub fn new(config: BackupConfig) -> Self {
+        let schedule = match std::env::var("BACKUP_SCHEDULE") {
+            Ok(value) => Cron::parse(&value).unwrap_or_else(|e| {
+                warn!("Ignoring BACKUP_SCHEDULE: {}", e);
+                Cron::parse(DEFAULT_SCHEDULE).expect("valid cron expression")
+            }),
+            Err(_) => Cron::parse(DEFAULT_SCHEDULE).expect("valid cron expression"),
+        };
+        DatabaseBackup { config, schedule }
+    }
+}
+
+#[async_trait]
+impl Task for DatabaseBackup {
+    fn name(&self) -> &'static str {
+        "db_backup"
+    }
+
+    fn schedule(&self) -> Schedule {
+        Schedule::Cron(self.schedule.clone())
+    }
+
+    async fn run(&self, task_ctx: &TaskContext) -> Result<(), Error> {
+        let backup = backup::create(&task_ctx.db, &self.config).await?;
+        info!(
+            "Database backed up to {} ({} bytes, schema v{}, {} old backups pruned)",
+            backup.path.display(),
+            backup.size,
+            backup.version,
+            backup.pruned
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,31 @@
+use async_trait::async_trait;
+use tracing::info;
+
+use super::schedule::Cron;
+use super::{speedrun, Schedule, Task, TaskContext};
+use crate::Error;
+
+/// Nightly housekeeping of the `task_state` table: deletes expired entries
+/// (see [`Db::set_task_state`](crate::db::Db::set_task_state)) and tracked
+/// speedrun runs that can no longer be resolved.
+pub struct StateCleanup;
+
+#[async_trait]
+impl Task for StateCleanup {
+    fn name(&self) -> &'static str {
+        "state_cleanup"
+    }
+
+    fn schedule(&self) -> Schedule {
+        Schedule::Cron(Cron::parse("30 4 * * *").expect("valid cron expression"))
+    }
+
+    async fn run(&self, task_ctx: &TaskContext) -> Result<(), Error> {
+        let expired = task_ctx.db.purge_expired_task_state().await?;
+        let orphaned = speedrun::collect_garbage(&task_ctx.ctx, &task_ctx.db).await?;
+        if expired > 0 || orphaned > 0 {
+            info!("State cleanup: removed {} expired and {} orphaned entries", expired, orphaned);
+        }
+        Ok(())
+    }
+}
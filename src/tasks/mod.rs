@@ -1,53 +1,509 @@
-use std::time::Duration;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use poise::serenity_prelude as serenity;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
 use tracing::{info, warn};
 
+use crate::api::llm::LlmClient;
 use crate::db::Db;
+use crate::metrics;
+use crate::shutdown::Shutdown;
 use crate::Error;
 
+mod backup;
+mod cleanup;
+pub mod schedule;
 pub mod speedrun;
+mod wiki_index;
+
+pub use schedule::Schedule;
+use schedule::Cron;
+
+/// Task state namespace for the runner's own persistence (paused tasks,
+/// next run times of cron and per-server schedules).
+const RUNNER_STATE: &str = "task_runner";
+
+/// How often cron and per-server schedules are checked for due runs.
+const CRON_POLL: Duration = Duration::from_secs(30);
+
+/// Longest a run may take before its task counts as hung.
+const MAX_RUN: Duration = Duration::from_secs(60 * 60);
+
+/// Slack on top of twice a task's interval before a task without a
+/// successful run counts as failing.
+const SUCCESS_GRACE: Duration = Duration::from_secs(10 * 60);
+
+/// Scheduled times looked at to find a cron schedule's longest gap.
+const CRON_GAPS: usize = 8;
 
 /// Shared context handed to every background task run, providing access to
 /// Discord (via the serenity context) and the persistent database.
 pub struct TaskContext {
     pub ctx: serenity::Context,
     pub db: Db,
+    /// The server this run is for, with [`Schedule::PerGuild`]; `None` for
+    /// bot-wide schedules.
+    #[allow(dead_code)] // No per-server scheduled task registered yet.
+    pub guild_id: Option<u64>,
+    /// Cancelled when the bot shuts down. The current run is allowed to
+    /// finish, but a run working through many items should check this
+    /// between items and return early.
+    pub shutdown: CancellationToken,
 }
 
-/// A background task that runs on a fixed interval.
+/// A background task that runs on a [`Schedule`].
 ///
 /// To add a new task: implement this trait, then register the task in [`tasks()`].
-/// The first run happens immediately on startup, then every `interval()` after that.
 /// Errors are logged and the task keeps running on its schedule.
 #[async_trait]
 pub trait Task: Send + Sync + 'static {
     fn name(&self) -> &'static str;
-    fn interval(&self) -> Duration;
+    fn schedule(&self) -> Schedule;
     async fn run(&self, task_ctx: &TaskContext) -> Result<(), Error>;
+
+    /// Whether to run once right after startup instead of waiting for the
+    /// schedule, say because what it maintains doesn't exist yet.
+    async fn run_at_startup(&self, _db: &Db) -> Result<bool, Error> {
+        Ok(false)
+    }
 }
 
-fn tasks() -> Vec<Box<dyn Task>> {
-    vec![
+fn tasks(llm: Option<Arc<LlmClient>>) -> Vec<Box<dyn Task>> {
+    let mut tasks: Vec<Box<dyn Task>> = vec![
         Box::new(speedrun::SpeedrunMonitor::new()),
-    ]
+        Box::new(cleanup::StateCleanup),
+    ];
+    if let Some(config) = crate::db::backup::BackupConfig::from_env() {
+        tasks.push(Box::new(backup::DatabaseBackup::new(config)));
+    }
+    if let Some(client) = llm.filter(|client| client.config().embedding_model.is_some()) {
+        tasks.push(Box::new(wiki_index::WikiIndex::new(client)));
+    }
+    tasks
+}
+
+/// What the runner knows about a task's recent runs. Kept in memory only, so
+/// it starts empty after a restart.
+#[derive(Clone, Default)]
+pub struct TaskStatus {
+    pub last_started: Option<DateTime<Utc>>,
+    pub last_finished: Option<DateTime<Utc>>,
+    pub last_duration: Option<Duration>,
+    pub last_success: Option<DateTime<Utc>>,
+    /// Error of the most recent run; cleared by the next successful run.
+    pub last_error: Option<String>,
+    pub running: bool,
+    pub runs: u64,
+    pub failures: u64,
+}
+
+struct TaskEntry {
+    name: &'static str,
+    schedule: Schedule,
+    status: RwLock<TaskStatus>,
+    /// Wakes the task loop for an immediate, out-of-schedule run.
+    trigger: Notify,
+    /// When the task loop last woke up, whether or not a run was due.
+    last_tick: RwLock<Instant>,
+    /// When the task loop started; stands in for the last success until
+    /// there is one.
+    started: DateTime<Utc>,
+}
+
+/// Whether a task's loop is alive and its runs succeed, as reported by the
+/// health endpoint.
+pub struct TaskHealth {
+    pub name: &'static str,
+    pub ok: bool,
+    pub detail: String,
+    /// Error of the most recent run. A single failure is retried on schedule;
+    /// only failing for longer than the schedule allows makes the task
+    /// unhealthy.
+    pub last_error: Option<String>,
+}
+
+/// Snapshot of one registered task, as reported by the `tasks` command.
+pub struct TaskInfo {
+    pub name: &'static str,
+    pub schedule: Schedule,
+    pub paused: bool,
+    pub status: TaskStatus,
+}
+
+/// Handle to the running background tasks: status reporting, manual runs and
+/// pausing. Cheap to clone; every clone refers to the same tasks.
+#[derive(Clone)]
+pub struct TaskRunner {
+    db: Db,
+    entries: Arc<Vec<TaskEntry>>,
+}
+
+impl TaskRunner {
+    pub async fn list(&self) -> Result<Vec<TaskInfo>, Error> {
+        let mut infos = Vec::new();
+        for entry in self.entries.iter() {
+            infos.push(TaskInfo {
+                name: entry.name,
+                schedule: entry.schedule.clone(),
+                paused: is_paused(&self.db, entry.name).await?,
+                status: entry.status.read().unwrap().clone(),
+            });
+        }
+        Ok(infos)
+    }
+
+    /// Checks every task loop is alive and its runs succeed (see
+    /// [`assess`]). Paused tasks keep polling, so their loop is checked too,
+    /// but they aren't expected to succeed.
+    pub async fn health(&self) -> Result<Vec<TaskHealth>, Error> {
+        let mut health = Vec::new();
+        for entry in self.entries.iter() {
+            let paused = is_paused(&self.db, entry.name).await?;
+            let status = entry.status.read().unwrap().clone();
+            let since_tick = entry.last_tick.read().unwrap().elapsed();
+            let (ok, detail) = assess(&entry.schedule, &status, since_tick, entry.started, paused, Utc::now());
+            health.push(TaskHealth { name: entry.name, ok, detail, last_error: status.last_error });
+        }
+        Ok(health)
+    }
+
+    /// Whether `name` is a registered task.
+    pub fn contains(&self, name: &str) -> bool {
+        self.entry(name).is_some()
+    }
+
+    /// Requests an immediate run of the task, even if it is paused; a
+    /// per-server task runs for every server that has scheduled it. Returns
+    /// `false` for an unknown task. A run already in progress is not
+    /// interrupted; the requested run starts once it finishes.
+    pub fn trigger(&self, name: &str) -> bool {
+        match self.entry(name) {
+            Some(entry) => {
+                entry.trigger.notify_one();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Pauses or resumes the scheduled runs of a task. Persisted, so a paused
+    /// task stays paused across restarts. Returns `false` for an unknown task.
+    pub async fn set_paused(&self, name: &str, paused: bool) -> Result<bool, Error> {
+        if !self.contains(name) {
+            return Ok(false);
+        }
+        let key = paused_key(name);
+        if paused {
+            self.db.set_task_state(RUNNER_STATE, &key, "1", None).await?;
+        } else {
+            self.db.delete_task_state(RUNNER_STATE, &key).await?;
+        }
+        Ok(true)
+    }
+
+    fn entry(&self, name: &str) -> Option<&TaskEntry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+}
+
+fn paused_key(name: &str) -> String {
+    format!("paused:{}", name)
+}
+
+async fn is_paused(db: &Db, name: &str) -> Result<bool, Error> {
+    Ok(db.get_task_state(RUNNER_STATE, &paused_key(name)).await?.is_some())
 }
 
 /// Spawns all registered background tasks. Called once when the bot is ready.
-pub fn start(ctx: serenity::Context, db: Db) {
-    for task in tasks() {
-        let task_ctx = TaskContext { ctx: ctx.clone(), db: db.clone() };
+/// Each run holds a [`Shutdown`] guard, so shutdown waits for it to finish;
+/// no new runs start once shutdown begins.
+pub fn start(ctx: serenity::Context, db: Db, shutdown: Shutdown, llm: Option<Arc<LlmClient>>) -> TaskRunner {
+    let tasks = tasks(llm);
+    let entries: Arc<Vec<TaskEntry>> = Arc::new(
+        tasks
+            .iter()
+            .map(|task| TaskEntry {
+                name: task.name(),
+                schedule: task.schedule(),
+                status: RwLock::new(TaskStatus::default()),
+                trigger: Notify::new(),
+                last_tick: RwLock::new(Instant::now()),
+                started: Utc::now(),
+            })
+            .collect(),
+    );
+
+    for (index, task) in tasks.into_iter().enumerate() {
+        let runner = TaskRunner { db: db.clone(), entries: entries.clone() };
+        let ctx = ctx.clone();
+        let shutdown = shutdown.clone();
         tokio::spawn(async move {
-            info!("Started background task '{}' (interval: {:?})", task.name(), task.interval());
-            let mut interval = tokio::time::interval(task.interval());
+            let entry = &runner.entries[index];
+            info!("Started background task '{}'", task.name());
+            let mut interval = tokio::time::interval(poll_period(&entry.schedule));
             interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            let mut startup_run = task.run_at_startup(&runner.db).await.unwrap_or_else(|e| {
+                warn!("Background task '{}': checking for a startup run failed: {:?}", task.name(), e);
+                false
+            });
             loop {
-                interval.tick().await;
-                if let Err(e) = task.run(&task_ctx).await {
-                    warn!("Background task '{}' failed: {:?}", task.name(), e);
+                let token = shutdown.token();
+                let manual = tokio::select! {
+                    _ = token.cancelled() => break,
+                    _ = interval.tick() => false,
+                    _ = entry.trigger.notified() => true,
+                };
+                *entry.last_tick.write().unwrap() = Instant::now();
+                let guilds = match due_runs(&runner.db, task.name(), &entry.schedule, manual).await {
+                    Ok(mut guilds) => {
+                        if std::mem::take(&mut startup_run) && guilds.is_empty() {
+                            guilds.push(None);
+                        }
+                        guilds
+                    }
+                    Err(e) => {
+                        warn!("Background task '{}': checking schedule failed: {:?}", task.name(), e);
+                        continue;
+                    }
+                };
+                if !manual {
+                    match is_paused(&runner.db, task.name()).await {
+                        Ok(false) => {}
+                        Ok(true) => continue,
+                        Err(e) => warn!("Background task '{}': reading pause state failed: {:?}", task.name(), e),
+                    }
+                }
+                for guild_id in guilds {
+                    let Some(_guard) = shutdown.guard().await else { break };
+                    let task_ctx = TaskContext { ctx: ctx.clone(), db: runner.db.clone(), guild_id, shutdown: token.clone() };
+                    run_once(task.as_ref(), &task_ctx, entry).await;
                 }
             }
+            info!("Stopped background task '{}'", task.name());
         });
     }
+
+    TaskRunner { db, entries }
+}
+
+/// A task's health: its loop woke up within twice its polling period (plus
+/// a minute of slack), or is in the middle of a run that hasn't taken longer
+/// than [`MAX_RUN`]; and, unless paused, it succeeded (or started, if it
+/// hasn't yet) within twice its [`expected_interval`] plus
+/// [`SUCCESS_GRACE`].
+fn assess(
+    schedule: &Schedule,
+    status: &TaskStatus,
+    since_tick: Duration,
+    started: DateTime<Utc>,
+    paused: bool,
+    now: DateTime<Utc>,
+) -> (bool, String) {
+    let running_for = status
+        .last_started
+        .filter(|_| status.running)
+        .map(|started| (now - started).to_std().unwrap_or_default());
+    let (mut ok, mut detail) = match running_for {
+        Some(running_for) => (
+            running_for <= MAX_RUN,
+            format!("running for {}s (allowed {}s)", running_for.as_secs(), MAX_RUN.as_secs()),
+        ),
+        None => {
+            let allowed = poll_period(schedule) * 2 + Duration::from_secs(60);
+            (
+                since_tick <= allowed,
+                format!("last polled {}s ago (allowed {}s)", since_tick.as_secs(), allowed.as_secs()),
+            )
+        }
+    };
+    if let Some(interval) = expected_interval(schedule, now).filter(|_| !paused) {
+        let allowed = interval * 2 + SUCCESS_GRACE;
+        let since = (now - status.last_success.unwrap_or(started)).to_std().unwrap_or_default();
+        ok &= since <= allowed;
+        let what = if status.last_success.is_some() { "last succeeded" } else { "no success since start" };
+        detail.push_str(&format!("; {} {}s ago (allowed {}s)", what, since.as_secs(), allowed.as_secs()));
+    }
+    (ok, detail)
+}
+
+/// The longest a task should go between runs: its interval, or the longest
+/// gap between a cron schedule's next few times. Per-server schedules differ
+/// per server, so they have none.
+fn expected_interval(schedule: &Schedule, now: DateTime<Utc>) -> Option<Duration> {
+    match schedule {
+        Schedule::Every(period) => Some(*period),
+        Schedule::Cron(cron) => {
+            let mut times = Vec::with_capacity(CRON_GAPS + 1);
+            let mut time = now;
+            for _ in 0..=CRON_GAPS {
+                time = cron.next_after(time)?;
+                times.push(time);
+            }
+            times.windows(2).filter_map(|pair| (pair[1] - pair[0]).to_std().ok()).max()
+        }
+        Schedule::PerGuild { .. } => None,
+    }
+}
+
+/// How often a task loop wakes up to check whether a run is due.
+fn poll_period(schedule: &Schedule) -> Duration {
+    match schedule {
+        Schedule::Every(period) => *period,
+        Schedule::Cron(_) | Schedule::PerGuild { .. } => CRON_POLL,
+    }
+}
+
+/// The runs due on this tick, as the `guild_id` of each run. Cron schedules
+/// advance their persisted next run time even while the task is paused, so
+/// resuming doesn't replay missed runs; per-server schedules keep one next
+/// run time per server, as `next:<task>:<guild>`.
+async fn due_runs(db: &Db, name: &str, schedule: &Schedule, manual: bool) -> Result<Vec<Option<u64>>, Error> {
+    match schedule {
+        Schedule::Every(_) => Ok(vec![None]),
+        Schedule::Cron(cron) => {
+            let due = cron_due(db, &format!("next:{}", name), cron).await?;
+            Ok(if due || manual { vec![None] } else { Vec::new() })
+        }
+        Schedule::PerGuild { scope, key } => {
+            let mut guilds = Vec::new();
+            for (guild_id, value) in db.guild_setting_values(scope, key).await? {
+                let cron = match Cron::parse(&value) {
+                    Ok(cron) => cron,
+                    Err(e) => {
+                        warn!("Background task '{}': bad schedule for guild {}: {}", name, guild_id, e);
+                        continue;
+                    }
+                };
+                let due = cron_due(db, &format!("next:{}:{}", name, guild_id), &cron).await?;
+                if due || manual {
+                    guilds.push(Some(guild_id));
+                }
+            }
+            Ok(guilds)
+        }
+    }
+}
+
+/// Whether a cron schedule is due, persisting its next run time as
+/// `<unix time>|<expression>`. A schedule seen for the first time, or whose
+/// expression changed, waits for its next match; a stored time that passed
+/// while the bot was down is due once.
+async fn cron_due(db: &Db, key: &str, cron: &Cron) -> Result<bool, Error> {
+    let now = Utc::now();
+    let stored = db.get_task_state(RUNNER_STATE, key).await?.and_then(|value| {
+        let (time, source) = value.split_once('|')?;
+        let time = time.parse::<i64>().ok()?;
+        (source == cron.source()).then(|| DateTime::from_timestamp(time, 0)).flatten()
+    });
+    let due = matches!(stored, Some(next) if next <= now);
+    if stored.is_none() || due {
+        match cron.next_after(now) {
+            Some(next) => {
+                let value = format!("{}|{}", next.timestamp(), cron.source());
+                db.set_task_state(RUNNER_STATE, key, &value, None).await?;
+            }
+            None => db.delete_task_state(RUNNER_STATE, key).await?,
+        }
+    }
+    Ok(due)
+}
+
+/// Runs a task once, recording its timing and outcome in the task's status.
+async fn run_once(task: &dyn Task, task_ctx: &TaskContext, entry: &TaskEntry) {
+    {
+        let mut status = entry.status.write().unwrap();
+        status.last_started = Some(Utc::now());
+        status.running = true;
+    }
+    let started = Instant::now();
+    let result = task.run(task_ctx).await;
+    metrics::task_finished(task.name(), started.elapsed(), result.is_ok());
+
+    if let Err(e) = &result {
+        warn!("Background task '{}' failed: {:?}", task.name(), e);
+    }
+    record_run(&mut entry.status.write().unwrap(), started.elapsed(), result);
+}
+
+/// Updates a task's status with the outcome of a run that just finished.
+fn record_run(status: &mut TaskStatus, duration: Duration, result: Result<(), Error>) {
+    status.running = false;
+    status.last_finished = Some(Utc::now());
+    status.last_duration = Some(duration);
+    status.runs += 1;
+    match result {
+        Ok(()) => {
+            status.last_error = None;
+            status.last_success = status.last_finished;
+        }
+        Err(e) => {
+            status.failures += 1;
+            status.last_error = Some(e.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::TempDb;
+
+    #[tokio::test]
+    async fn per_guild_schedules_are_due_per_server() {
+        let temp = TempDb::new("tasks-per-guild");
+        let db = temp.db().await;
+        let schedule = Schedule::PerGuild { scope: "digest", key: "schedule" };
+        let expression = "0 18 * * SUN";
+        for guild_id in [1, 2, 3] {
+            db.set_guild_setting(guild_id, "digest", "schedule", expression, 1).await.unwrap();
+        }
+        let past = Utc::now().timestamp() - 60;
+        let future = Utc::now().timestamp() + 3600;
+        db.set_task_state(RUNNER_STATE, "next:digest:1", &format!("{}|{}", past, expression), None).await.unwrap();
+        db.set_task_state(RUNNER_STATE, "next:digest:2", &format!("{}|{}", future, expression), None).await.unwrap();
+
+        // Only server 1's time passed; server 3 is seen for the first time and
+        // waits for its next match.
+        assert_eq!(due_runs(&db, "digest", &schedule, false).await.unwrap(), vec![Some(1)]);
+        assert!(due_runs(&db, "digest", &schedule, false).await.unwrap().is_empty());
+        let stored = db.get_task_state(RUNNER_STATE, "next:digest:3").await.unwrap().unwrap();
+        assert!(stored.ends_with(expression));
+        // A manual run covers every server that scheduled the task.
+        assert_eq!(due_runs(&db, "digest", &schedule, true).await.unwrap(), vec![Some(1), Some(2), Some(3)]);
+        db.close().await;
+    }
+
+    #[test]
+    fn a_task_that_always_fails_turns_unhealthy() {
+        let schedule = Schedule::Every(Duration::from_secs(60 * 60));
+        let started = Utc::now() - chrono::Duration::hours(3);
+        let mut status = TaskStatus::default();
+        for _ in 0..3 {
+            record_run(&mut status, Duration::from_secs(1), Err("speedrun.com is down".into()));
+        }
+        // The loop still polls on time, but nothing succeeded for three hours.
+        let (ok, detail) = assess(&schedule, &status, Duration::from_secs(5), started, false, Utc::now());
+        assert!(!ok, "{}", detail);
+        assert!(detail.contains("no success since start"), "{}", detail);
+        // Paused tasks aren't expected to succeed.
+        assert!(assess(&schedule, &status, Duration::from_secs(5), started, true, Utc::now()).0);
+
+        // One success brings it back.
+        record_run(&mut status, Duration::from_secs(1), Ok(()));
+        let (ok, detail) = assess(&schedule, &status, Duration::from_secs(5), started, false, Utc::now());
+        assert!(ok, "{}", detail);
+        assert!(detail.contains("last succeeded"), "{}", detail);
+    }
+
+    #[test]
+    fn cron_tasks_are_allowed_their_longest_gap() {
+        // Weekdays only: the weekend gap is the longest.
+        let cron = Cron::parse("0 9 * * MON-FRI").unwrap();
+        let interval = expected_interval(&Schedule::Cron(cron), Utc::now()).unwrap();
+        assert_eq!(interval, Duration::from_secs(3 * 24 * 60 * 60));
+    }
 }
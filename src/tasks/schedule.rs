@@ -0,0 +1,262 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, NaiveDateTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+
+/// When a background task runs.
+#[derive(Clone)]
+pub enum Schedule {
+    /// On a fixed interval; the first run happens immediately on startup.
+    Every(Duration),
+    /// At the times matched by a cron expression, e.g. every Sunday 18:00 in
+    /// Stockholm. The next run time is persisted, so a run missed while the
+    /// bot was down happens once on startup.
+    Cron(Cron),
+    /// Once per server, at the times each server chooses with a cron
+    /// expression stored in the per-server setting `scope.key`. Servers
+    /// without the setting never run. Runs get the server in
+    /// [`TaskContext::guild_id`](super::TaskContext::guild_id).
+    #[allow(dead_code)] // Not used by any registered task yet.
+    PerGuild { scope: &'static str, key: &'static str },
+}
+
+/// A parsed cron expression: `minute hour day-of-month month day-of-week`,
+/// optionally followed by an IANA timezone (default UTC). Fields accept `*`,
+/// numbers, ranges (`1-5`), steps (`*/15`, `0-30/10`) and comma lists; month
+/// and weekday also accept three-letter names (`JAN`, `SUN`). As in classic
+/// cron, when both day fields are restricted a day matching either one runs.
+///
+/// Times are matched on the wall clock of the timezone, so `0 18 * * SUN
+/// Europe/Stockholm` stays at 18:00 local across DST changes. A time skipped
+/// by a DST jump runs an hour later; a time repeated by one runs once.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cron {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+    tz: Tz,
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+const WEEKDAY_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// How far ahead [`Cron::next_after`] searches; enough for any valid
+/// expression (`29 2` is at most eight years away), while expressions that
+/// can never match, like `31 2`, return `None` instead of looping.
+const SEARCH_DAYS: i64 = 366 * 8;
+
+impl Cron {
+    pub fn parse(value: &str) -> Result<Cron, String> {
+        let fields: Vec<&str> = value.split_whitespace().collect();
+        let (fields, tz) = match fields.len() {
+            5 => (&fields[..], Tz::UTC),
+            6 => {
+                let tz = fields[5]
+                    .parse::<Tz>()
+                    .map_err(|_| format!("`{}` is not a known timezone (e.g. Europe/Stockholm)", fields[5]))?;
+                (&fields[..5], tz)
+            }
+            _ => {
+                return Err(format!(
+                    "`{}` is not a cron expression (expected `minute hour day month weekday [timezone]`)",
+                    value
+                ))
+            }
+        };
+
+        let mut days_of_week = parse_field(fields[4], 0, 7, &WEEKDAY_NAMES, "weekday")?;
+        // Both 0 and 7 mean Sunday.
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+
+        Ok(Cron {
+            source: value.split_whitespace().collect::<Vec<_>>().join(" "),
+            minutes: parse_field(fields[0], 0, 59, &[], "minute")?,
+            hours: parse_field(fields[1], 0, 23, &[], "hour")?,
+            days_of_month: parse_field(fields[2], 1, 31, &[], "day of month")?,
+            months: parse_field(fields[3], 1, 12, &MONTH_NAMES, "month")?,
+            days_of_week,
+            any_day_of_month: fields[2] == "*",
+            any_day_of_week: fields[4] == "*",
+            tz,
+        })
+    }
+
+    /// The expression as written (whitespace normalised).
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The first matching time strictly after `after`.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let local = after.with_timezone(&self.tz).naive_local();
+        let start = local.with_second(0)?.with_nanosecond(0)? + ChronoDuration::minutes(1);
+
+        for day in 0..SEARCH_DAYS {
+            let date = start.date() + ChronoDuration::days(day);
+            if !self.matches_date(date.month(), date.day(), date.weekday().num_days_from_sunday()) {
+                continue;
+            }
+            for hour in bits(self.hours) {
+                for minute in bits(self.minutes) {
+                    let naive = date.and_hms_opt(hour, minute, 0)?;
+                    if naive < start {
+                        continue;
+                    }
+                    if let Some(time) = self.resolve(naive) {
+                        if time > after {
+                            return Some(time);
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn matches_date(&self, month: u32, day: u32, weekday: u32) -> bool {
+        if self.months & (1 << month) == 0 {
+            return false;
+        }
+        let dom = self.days_of_month & (1 << day) != 0;
+        let dow = self.days_of_week & (1 << weekday) != 0;
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (true, true) => true,
+            (true, false) => dow,
+            (false, true) => dom,
+            (false, false) => dom || dow,
+        }
+    }
+
+    /// Maps a local wall-clock time to UTC: the first occurrence of a
+    /// repeated time, or an hour later for a time inside a DST gap.
+    fn resolve(&self, naive: NaiveDateTime) -> Option<DateTime<Utc>> {
+        let local = self
+            .tz
+            .from_local_datetime(&naive)
+            .earliest()
+            .or_else(|| self.tz.from_local_datetime(&(naive + ChronoDuration::hours(1))).earliest())?;
+        Some(local.with_timezone(&Utc))
+    }
+}
+
+fn bits(mask: u64) -> impl Iterator<Item = u32> {
+    (0..64).filter(move |bit| mask & (1 << bit) != 0)
+}
+
+/// Parses one cron field into a bitmask of allowed values.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str], what: &str) -> Result<u64, String> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("`{}` has an invalid step in the {} field", part, what))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (parse_value(a, min, names, what)?, parse_value(b, min, names, what)?)
+        } else {
+            let value = parse_value(range, min, names, what)?;
+            // `5/10` means "from 5, every 10".
+            (value, if step > 1 { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("`{}` is out of range for the {} field ({}-{})", part, what, min, max));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+fn parse_value(value: &str, min: u32, names: &[&str], what: &str) -> Result<u32, String> {
+    if let Ok(n) = value.parse::<u32>() {
+        return Ok(n);
+    }
+    names
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(value))
+        .map(|i| i as u32 + min)
+        .ok_or_else(|| format!("`{}` is not a valid {}", value, what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn weekly_schedule_follows_local_time_across_dst() {
+        let cron = Cron::parse("0 18 * * SUN Europe/Stockholm").unwrap();
+        // 2026-03-22 is the Sunday before DST starts (CET, UTC+1)…
+        assert_eq!(cron.next_after(utc("2026-03-20T12:00:00Z")), Some(utc("2026-03-22T17:00:00Z")));
+        // …and 2026-03-29 the Sunday it starts (CEST, UTC+2).
+        assert_eq!(cron.next_after(utc("2026-03-22T17:00:00Z")), Some(utc("2026-03-29T16:00:00Z")));
+    }
+
+    #[test]
+    fn time_in_dst_gap_runs_an_hour_later() {
+        // 02:30 doesn't exist in Stockholm on 2026-03-29.
+        let cron = Cron::parse("30 2 * * * Europe/Stockholm").unwrap();
+        assert_eq!(cron.next_after(utc("2026-03-28T12:00:00Z")), Some(utc("2026-03-29T01:30:00Z")));
+    }
+
+    #[test]
+    fn repeated_time_runs_once() {
+        // 02:30 happens twice in Stockholm on 2026-10-25.
+        let cron = Cron::parse("30 2 * * * Europe/Stockholm").unwrap();
+        let first = cron.next_after(utc("2026-10-24T12:00:00Z")).unwrap();
+        assert_eq!(first, utc("2026-10-25T00:30:00Z"));
+        assert_eq!(cron.next_after(first), Some(utc("2026-10-26T01:30:00Z")));
+    }
+
+    #[test]
+    fn parses_steps_ranges_and_names() {
+        let cron = Cron::parse("*/15 9-17 * JAN-MAR MON-FRI").unwrap();
+        assert_eq!(cron.next_after(utc("2026-01-02T17:50:00Z")), Some(utc("2026-01-05T09:00:00Z")));
+        assert_eq!(cron.next_after(utc("2026-01-05T09:00:00Z")), Some(utc("2026-01-05T09:15:00Z")));
+        // 7 is Sunday too.
+        assert_eq!(Cron::parse("0 0 * * 7").unwrap().days_of_week, 1);
+    }
+
+    #[test]
+    fn restricted_day_fields_match_either() {
+        // The 1st of the month or any Monday.
+        let cron = Cron::parse("0 12 1 * MON").unwrap();
+        assert_eq!(cron.next_after(utc("2026-06-01T13:00:00Z")), Some(utc("2026-06-08T12:00:00Z")));
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        assert!(Cron::parse("0 18 * *").is_err());
+        assert!(Cron::parse("60 * * * *").is_err());
+        assert!(Cron::parse("*/0 * * * *").is_err());
+        assert!(Cron::parse("0 18 * * FUNDAY").is_err());
+        assert!(Cron::parse("0 18 * * SUN Mars/Olympus").is_err());
+    }
+
+    #[test]
+    fn impossible_date_never_matches() {
+        assert_eq!(Cron::parse("0 0 31 2 *").unwrap().next_after(utc("2026-01-01T00:00:00Z")), None);
+    }
+}
@@ -10,9 +10,10 @@ use poise::serenity_prelude::{
 use serde::{Deserialize, Serialize};
 use tracing::warn;
 
-use super::{Task, TaskContext};
+use super::{Schedule, Task, TaskContext};
 use crate::api::speedrun::{self, Run, RunStatusChange};
 use crate::db::Db;
+use crate::metrics;
 use crate::Error;
 
 pub mod judge;
@@ -28,6 +29,18 @@ const SCOPE: &str = "speedrun";
 /// game/category with `speedrun.thresholds`.
 const DEFAULT_THRESHOLD: u32 = 50;
 
+/// Game id/name lookups are cached this long, so renamed or deleted games
+/// are eventually looked up again.
+const GAME_CACHE_TTL: Duration = Duration::from_secs(30 * 24 * 3600);
+
+/// Queue runs are remembered as seen this long; the expiry is refreshed every
+/// tick while the run is still queued.
+const SEEN_TTL: Duration = Duration::from_secs(30 * 24 * 3600);
+
+/// Demo and showcase entries expire after a week; their buttons are only
+/// for trying out the flow.
+const DEMO_TTL: Duration = Duration::from_secs(7 * 24 * 3600);
+
 /// New queue runs processed per game per tick; evidence gathering costs a few
 /// API calls per run, so a large backlog is drained over several ticks.
 const MAX_NEW_RUNS_PER_TICK: usize = 10;
@@ -270,8 +283,8 @@ impl Task for SpeedrunMonitor {
         TASK_NAME
     }
 
-    fn interval(&self) -> Duration {
-        Duration::from_secs(120)
+    fn schedule(&self) -> Schedule {
+        Schedule::Every(Duration::from_secs(120))
     }
 
     async fn run(&self, task_ctx: &TaskContext) -> Result<(), Error> {
@@ -306,6 +319,9 @@ impl Task for SpeedrunMonitor {
             .collect();
 
         for abbreviation in &games {
+            if task_ctx.shutdown.is_cancelled() {
+                break;
+            }
             let Some((game_id, game_name)) = self.resolve_game(db, abbreviation).await? else {
                 continue;
             };
@@ -409,8 +425,9 @@ impl SpeedrunMonitor {
 
         match speedrun::get_game(abbreviation).await? {
             Some(game) => {
-                db.set_task_state(TASK_NAME, &id_key, &game.id).await?;
-                db.set_task_state(TASK_NAME, &name_key, &game.names.international).await?;
+                db.set_task_state(TASK_NAME, &id_key, &game.id, Some(GAME_CACHE_TTL)).await?;
+                db.set_task_state(TASK_NAME, &name_key, &game.names.international, Some(GAME_CACHE_TTL))
+                    .await?;
                 Ok(Some((game.id, game.names.international)))
             }
             None => {
@@ -423,6 +440,7 @@ impl SpeedrunMonitor {
     async fn check_game(&self, task_ctx: &TaskContext, game: &GameContext<'_>) -> Result<(), Error> {
         let db = &task_ctx.db;
         let queue = speedrun::get_runs(&game.game_id, Some("new")).await?;
+        metrics::speedrun_queue_size(game.abbreviation, queue.len());
         let queue_ids: HashSet<&str> = queue.iter().map(|r| r.id.as_str()).collect();
 
         // Tracked runs that left the queue were decided on the website (or
@@ -459,13 +477,15 @@ impl SpeedrunMonitor {
         for run in queue.iter().rev() {
             let seen_key = format!("seen:{}", run.id);
             if db.get_task_state(TASK_NAME, &seen_key).await?.is_some() {
+                db.set_task_state(TASK_NAME, &seen_key, "1", Some(SEEN_TTL)).await?;
                 continue;
             }
-            if processed >= MAX_NEW_RUNS_PER_TICK {
+            // Unprocessed runs are picked up on the next start.
+            if processed >= MAX_NEW_RUNS_PER_TICK || task_ctx.shutdown.is_cancelled() {
                 break;
             }
             processed += 1;
-            db.set_task_state(TASK_NAME, &seen_key, "1").await?;
+            db.set_task_state(TASK_NAME, &seen_key, "1", Some(SEEN_TTL)).await?;
             if let Err(e) = self.process_run(task_ctx, game, run).await {
                 warn!("Speedrun monitor: processing run {} failed: {:?}", run.id, e);
             }
@@ -542,7 +562,7 @@ impl SpeedrunMonitor {
             demo_announcement: None,
             review: None,
         };
-        db.set_task_state(TASK_NAME, &format!("pending:{}", run.id), &serde_json::to_string(&pending)?)
+        db.set_task_state(TASK_NAME, &format!("pending:{}", run.id), &serde_json::to_string(&pending)?, None)
             .await?;
 
         Ok(())
@@ -602,6 +622,7 @@ impl SpeedrunMonitor {
         let threshold = game.policy.threshold_for(game.abbreviation, category);
         let evidence = judge::gather(game.abbreviation, &game.game_name, &game.game_id, run).await;
         let judgement = self.judge.judge(&evidence).await?;
+        metrics::judge_score(game.abbreviation, judgement.score);
         let suspicious = judgement.score >= threshold;
         let action = match (mode, suspicious) {
             (Mode::Auto, false) => PlannedAction::Verify,
@@ -855,7 +876,7 @@ pub async fn enter_review(
     restamp_mod_messages(ctx, &pending.messages, COLOUR_REVIEW, &verdict, demo, run_id).await;
 
     pending.review = Some(review);
-    db.set_task_state(TASK_NAME, &key, &serde_json::to_string(&pending)?).await?;
+    db.set_task_state(TASK_NAME, &key, &serde_json::to_string(&pending)?, demo.then_some(DEMO_TTL)).await?;
 
     Ok(ReviewResult::Opened(thread.id.get()))
 }
@@ -886,7 +907,7 @@ pub async fn dismiss_review(
     restamp_mod_messages(ctx, &pending.messages, review.prior_colour, &review.prior_status, demo, run_id)
         .await;
 
-    db.set_task_state(TASK_NAME, &key, &serde_json::to_string(&pending)?).await?;
+    db.set_task_state(TASK_NAME, &key, &serde_json::to_string(&pending)?, demo.then_some(DEMO_TTL)).await?;
 
     archive_thread(
         ctx,
@@ -1039,6 +1060,51 @@ pub fn outcome_verdict(outcome: &RunOutcome) -> (u32, String) {
     }
 }
 
+/// Drops tracked runs that can no longer be resolved: entries for games no
+/// server watches any more (and no mode is set for), and entries whose mod
+/// log messages were all deleted. Returns how many entries were removed.
+pub async fn collect_garbage(ctx: &serenity::Context, db: &Db) -> Result<usize, Error> {
+    let policy = load_policy(db).await?;
+    let mut watched: HashSet<String> =
+        policy.modes.keys().filter_map(|k| k.split('/').next().map(str::to_string)).collect();
+    for (_, games) in db.guild_setting_values(SCOPE, "games").await? {
+        watched.extend(games.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_lowercase));
+    }
+
+    let mut removed = 0;
+    for (key, raw) in db.list_task_state(TASK_NAME, "pending:").await? {
+        let orphaned = match serde_json::from_str::<PendingRun>(&raw) {
+            Ok(pending) => !watched.contains(&pending.game) || all_messages_deleted(ctx, &pending.messages).await,
+            Err(_) => true,
+        };
+        if orphaned {
+            db.delete_task_state(TASK_NAME, &key).await?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Whether every message is confirmed gone (404). Any other failure counts as
+/// still present, so a Discord outage doesn't drop tracked runs.
+async fn all_messages_deleted(ctx: &serenity::Context, messages: &[(u64, u64)]) -> bool {
+    if messages.is_empty() {
+        return false;
+    }
+    for (channel_id, message_id) in messages {
+        let result = ChannelId::new(*channel_id).message(&ctx.http, MessageId::new(*message_id)).await;
+        let deleted = matches!(
+            &result,
+            Err(serenity::Error::Http(serenity::HttpError::UnsuccessfulRequest(response)))
+                if response.status_code == serenity::StatusCode::NOT_FOUND
+        );
+        if !deleted {
+            return false;
+        }
+    }
+    true
+}
+
 async fn load_policy(db: &Db) -> Result<Policy, Error> {
     let modes = parse_modes(db.get_global_setting(SCOPE, "modes").await?.as_deref().unwrap_or(""));
     let thresholds =
@@ -16,7 +16,7 @@ use super::judge::{Evidence, PlayerEvidence, VideoEvidence};
 use super::{
     build_mod_embed, load_policy, parse_channel, pending_colour, post_to_channels, review_buttons,
     DemoAnnouncement, DemoResult, GameContext, Mode, PendingRun, PlannedAction, Policy,
-    RunPipelineResult, SpeedrunMonitor, DEFAULT_THRESHOLD, SCOPE, TASK_NAME,
+    RunPipelineResult, SpeedrunMonitor, DEFAULT_THRESHOLD, DEMO_TTL, SCOPE, TASK_NAME,
 };
 use crate::api::speedrun::{self, Category, Embedded, Names, Player, Run, RunStatus, Times, VideoLink, Videos};
 use crate::db::Db;
@@ -90,7 +90,7 @@ pub async fn demo_recent_runs(
             demo_announcement: None,
             review: None,
         };
-        db.set_task_state(TASK_NAME, &format!("demo:{}", run.id), &serde_json::to_string(&pending)?)
+        db.set_task_state(TASK_NAME, &format!("demo:{}", run.id), &serde_json::to_string(&pending)?, Some(DEMO_TTL))
             .await?;
     }
 
@@ -180,7 +180,7 @@ pub async fn demo_showcase(
                     demo_announcement: Some(announcement),
                     review: None,
                 };
-                db.set_task_state(TASK_NAME, &format!("demo:{}", run.id), &serde_json::to_string(&pending)?)
+                db.set_task_state(TASK_NAME, &format!("demo:{}", run.id), &serde_json::to_string(&pending)?, Some(DEMO_TTL))
                     .await?;
             }
             Err(e) => warn!("Speedrun monitor: posting showcase scenario failed: {:?}", e),
@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tracing::info;
+
+use super::schedule::Cron;
+use super::{Schedule, Task, TaskContext};
+use crate::api::llm::LlmClient;
+use crate::db::Db;
+use crate::util::llm_usage::MeteredLlm;
+use crate::util::wiki_search;
+use crate::Error;
+
+/// Keeps the wiki search index behind `ask` up to date, nightly. Only
+/// registered when an LLM backend with an embedding model is configured; the
+/// first run indexes the whole wiki, right after startup while the index is
+/// empty.
+pub struct WikiIndex {
+    client: Arc<LlmClient>,
+}
+
+impl WikiIndex {
+    pub fn new(client: Arc<LlmClient>) -> Self {
+        WikiIndex { client }
+    }
+}
+
+#[async_trait]
+impl Task for WikiIndex {
+    fn name(&self) -> &'static str {
+        "wiki_index"
+    }
+
+    fn schedule(&self) -> Schedule {
+        Schedule::Cron(Cron::parse("0 5 * * *").expect("valid cron expression"))
+    }
+
+    async fn run_at_startup(&self, db: &Db) -> Result<bool, Error> {
+        Ok(db.wiki_page_versions().await?.is_empty())
+    }
+
+    async fn run(&self, task_ctx: &TaskContext) -> Result<(), Error> {
+        let llm = MeteredLlm::new(&self.client, &task_ctx.db, "wiki_index");
+        let report = wiki_search::index(&task_ctx.db, &llm, &task_ctx.shutdown).await?;
+        info!(
+            "Wiki index: {} page(s) indexed, {} unchanged, {} removed, {} failed",
+            report.indexed, report.unchanged, report.removed, report.failed
+        );
+        if report.failed > 0 && report.indexed == 0 && report.unchanged == 0 {
+            return Err(format!("all {} page(s) failed to index", report.failed).into());
+        }
+        Ok(())
+    }
+}
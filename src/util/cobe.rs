@@ -1,5 +1,39 @@
-use inline_python::{Context, python};
-use crate::Data;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use poise::serenity_prelude as serenity;
+use rand::Rng;
+use sqlx::SqliteConnection;
+
+use crate::commands::config::parse_bool;
+use crate::db::brain::{self, END_TOKEN, ORDER, SHARED_BRAIN, SPACE_TOKEN};
+use crate::db::Db;
+use crate::util::persona;
+use crate::{Data, Error};
+
+mod search;
+mod tokenizer;
+
+pub use tokenizer::stem;
+
+/// Texts with fewer tokens (not counting spaces) aren't learned.
+const MIN_LEARN_TOKENS: usize = 3;
+
+/// Roughly how long a reply searches for the best candidate.
+const REPLY_TIME: Duration = Duration::from_millis(500);
+
+/// Random tokens tried when the input has none the brain knows.
+const BABBLE_PIVOTS: usize = 5;
+
+/// Contributions unlearned per transaction when forgetting a user, so the
+/// write lock is released in between.
+const FORGET_BATCH: i64 = 100;
+
+/// The classic MegaHAL answer of an empty brain.
+const DONT_KNOW: &str = "I don't know enough to answer you yet!";
+
+/// Settings scope of the chatter controls (see `config list cobe`).
+const SCOPE: &str = "cobe";
 
 fn strip_bot_mentions(content: &str, bot_id: u64, bot_name: &str) -> String {
     let mentions = [
@@ -15,65 +49,336 @@ fn strip_bot_mentions(content: &str, bot_id: u64, bot_name: &str) -> String {
         .to_string()
 }
 
+/// Every server's chatter brain. A brain exists once it learned something.
+pub struct Brains {
+    db: Db,
+}
+
+impl Brains {
+    pub fn new(db: Db) -> Brains {
+        Brains { db }
+    }
+
+    /// The brain with this id: a guild id or [`SHARED_BRAIN`].
+    pub fn get(&self, brain: u64) -> Cobe {
+        Cobe { db: self.db.clone(), brain }
+    }
+
+    /// The brain a server learns into and replies from: its own, or the
+    /// shared one if it opted in with `cobe.shared_brain`. DMs use the
+    /// shared one.
+    pub async fn for_guild(&self, guild_id: Option<u64>) -> Result<Cobe, Error> {
+        let Some(guild_id) = guild_id else {
+            return Ok(self.get(SHARED_BRAIN));
+        };
+        let shared = self.db.get_guild_setting(guild_id, SCOPE, "shared_brain").await?;
+        let shared = shared.as_deref().and_then(parse_bool).unwrap_or(false);
+        Ok(self.get(if shared { SHARED_BRAIN } else { guild_id }))
+    }
+
+    /// Unlearns everything a user taught any brain, in every server or only
+    /// in `guild_id`, a batch of texts per transaction. Returns the number
+    /// of texts unlearned.
+    pub async fn forget_user(&self, user_id: u64, guild_id: Option<u64>) -> Result<usize, Error> {
+        let mut forgotten = 0;
+        loop {
+            let mut tx = self.db.brain_transaction().await?;
+            let contributions = brain::contributions(&mut tx, user_id, guild_id, FORGET_BATCH).await?;
+            if contributions.is_empty() {
+                return Ok(forgotten);
+            }
+            for (id, edges) in &contributions {
+                unlearn(&mut tx, edges).await?;
+                brain::delete_contribution(&mut tx, *id).await?;
+            }
+            tx.commit().await?;
+            forgotten += contributions.len();
+        }
+    }
+}
+
+/// Takes back one earlier `learn`, given the edges it added: the edges'
+/// counts go down, and nodes and tokens nothing uses anymore are deleted.
+/// Edges already gone (say, after a reset) are skipped.
+async fn unlearn(conn: &mut SqliteConnection, edges: &[i64]) -> Result<(), Error> {
+    let mut nodes = Vec::new();
+    for edge in edges {
+        if let Some((prev, next)) = brain::remove_edge(&mut *conn, *edge).await? {
+            nodes.extend([prev, next]);
+        }
+    }
+    nodes.sort_unstable();
+    nodes.dedup();
+    let mut tokens = brain::prune_nodes(&mut *conn, &nodes).await?;
+    tokens.sort_unstable();
+    tokens.dedup();
+    brain::prune_tokens(conn, &tokens).await
+}
+
+/// A chatter brain: cobe 2's order-3 Markov model, natively on top of the
+/// `brain_*` tables. Every text seen is learned; replies are assembled from
+/// random walks through contexts containing words of the input and the most
+/// surprising one wins. Learning and replying run concurrently, serialized
+/// only by SQLite's write lock.
+#[derive(Clone)]
 pub struct Cobe {
-    context: Context
+    db: Db,
+    brain: u64,
 }
 
 impl Cobe {
-    pub fn new() -> Cobe {
-        let context: Context = python! {
-            import sys
-            sys.path.insert(1, '.')
-            print("Python :: Initializing COBE Brain")
-            from cobe.brain import Brain
-            b = Brain("bot.brain")
-            print("Python :: Done initializing")
+    /// Which brain this is: a guild id or [`SHARED_BRAIN`].
+    pub fn id(&self) -> u64 {
+        self.brain
+    }
+
+    /// Learns a text `user_id` wrote in `guild_id` (0 in DMs), remembering
+    /// who taught it so it can be forgotten.
+    pub async fn learn(&self, guild_id: u64, user_id: u64, text: &str) -> Result<(), Error> {
+        let mut tx = self.db.brain_transaction().await?;
+        self.learn_in(&mut tx, guild_id, user_id, text).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Learns many `(user id, text)` pairs from `guild_id` in one
+    /// transaction, for training from history. Returns how many texts were
+    /// long enough to learn.
+    pub async fn learn_batch(&self, guild_id: u64, texts: &[(u64, String)]) -> Result<usize, Error> {
+        let mut tx = self.db.brain_transaction().await?;
+        let mut learned = 0;
+        for (user_id, text) in texts {
+            if self.learn_in(&mut tx, guild_id, *user_id, text).await? {
+                learned += 1;
+            }
+        }
+        tx.commit().await?;
+        Ok(learned)
+    }
+
+    async fn learn_in(&self, conn: &mut SqliteConnection, guild_id: u64, user_id: u64, text: &str) -> Result<bool, Error> {
+        let tokens = tokenizer::split(text);
+        if tokens.iter().filter(|t| *t != " ").count() < MIN_LEARN_TOKENS {
+            return Ok(false);
+        }
+
+        let mut ids = Vec::with_capacity(tokens.len());
+        for token in &tokens {
+            let id = match token.as_str() {
+                " " => SPACE_TOKEN,
+                token => {
+                    let stem = tokenizer::stem(token);
+                    brain::create_token(&mut *conn, token, tokenizer::is_word(token), stem.as_deref()).await?
+                }
+            };
+            ids.push(id);
+        }
+        let mut contexts = search::contexts(&ids).into_iter();
+        let (first, _) = contexts.next().expect("a text has at least the end context");
+        let mut prev = brain::create_node(&mut *conn, self.brain, first).await?;
+        let mut edges = Vec::new();
+        for (context, has_space) in contexts {
+            let next = brain::create_node(&mut *conn, self.brain, context).await?;
+            edges.push(brain::add_edge(&mut *conn, prev, next, has_space).await?);
+            prev = next;
+        }
+        brain::add_contribution(conn, self.brain, guild_id, user_id, &edges).await?;
+        Ok(true)
+    }
+
+    pub async fn reply(&self, text: &str) -> Result<String, Error> {
+        let mut conn = self.db.brain().await?;
+        let Some(end_node) = brain::node_id(&mut conn, self.brain, [END_TOKEN; ORDER]).await? else {
+            return Ok(DONT_KNOW.to_string());
         };
 
-        Cobe { context }
+        // Reply around the words of the input, as cobe does: each known
+        // word is a pivot, except that all tokens sharing the stem of an
+        // input token make up one pivot together (so "samus" finds "Samus").
+        // Without any, babble about something random.
+        let tokens = tokenizer::split(text);
+        let mut input = Vec::new();
+        for token in &tokens {
+            if let Some(id) = brain::token_id(&mut conn, token).await? {
+                input.push(id);
+            }
+        }
+        let mut words = brain::known_words(&mut conn, self.brain, &input).await?;
+        let mut pivots: Vec<Vec<i64>> = Vec::new();
+        for stem in tokens.iter().filter_map(|token| tokenizer::stem(token)) {
+            let mut ids = brain::tokens_with_stem(&mut conn, self.brain, &stem).await?;
+            if !ids.is_empty() {
+                ids.sort_unstable();
+                words.retain(|id| !ids.contains(id));
+                pivots.push(ids);
+            }
+        }
+        pivots.extend(words.into_iter().map(|id| vec![id]));
+        if pivots.is_empty() {
+            for _ in 0..BABBLE_PIVOTS {
+                pivots.extend(brain::random_token(&mut conn, self.brain).await?.map(|id| vec![id]));
+            }
+        }
+        pivots.sort_unstable();
+        pivots.dedup();
+
+        let Some(edges) = search::best_reply(&mut conn, self.brain, &pivots, end_node, REPLY_TIME).await? else {
+            return Ok(DONT_KNOW.to_string());
+        };
+        let mut reply = String::new();
+        for edge in edges {
+            let (text, has_space) = brain::edge_text(&mut conn, edge).await?;
+            reply.push_str(&text);
+            if has_space {
+                reply.push(' ');
+            }
+        }
+        Ok(reply)
+    }
+}
+
+/// Where and from whom the brain learns and replies: the `cobe` settings
+/// for one channel, each falling back to the server and global value.
+struct Controls {
+    learn: bool,
+    reply: bool,
+    /// Percent chance of an unprompted reply.
+    reply_chance: u32,
+    min_length: usize,
+    ignore_users: Vec<u64>,
+    ignore_roles: Vec<u64>,
+    ignore_bots: bool,
+}
+
+impl Controls {
+    async fn load(db: &Db, guild_id: u64, channel_id: u64) -> Result<Controls, Error> {
+        let values = db.scope_values(guild_id, channel_id, SCOPE).await?;
+        let flag = |value: Option<&str>, default| value.and_then(parse_bool).unwrap_or(default);
+        Ok(Controls {
+            learn: flag(values.resolve("learn"), true),
+            reply: flag(values.resolve("reply"), true),
+            reply_chance: values.resolve("reply_chance").and_then(|v| v.parse().ok()).unwrap_or(0),
+            min_length: values.resolve("min_length").and_then(|v| v.parse().ok()).unwrap_or(0),
+            ignore_users: parse_ids(values.server("ignore_users")),
+            ignore_roles: parse_ids(values.server("ignore_roles")),
+            ignore_bots: flag(values.server("ignore_bots"), false),
+        })
+    }
+
+    /// Whether a message by this author is skipped entirely.
+    fn ignores(&self, user_id: u64, is_bot: bool, roles: &[u64]) -> bool {
+        (is_bot && self.ignore_bots)
+            || self.ignore_users.contains(&user_id)
+            || roles.iter().any(|role| self.ignore_roles.contains(role))
+    }
+}
+
+fn parse_ids(value: Option<&str>) -> Vec<u64> {
+    value.unwrap_or_default().split(',').filter_map(|id| id.trim().parse().ok()).collect()
+}
+
+/// Learns a channel's history a page at a time into a brain, skipping what
+/// `message_hook` wouldn't learn. The channel's controls and the authors'
+/// roles are looked up once for the whole run.
+pub struct Trainer {
+    cobe: Cobe,
+    guild_id: serenity::GuildId,
+    controls: Controls,
+    /// History doesn't come with members; roles are only looked up when
+    /// some are ignored.
+    roles: HashMap<serenity::UserId, Vec<u64>>,
+}
+
+impl Trainer {
+    pub async fn new(data: &Data, guild_id: serenity::GuildId, channel_id: serenity::ChannelId) -> Result<Self, Error> {
+        Ok(Trainer {
+            cobe: data.cobe.for_guild(Some(guild_id.get())).await?,
+            guild_id,
+            controls: Controls::load(&data.db, guild_id.get(), channel_id.get()).await?,
+            roles: HashMap::new(),
+        })
     }
 
-    pub fn reply(&self, msg: &str) -> String {
-        self.context.run(python! {
-            reply = b.reply('msg)
-        });
-        self.context.get::<String>("reply")
+    /// The brain being trained.
+    pub fn cobe(&self) -> &Cobe {
+        &self.cobe
     }
 
-    pub fn learn(&self, msg: &str) {
-        self.context.run(python! {
-            b.learn('msg)
-        });
+    /// Learns a page of the channel's history. Returns how many messages
+    /// were learned.
+    pub async fn learn(&mut self, ctx: &serenity::Context, data: &Data, messages: &[serenity::Message]) -> Result<usize, Error> {
+        if !self.controls.learn {
+            return Ok(0);
+        }
+        let current_user = ctx.cache.current_user().clone();
+        let mut texts = Vec::new();
+        for msg in messages {
+            if msg.author.id == current_user.id {
+                continue;
+            }
+            if !self.controls.ignore_roles.is_empty() && !self.roles.contains_key(&msg.author.id) {
+                let member_roles = match self.guild_id.member(ctx, msg.author.id).await {
+                    Ok(member) => member.roles.iter().map(|r| r.get()).collect(),
+                    // Members who left have no roles.
+                    Err(_) => Vec::new(),
+                };
+                self.roles.insert(msg.author.id, member_roles);
+            }
+            let author_roles = self.roles.get(&msg.author.id).map(Vec::as_slice).unwrap_or_default();
+            if self.controls.ignores(msg.author.id.get(), msg.author.bot, author_roles) {
+                continue;
+            }
+            let content = strip_bot_mentions(&msg.content, current_user.id.get(), &current_user.name);
+            if content.chars().count() < self.controls.min_length || data.db.brain_opted_out(msg.author.id.get()).await? {
+                continue;
+            }
+            texts.push((msg.author.id.get(), content));
+        }
+        self.cobe.learn_batch(self.guild_id.get(), &texts).await
     }
 }
 
-pub async fn message_hook(
-    ctx: &poise::serenity_prelude::Context,
-    msg: &poise::serenity_prelude::Message,
-    data: &Data,
-) -> Result<(), crate::Error> {
+pub async fn message_hook(ctx: &serenity::Context, msg: &serenity::Message, data: &Data) -> Result<(), Error> {
     if msg.author.id == ctx.cache.current_user().id {
         return Ok(());
     }
 
+    let guild_id = msg.guild_id.map(|g| g.get()).unwrap_or_default();
+    let controls = Controls::load(&data.db, guild_id, msg.channel_id.get()).await?;
+    let roles: Vec<u64> = msg.member.as_ref().map(|m| m.roles.iter().map(|r| r.get()).collect()).unwrap_or_default();
+    if controls.ignores(msg.author.id.get(), msg.author.bot, &roles) {
+        return Ok(());
+    }
+
     let current_user = ctx.cache.current_user().clone();
     let bot_id = current_user.id.get();
     let content = strip_bot_mentions(&msg.content, bot_id, &current_user.name);
+    let long_enough = content.chars().count() >= controls.min_length;
 
-    if msg.mentions_me(&ctx).await? {
-        let reply = {
-            let cobe = data.cobe.lock().await;
-            cobe.learn(&content);
-            cobe.reply(&content)
-        };
+    let cobe = data.cobe.for_guild(msg.guild_id.map(|g| g.get())).await?;
+    if controls.learn && long_enough && !data.db.brain_opted_out(msg.author.id.get()).await? {
+        cobe.learn(guild_id, msg.author.id.get(), &content).await?;
+    }
+    if !controls.reply {
+        return Ok(());
+    }
+    let prompted = msg.mentions_me(&ctx).await?;
+    if prompted {
+        if let Some(reply) = persona::reply(ctx, msg, data, &content).await? {
+            let message = serenity::CreateMessage::new()
+                .content(reply)
+                .allowed_mentions(serenity::CreateAllowedMentions::new());
+            let _ = msg.channel_id.send_message(&ctx, message).await;
+            return Ok(());
+        }
+    }
+    if prompted || (long_enough && rand::thread_rng().gen_range(0..100) < controls.reply_chance) {
+        let reply = cobe.reply(&content).await?;
         let reply = strip_bot_mentions(&reply, bot_id, &current_user.name);
 
         if !reply.is_empty() {
             let _ = msg.channel_id.say(&ctx, reply).await;
         }
-    } else {
-        let cobe = data.cobe.lock().await;
-        cobe.learn(&content);
     }
 
     Ok(())
@@ -81,7 +386,8 @@ pub async fn message_hook(
 
 #[cfg(test)]
 mod tests {
-    use super::strip_bot_mentions;
+    use super::*;
+    use crate::db::TempDb;
 
     #[test]
     fn strips_discord_bot_mentions() {
@@ -95,4 +401,96 @@ mod tests {
     fn strips_legacy_name_mentions() {
         assert_eq!(strip_bot_mentions("@Shaktool hello", 123, "Shaktool"), "hello");
     }
+
+    #[test]
+    fn ignores_listed_users_roles_and_bots() {
+        let controls = Controls {
+            learn: true,
+            reply: true,
+            reply_chance: 0,
+            min_length: 0,
+            ignore_users: parse_ids(Some("1, 2")),
+            ignore_roles: parse_ids(Some("10")),
+            ignore_bots: true,
+        };
+        assert!(controls.ignores(2, false, &[]));
+        assert!(controls.ignores(3, false, &[11, 10]));
+        assert!(controls.ignores(3, true, &[]));
+        assert!(!controls.ignores(3, false, &[11]));
+        assert!(!Controls { ignore_bots: false, ..controls }.ignores(3, true, &[]));
+    }
+
+    #[tokio::test]
+    async fn replies_with_what_it_learned() {
+        let temp = TempDb::new("cobe");
+        let db = temp.db().await;
+        let brains = Brains::new(db.clone());
+        let cobe = brains.get(1);
+
+        assert_eq!(cobe.reply("hello").await.unwrap(), DONT_KNOW);
+        // Too short to learn.
+        cobe.learn(1, 10, "hi there").await.unwrap();
+        assert_eq!(cobe.reply("hello").await.unwrap(), DONT_KNOW);
+
+        cobe.learn(1, 10, "Samus jumps over the spikes.").await.unwrap();
+        cobe.learn(1, 10, "Samus jumps over the spikes.").await.unwrap();
+        assert_eq!(cobe.reply("where does samus go?").await.unwrap(), "Samus jumps over the spikes.");
+        let stats = db.brain_stats(1).await.unwrap();
+        // The end token and six learned ones; learning twice adds nothing new.
+        assert_eq!(stats.tokens, 7);
+
+        // Other servers' brains know nothing of it.
+        assert_eq!(brains.get(2).reply("where does samus go?").await.unwrap(), DONT_KNOW);
+        assert_eq!(db.brain_stats(2).await.unwrap().nodes, 0);
+        assert!(db.reset_brain(1).await.unwrap() > 0);
+        assert_eq!(cobe.reply("where does samus go?").await.unwrap(), DONT_KNOW);
+        db.close().await;
+    }
+
+    #[tokio::test]
+    async fn pivots_on_every_form_of_an_input_word() {
+        let temp = TempDb::new("cobe-stems");
+        let db = temp.db().await;
+        let cobe = Brains::new(db.clone()).get(1);
+        cobe.learn(1, 10, "Samus jumps over the spikes.").await.unwrap();
+        cobe.learn(1, 10, "Kraid lives in the swamp.").await.unwrap();
+        // Neither "SAMUS" nor "jumping" was learned as such; their stems were.
+        for _ in 0..3 {
+            assert_eq!(cobe.reply("SAMUS").await.unwrap(), "Samus jumps over the spikes.");
+            assert_eq!(cobe.reply("jumping").await.unwrap(), "Samus jumps over the spikes.");
+        }
+        db.close().await;
+    }
+
+    #[tokio::test]
+    async fn forgets_only_the_users_contributions() {
+        let temp = TempDb::new("cobe-forget");
+        let db = temp.db().await;
+        let brains = Brains::new(db.clone());
+        let cobe = brains.get(1);
+
+        cobe.learn(1, 20, "Ridley guards the lower depths.").await.unwrap();
+        let before = db.brain_stats(1).await.unwrap();
+        cobe.learn(1, 10, "Samus jumps over the spikes.").await.unwrap();
+        cobe.learn(1, 10, "Ridley guards the lower depths.").await.unwrap();
+        brains.get(2).learn(2, 10, "Kraid lives in the swamp.").await.unwrap();
+
+        // Only what user 10 taught in server 1.
+        assert_eq!(brains.forget_user(10, Some(1)).await.unwrap(), 2);
+        let after = db.brain_stats(1).await.unwrap();
+        assert_eq!((after.tokens, after.nodes, after.edges), (before.tokens, before.nodes, before.edges));
+        assert_eq!(cobe.reply("samus ridley").await.unwrap(), "Ridley guards the lower depths.");
+        assert!(db.brain_stats(2).await.unwrap().nodes > 0);
+
+        assert_eq!(brains.forget_user(10, None).await.unwrap(), 1);
+        assert_eq!(db.brain_stats(2).await.unwrap().nodes, 0);
+
+        // Tokens only the forgotten texts used are gone too.
+        let mut tx = db.brain_transaction().await.unwrap();
+        assert_eq!(brain::token_id(&mut tx, "Samus").await.unwrap(), None);
+        assert_eq!(brain::token_id(&mut tx, "Kraid").await.unwrap(), None);
+        assert!(brain::token_id(&mut tx, "Ridley").await.unwrap().is_some());
+        drop(tx);
+        db.close().await;
+    }
 }
@@ -0,0 +1,199 @@
+//! Reply generation: cobe's random-walk search and `CobeScorer`.
+
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use rand::seq::SliceRandom;
+use sqlx::SqliteConnection;
+
+use crate::db::brain::{self, END_TOKEN, ORDER, SPACE_TOKEN};
+use crate::Error;
+
+/// Walks longer than this are abandoned; a consistent graph reaches the end
+/// long before.
+const MAX_WALK: usize = 1000;
+
+/// How much longer than the reply budget the search keeps going while it
+/// hasn't found any reply at all.
+const GRACE: Duration = Duration::from_secs(2);
+
+/// The context nodes a learned text passes through, each with whether a
+/// space preceded its last token. The text is padded with end tokens on both
+/// sides, so the first and last node are the end context.
+pub(super) fn contexts(token_ids: &[i64]) -> Vec<([i64; ORDER], bool)> {
+    let padding = [END_TOKEN; ORDER];
+    let mut contexts = Vec::new();
+    let mut context: Vec<i64> = Vec::with_capacity(ORDER);
+    let mut has_space = false;
+    for &id in padding.iter().chain(token_ids).chain(&padding) {
+        context.push(id);
+        if context.len() == ORDER {
+            if id == SPACE_TOKEN {
+                context.pop();
+                has_space = true;
+                continue;
+            }
+            contexts.push(([context[0], context[1], context[2]], has_space));
+            context.remove(0);
+            has_space = false;
+        }
+    }
+    contexts
+}
+
+/// Searches for replies through `brain`'s nodes containing one of `pivots`
+/// for about `budget`, returning the best-scoring one as its edges, or
+/// `None` if none was found. Each pivot is a set of tokens, equally likely
+/// to be chosen as the whole pivot is.
+pub(super) async fn best_reply(
+    conn: &mut SqliteConnection,
+    brain: u64,
+    pivots: &[Vec<i64>],
+    end_node: i64,
+    budget: Duration,
+) -> Result<Option<Vec<i64>>, Error> {
+    if pivots.is_empty() {
+        return Ok(None);
+    }
+    let started = Instant::now();
+    // Every forward and backward walk found from a node; any pair of them
+    // makes a reply through it.
+    let mut next_cache: HashMap<i64, HashSet<Vec<i64>>> = HashMap::new();
+    let mut prev_cache: HashMap<i64, HashSet<Vec<i64>>> = HashMap::new();
+    let mut scored: HashSet<Vec<i64>> = HashSet::new();
+    let mut edge_cache: HashMap<i64, (f64, bool)> = HashMap::new();
+    let mut best: Option<(f64, Vec<i64>)> = None;
+
+    loop {
+        let elapsed = started.elapsed();
+        if elapsed > budget && (best.is_some() || elapsed > budget + GRACE) {
+            break;
+        }
+        let Some(&pivot) = pivots.choose(&mut rand::thread_rng()).and_then(|p| p.choose(&mut rand::thread_rng())) else {
+            continue;
+        };
+        // Walks from the end context would go all the way round.
+        let Some(node) = brain::random_node_with_token(conn, brain, pivot).await?.filter(|&n| n != end_node) else {
+            continue;
+        };
+
+        let mut candidates = Vec::new();
+        if let Some(next) = walk(conn, node, end_node, true).await? {
+            for prev in prev_cache.get(&node).into_iter().flatten() {
+                candidates.push([prev.as_slice(), &next].concat());
+            }
+            next_cache.entry(node).or_default().insert(next);
+        }
+        if let Some(mut prev) = walk(conn, node, end_node, false).await? {
+            prev.reverse();
+            for next in next_cache.get(&node).into_iter().flatten() {
+                candidates.push([prev.as_slice(), next].concat());
+            }
+            prev_cache.entry(node).or_default().insert(prev);
+        }
+
+        for edges in candidates {
+            if !scored.insert(edges.clone()) {
+                continue;
+            }
+            let score = score(conn, &edges, &mut edge_cache).await?;
+            if best.as_ref().is_none_or(|(best, _)| score > *best) {
+                best = Some((score, edges));
+            }
+        }
+    }
+    Ok(best.map(|(_, edges)| edges))
+}
+
+/// One random walk from `node` to the end context, forward or backward, as
+/// the edges taken (in walking order).
+async fn walk(conn: &mut SqliteConnection, mut node: i64, end_node: i64, forward: bool) -> Result<Option<Vec<i64>>, Error> {
+    let mut path = Vec::new();
+    while path.len() < MAX_WALK {
+        let Some((edge, other)) = brain::random_edge(conn, node, forward).await? else {
+            return Ok(None);
+        };
+        path.push(edge);
+        if other == end_node {
+            return Ok(Some(path));
+        }
+        node = other;
+    }
+    Ok(None)
+}
+
+/// cobe's `CobeScorer`: the information content of the reply's edges,
+/// damped for long replies and mapped into 0..1.
+async fn score(
+    conn: &mut SqliteConnection,
+    edges: &[i64],
+    cache: &mut HashMap<i64, (f64, bool)>,
+) -> Result<f64, Error> {
+    let mut info = Vec::with_capacity(edges.len());
+    for edge in edges {
+        let entry = match cache.get(edge) {
+            Some(entry) => *entry,
+            None => {
+                let entry = brain::edge_info(conn, *edge).await?;
+                cache.insert(*edge, entry);
+                entry
+            }
+        };
+        info.push(entry);
+    }
+    Ok(score_edges(&info))
+}
+
+/// Scores a reply from each edge's `(log probability, has space)`.
+fn score_edges(edges: &[(f64, bool)]) -> f64 {
+    let mut info: f64 = -edges.iter().map(|(logprob, _)| logprob).sum::<f64>();
+    // Approximates cobe 1.2's word count: the padding edges at either end
+    // don't count, the spaces between tokens do.
+    let spaces = edges.iter().filter(|(_, has_space)| *has_space).count() as i64;
+    let words = edges.len() as i64 - (ORDER as i64 - 1) * 2 + spaces;
+    // cobe 1.x scored both directions.
+    info *= 2.0;
+    if words > 16 {
+        info /= ((words - 1) as f64).sqrt();
+    }
+    if info < 0.0 {
+        info
+    } else {
+        1.0 - 1.0 / (1.0 + info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const E: i64 = END_TOKEN;
+    const S: i64 = SPACE_TOKEN;
+
+    #[test]
+    fn contexts_pad_with_end_tokens_and_flag_spaces() {
+        // "a b c" as tokens 2, 3, 4.
+        let contexts = contexts(&[2, S, 3, S, 4]);
+        assert_eq!(
+            contexts,
+            vec![
+                ([E, E, E], false),
+                ([E, E, 2], false),
+                ([E, 2, 3], true),
+                ([2, 3, 4], true),
+                ([3, 4, E], false),
+                ([4, E, E], false),
+                ([E, E, E], false),
+            ]
+        );
+    }
+
+    #[test]
+    fn surprising_replies_score_higher() {
+        let likely = vec![(0.0, false); 8];
+        let unlikely = vec![(-2.0, false); 8];
+        assert!(score_edges(&unlikely) > score_edges(&likely));
+        assert_eq!(score_edges(&likely), 0.0);
+        assert!(score_edges(&unlikely) < 1.0);
+    }
+}
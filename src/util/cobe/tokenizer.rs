@@ -0,0 +1,97 @@
+//! cobe's `CobeTokenizer`: words (with apostrophes and hyphens), URLs,
+//! punctuation runs and the whitespace between them. Case is preserved.
+//! Also cobe's `CobeStemmer`, which lets a reply pivot on every form of a
+//! word of the input.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+use rust_stemmers::{Algorithm, Stemmer};
+
+static TOKEN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"\w+:\S+|[\w'-]+|[^\w\s][^\w]*[^\w\s]|[^\w\s]|\s+",
+    )
+    .expect("token pattern is valid")
+});
+
+static WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\w").expect("word pattern is valid"));
+
+static SMILE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r":-?[ )]*\)").expect("smile pattern is valid"));
+static FROWN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r":-?[' (]*\(").expect("frown pattern is valid"));
+
+static STEMMER: LazyLock<Stemmer> = LazyLock::new(|| Stemmer::create(Algorithm::English));
+
+/// Splits text into tokens. Leading and trailing whitespace is dropped and
+/// runs of spaces collapse into a single `" "` token; other whitespace (like
+/// a newline) stays a token of its own.
+pub fn split(text: &str) -> Vec<String> {
+    TOKEN
+        .find_iter(text.trim())
+        .map(|m| {
+            let token = m.as_str();
+            if token.starts_with(' ') && token.len() > 1 {
+                " ".to_string()
+            } else {
+                token.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Whether a token contains a word character; only words are used as reply
+/// pivots when the input has any.
+pub fn is_word(token: &str) -> bool {
+    WORD.is_match(token)
+}
+
+/// The stem of a token: a word's lowercase English stem, `:)` or `:(` for
+/// smileys and frowns, and none for other punctuation.
+pub fn stem(token: &str) -> Option<String> {
+    if is_word(token) {
+        Some(STEMMER.stem(&token.to_lowercase()).into_owned())
+    } else if SMILE.is_match(token) {
+        Some(":)".to_string())
+    } else if FROWN.is_match(token) {
+        Some(":(".to_string())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_words_punctuation_and_spaces() {
+        assert_eq!(split("  Hello,   world! "), vec!["Hello", ",", " ", "world", "!"]);
+        assert_eq!(split("don't re-roll :-("), vec!["don't", " ", "re-roll", " ", ":-("]);
+        assert_eq!(split("see https://wiki.supermetroid.run now"), vec![
+            "see",
+            " ",
+            "https://wiki.supermetroid.run",
+            " ",
+            "now"
+        ]);
+        assert_eq!(split("a\nb"), vec!["a", "\n", "b"]);
+        assert!(split("   ").is_empty());
+    }
+
+    #[test]
+    fn words_contain_word_characters() {
+        assert!(is_word("Samus"));
+        assert!(is_word("100%"));
+        assert!(!is_word("?!"));
+        assert!(!is_word(""));
+    }
+
+    #[test]
+    fn stems_ignore_case_and_inflection() {
+        assert_eq!(stem("Samus").as_deref(), Some("samus"));
+        assert_eq!(stem("jumping"), stem("Jumps"));
+        assert_eq!(stem(":-))").as_deref(), Some(":)"));
+        assert_eq!(stem(":'(").as_deref(), Some(":("));
+        assert_eq!(stem("?!"), None);
+    }
+}
@@ -0,0 +1,202 @@
+//! Accounting for language model calls: every call made through
+//! [`MeteredLlm`] is recorded with the server, user and feature it was for,
+//! and refused once the server's `llm` token budget for the day or month is
+//! used up.
+
+use std::fmt;
+
+use chrono::{Datelike, NaiveDate, Utc};
+use futures::future::FutureExt;
+use futures::stream::BoxStream;
+
+use crate::api::llm::{self, Embeddings, LlmClient, LlmDelta, LlmRequest, LlmResponse, LlmUsage, ToolHandler};
+use crate::db::llm_usage::UsageEntry;
+use crate::db::Db;
+use crate::Error;
+
+const SCOPE: &str = "llm";
+
+/// The error of a call refused because the server's budget is used up.
+/// Features can check for it (`downcast_ref`) to fall back to something else.
+#[derive(Debug)]
+pub struct OverBudget {
+    /// `"daily"` or `"monthly"`.
+    pub period: &'static str,
+}
+
+impl fmt::Display for OverBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "this server's {} language model budget is used up", self.period)
+    }
+}
+
+impl std::error::Error for OverBudget {}
+
+/// A server's budgets and what it used of them.
+pub struct Budget {
+    pub daily: Option<u64>,
+    pub used_today: u64,
+    pub monthly: Option<u64>,
+    pub used_this_month: u64,
+}
+
+impl Budget {
+    pub async fn load(db: &Db, guild_id: u64) -> Result<Self, Error> {
+        let (today, month) = period_starts(Utc::now().date_naive());
+        Ok(Self {
+            daily: limit(db, guild_id, "daily_tokens").await?,
+            used_today: db.llm_tokens_since(guild_id, today).await?,
+            monthly: limit(db, guild_id, "monthly_tokens").await?,
+            used_this_month: db.llm_tokens_since(guild_id, month).await?,
+        })
+    }
+
+    /// The period whose budget is used up, if any.
+    pub fn exhausted(&self) -> Option<&'static str> {
+        if self.daily.is_some_and(|daily| self.used_today >= daily) {
+            Some("daily")
+        } else if self.monthly.is_some_and(|monthly| self.used_this_month >= monthly) {
+            Some("monthly")
+        } else {
+            None
+        }
+    }
+}
+
+/// An [`LlmClient`] whose calls are checked against the server's budget and
+/// recorded.
+pub struct MeteredLlm<'a> {
+    pub client: &'a LlmClient,
+    db: &'a Db,
+    guild_id: Option<u64>,
+    user_id: Option<u64>,
+    feature: &'static str,
+}
+
+impl<'a> MeteredLlm<'a> {
+    /// Calls for `feature`, on nobody's behalf (e.g. a background task).
+    pub fn new(client: &'a LlmClient, db: &'a Db, feature: &'static str) -> Self {
+        Self {
+            client,
+            db,
+            guild_id: None,
+            user_id: None,
+            feature,
+        }
+    }
+
+    /// Calls on behalf of a user, counted against the server's budget.
+    pub fn for_user(self, guild_id: Option<u64>, user_id: u64) -> Self {
+        Self {
+            guild_id,
+            user_id: Some(user_id),
+            ..self
+        }
+    }
+
+    /// Fails with [`OverBudget`] if the server used up its budget. Calls
+    /// outside a server have none.
+    pub async fn check_budget(&self) -> Result<(), Error> {
+        let Some(guild_id) = self.guild_id else {
+            return Ok(());
+        };
+        match Budget::load(self.db, guild_id).await?.exhausted() {
+            Some(period) => Err(OverBudget { period }.into()),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn chat(&self, request: LlmRequest) -> Result<LlmResponse, Error> {
+        self.check_budget().await?;
+        let response = self.client.chat(request).await?;
+        self.record(&response.model, response.usage).await?;
+        Ok(response)
+    }
+
+    pub async fn embed(&self, texts: &[String]) -> Result<Embeddings, Error> {
+        self.check_budget().await?;
+        let embeddings = self.client.embed(texts).await?;
+        if !texts.is_empty() {
+            self.record(&embeddings.model, embeddings.usage).await?;
+        }
+        Ok(embeddings)
+    }
+
+    /// [`LlmClient::chat_with_tools`], checking the budget before every round.
+    /// The rounds' usage is recorded even if a later one fails.
+    #[allow(dead_code)] // No feature uses tools yet.
+    pub async fn chat_with_tools(
+        &self,
+        request: LlmRequest,
+        handler: &dyn ToolHandler,
+        max_rounds: usize,
+    ) -> Result<LlmResponse, Error> {
+        let mut usage = LlmUsage::default();
+        let response = llm::tool_rounds(request, handler, max_rounds, &mut usage, |request| {
+            async move {
+                self.check_budget().await?;
+                self.client.chat(request).await
+            }
+            .boxed()
+        })
+        .await;
+        if usage != LlmUsage::default() {
+            let model = response.as_ref().map_or(&self.client.config().model, |response| &response.model);
+            self.record(model, usage).await?;
+        }
+        response
+    }
+
+    /// Starts a streamed chat; pass the usage the stream reported to
+    /// [`MeteredLlm::record`] once it's done.
+    pub async fn chat_stream(&self, request: LlmRequest) -> Result<BoxStream<'static, Result<LlmDelta, Error>>, Error> {
+        self.check_budget().await?;
+        self.client.chat_stream(request).await
+    }
+
+    pub async fn record(&self, model: &str, usage: LlmUsage) -> Result<(), Error> {
+        self.db
+            .record_llm_usage(&UsageEntry {
+                guild_id: self.guild_id,
+                user_id: self.user_id,
+                feature: self.feature,
+                model,
+                usage,
+            })
+            .await
+    }
+}
+
+async fn limit(db: &Db, guild_id: u64, key: &str) -> Result<Option<u64>, Error> {
+    Ok(db.get_guild_setting(guild_id, SCOPE, key).await?.and_then(|value| value.trim().parse().ok()))
+}
+
+/// Unix times at which the UTC day and month of `today` started.
+pub fn period_starts(today: NaiveDate) -> (i64, i64) {
+    let start = |day: NaiveDate| day.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp();
+    (start(today), start(today.with_day(1).unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn budgets_run_out_per_day_then_month() {
+        let budget = |used_today, used_this_month| Budget {
+            daily: Some(1000),
+            used_today,
+            monthly: Some(5000),
+            used_this_month,
+        };
+        assert_eq!(budget(999, 4000).exhausted(), None);
+        assert_eq!(budget(1000, 4000).exhausted(), Some("daily"));
+        assert_eq!(budget(10, 5000).exhausted(), Some("monthly"));
+        let unlimited = Budget { daily: None, used_today: 1 << 40, monthly: None, used_this_month: 1 << 40 };
+        assert_eq!(unlimited.exhausted(), None);
+
+        let (today, month) = period_starts(NaiveDate::from_ymd_opt(2024, 3, 15).unwrap());
+        assert_eq!(today, 1_710_460_800);
+        assert_eq!(month, 1_709_251_200);
+    }
+}
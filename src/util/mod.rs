@@ -1,2 +1,6 @@
 pub mod cobe;
+pub mod llm_usage;
+pub mod persona;
 pub mod slugid;
+pub mod streamed_reply;
+pub mod wiki_search;
@@ -0,0 +1,172 @@
+//! Chat mode: with a server's `llm.chat` setting on, mentions are answered by
+//! the language model in the persona of `llm.persona`, with the channel's
+//! latest messages as context. Whenever it can't answer — no LLM configured,
+//! the budget or the user's hourly replies used up, the call failing — the
+//! chatter brain answers as before.
+
+use chrono::Utc;
+use poise::serenity_prelude as serenity;
+use tracing::warn;
+
+use crate::api::llm::{LlmMessage, LlmRequest};
+use crate::commands::config::parse_bool;
+use crate::util::llm_usage::{MeteredLlm, OverBudget};
+use crate::{Data, Error};
+
+const SCOPE: &str = "llm";
+
+/// Feature name chat calls are recorded under.
+const FEATURE: &str = "chat";
+
+const DEFAULT_PERSONA: &str = "You are Shaktool, the friendly sand-digging robot from Super Metroid's Maridia, \
+now a bot in a Discord community about Super Metroid, its randomizers and speedrunning. You're upbeat, a little \
+quirky and love talking about the game.";
+
+/// How the conversation is framed, after the persona.
+const RULES: &str = "You are reading a Discord channel. Messages from others are given as `name: text`. Reply to \
+the last message as yourself, briefly (a few sentences, under 1500 characters), without prefixing your name. \
+Never try to ping anyone.";
+
+const DEFAULT_HISTORY: usize = 15;
+const DEFAULT_REPLIES_PER_HOUR: u64 = 10;
+
+/// Discord's message length limit.
+const MESSAGE_LIMIT: usize = 2000;
+
+/// A server's `llm` chat settings.
+struct ChatSettings {
+    enabled: bool,
+    persona: String,
+    history: usize,
+    replies_per_hour: u64,
+}
+
+impl ChatSettings {
+    async fn load(data: &Data, guild_id: u64) -> Result<Self, Error> {
+        let get = |key| data.db.get_guild_setting(guild_id, SCOPE, key);
+        let enabled = get("chat").await?.as_deref().and_then(parse_bool).unwrap_or(false);
+        Ok(Self {
+            enabled,
+            persona: get("persona").await?.filter(|p| !p.trim().is_empty()).unwrap_or_else(|| DEFAULT_PERSONA.to_string()),
+            history: get("history").await?.and_then(|v| v.trim().parse().ok()).unwrap_or(DEFAULT_HISTORY),
+            replies_per_hour: get("user_replies_per_hour")
+                .await?
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(DEFAULT_REPLIES_PER_HOUR),
+        })
+    }
+}
+
+/// Answers a mention with the language model, or `None` when the chatter
+/// brain should answer instead. `content` is the message without the
+/// mention.
+pub async fn reply(
+    ctx: &serenity::Context,
+    msg: &serenity::Message,
+    data: &Data,
+    content: &str,
+) -> Result<Option<String>, Error> {
+    let Some(guild_id) = msg.guild_id.map(|g| g.get()) else {
+        return Ok(None);
+    };
+    let settings = ChatSettings::load(data, guild_id).await?;
+    if !settings.enabled {
+        return Ok(None);
+    }
+    let Some(client) = &data.llm else {
+        return Ok(None);
+    };
+    let user_id = msg.author.id.get();
+    let hour_ago = Utc::now().timestamp() - 3600;
+    if data.db.llm_calls_since(guild_id, user_id, FEATURE, hour_ago).await? >= settings.replies_per_hour {
+        return Ok(None);
+    }
+
+    let history = if settings.history > 0 {
+        let request = serenity::GetMessages::new().before(msg.id).limit(settings.history as u8);
+        match msg.channel_id.messages(ctx, request).await {
+            Ok(history) => history,
+            Err(e) => {
+                warn!("Could not read the channel for chat context, answering without it: {}", e);
+                Vec::new()
+            }
+        }
+    } else {
+        Vec::new()
+    };
+    let bot_id = ctx.cache.current_user().id;
+    // History comes newest first.
+    let mut lines: Vec<(bool, String)> = history
+        .iter()
+        .rev()
+        .filter(|m| !m.content.trim().is_empty())
+        .map(|m| {
+            if m.author.id == bot_id {
+                (true, m.content.clone())
+            } else {
+                (false, format!("{}: {}", m.author.display_name(), m.content))
+            }
+        })
+        .collect();
+    lines.push((false, format!("{}: {}", msg.author.display_name(), content)));
+
+    let llm = MeteredLlm::new(client, &data.db, FEATURE).for_user(Some(guild_id), user_id);
+    let request = LlmRequest::new(conversation(&settings.persona, lines))
+        .with_temperature(0.8)
+        .with_max_tokens(500);
+    let _ = msg.channel_id.broadcast_typing(ctx).await;
+    match llm.chat(request).await {
+        Ok(response) => {
+            let text = clip(response.text.trim());
+            Ok((!text.is_empty()).then_some(text))
+        }
+        Err(e) if e.downcast_ref::<OverBudget>().is_some() => Ok(None),
+        Err(e) => {
+            warn!("LLM chat failed, the chatter brain answers instead: {}", e);
+            Ok(None)
+        }
+    }
+}
+
+/// The chat as model messages: the persona and rules, then the channel's
+/// lines as `(from the bot, text)`, oldest first. The conversation has to
+/// start with a user turn, so earlier lines of the bot are dropped.
+fn conversation(persona: &str, lines: Vec<(bool, String)>) -> Vec<LlmMessage> {
+    let mut messages = vec![LlmMessage::system(format!("{}\n\n{}", persona.trim(), RULES))];
+    messages.extend(
+        lines
+            .into_iter()
+            .skip_while(|(from_bot, _)| *from_bot)
+            .map(|(from_bot, text)| if from_bot { LlmMessage::assistant(text) } else { LlmMessage::user(text) }),
+    );
+    messages
+}
+
+fn clip(text: &str) -> String {
+    match text.char_indices().nth(MESSAGE_LIMIT) {
+        Some((end, _)) => text[..end].to_string(),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::llm::LlmRole;
+
+    #[test]
+    fn channel_lines_become_a_conversation() {
+        let lines = vec![
+            (true, "Hello!".to_string()),
+            (false, "zoast: any% record?".to_string()),
+            (true, "41:12, by Zoast.".to_string()),
+            (false, "sam: nice".to_string()),
+        ];
+        let messages = conversation("  You are Shaktool. ", lines);
+        let roles: Vec<LlmRole> = messages.iter().map(|m| m.role).collect();
+        assert_eq!(roles, vec![LlmRole::System, LlmRole::User, LlmRole::Assistant, LlmRole::User]);
+        assert!(messages[0].content.starts_with("You are Shaktool.\n\n"));
+        assert_eq!(messages[3].content, "sam: nice");
+        assert_eq!(clip(&"é".repeat(2500)).chars().count(), MESSAGE_LIMIT);
+    }
+}
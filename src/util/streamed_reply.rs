@@ -0,0 +1,146 @@
+//! Shows text as it's being written, e.g. a streamed LLM answer: the reply
+//! is edited at a throttled rate as text arrives and continues in a new
+//! message when it reaches Discord's length limit.
+
+use std::time::Duration;
+
+use futures::{Stream, StreamExt};
+use poise::serenity_prelude as serenity;
+use tokio::time::Instant;
+
+use crate::api::llm::{LlmDelta, LlmUsage};
+use crate::{Context, Error};
+
+/// Discord's message length limit.
+const MESSAGE_LIMIT: usize = 2000;
+
+/// Least time between edits of a message, well within Discord's rate limit
+/// for edits in a channel.
+const EDIT_INTERVAL: Duration = Duration::from_millis(1500);
+
+/// A reply being written. Text is added with [`StreamedReply::push`] and
+/// shown for good with [`StreamedReply::finish`].
+pub struct StreamedReply<'a> {
+    ctx: Context<'a>,
+    /// The message showing `page`, once sent.
+    message: Option<poise::ReplyHandle<'a>>,
+    /// Text of the current message.
+    page: String,
+    /// Whether `page` changed since it was last shown.
+    changed: bool,
+    last_shown: Option<Instant>,
+    text: String,
+}
+
+impl<'a> StreamedReply<'a> {
+    pub fn new(ctx: Context<'a>) -> Self {
+        Self {
+            ctx,
+            message: None,
+            page: String::new(),
+            changed: false,
+            last_shown: None,
+            text: String::new(),
+        }
+    }
+
+    /// Everything pushed so far.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Adds text, showing it if the last edit was long enough ago. Full
+    /// messages are finished and the text continues in a new one.
+    pub async fn push(&mut self, delta: &str) -> Result<(), Error> {
+        self.text.push_str(delta);
+        self.page.push_str(delta);
+        self.changed = true;
+        while self.page.len() > MESSAGE_LIMIT {
+            let cut = split_point(&self.page, MESSAGE_LIMIT);
+            let rest = self.page[cut..].trim_start().to_string();
+            self.page.truncate(cut);
+            self.show().await?;
+            self.message = None;
+            self.page = rest;
+            self.changed = !self.page.is_empty();
+        }
+        if self.last_shown.is_none_or(|shown| shown.elapsed() >= EDIT_INTERVAL) {
+            self.show().await?;
+        }
+        Ok(())
+    }
+
+    /// Adds the text of a streamed chat
+    /// ([`LlmClient::chat_stream`](crate::api::llm::LlmClient::chat_stream))
+    /// as it arrives, keeping `usage` at the tokens it used so far, so they
+    /// can be recorded even if the stream fails. What was shown before the
+    /// stream failed stays.
+    pub async fn push_stream(
+        &mut self,
+        mut deltas: impl Stream<Item = Result<LlmDelta, Error>> + Unpin,
+        usage: &mut LlmUsage,
+    ) -> Result<(), Error> {
+        while let Some(delta) = deltas.next().await {
+            match delta? {
+                LlmDelta::Text(text) => self.push(&text).await?,
+                LlmDelta::Usage(so_far) => *usage = so_far,
+            }
+        }
+        Ok(())
+    }
+
+    /// Shows what's left to show and returns the whole text.
+    pub async fn finish(mut self) -> Result<String, Error> {
+        self.show().await?;
+        Ok(self.text)
+    }
+
+    /// Sends or edits the current message, if its text changed.
+    async fn show(&mut self) -> Result<(), Error> {
+        if !self.changed || self.page.trim().is_empty() {
+            return Ok(());
+        }
+        let reply = poise::CreateReply::default()
+            .content(self.page.clone())
+            .allowed_mentions(serenity::CreateAllowedMentions::new());
+        match &self.message {
+            Some(message) => message.edit(self.ctx, reply).await?,
+            None => self.message = Some(self.ctx.send(reply).await?),
+        }
+        self.changed = false;
+        self.last_shown = Some(Instant::now());
+        Ok(())
+    }
+}
+
+/// Where to end a message of at most `limit` bytes: after the last line
+/// break, else the last space, else wherever the limit falls.
+fn split_point(text: &str, limit: usize) -> usize {
+    if text.len() <= limit {
+        return text.len();
+    }
+    let mut cut = limit;
+    while !text.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    let head = &text[..cut];
+    match head.rfind('\n').or_else(|| head.rfind(' ')) {
+        Some(at) if at > 0 => at,
+        _ => cut,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_end_at_line_breaks_then_spaces() {
+        assert_eq!(split_point("short", 10), 5);
+        assert_eq!(split_point("one two\nthree four", 15), 7);
+        assert_eq!(split_point("one two three four", 15), 13);
+        assert_eq!(split_point("abcdefghij", 4), 4);
+        // Never inside a character.
+        assert_eq!(split_point("ééééé", 5), 4);
+    }
+}